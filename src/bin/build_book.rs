@@ -0,0 +1,66 @@
+// Builds a Polyglot opening book from PGN files
+// Usage: build_book <output.bin> <games.pgn>... [--min-elo N] [--max-ply N] [--min-game-ply N] [--min-games N] [--no-draws]
+
+use std::{env, path::PathBuf, process};
+
+use shallow_red_engine::book::builder::{BookBuilder, BookFilter, PgnResult};
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: build_book <output.bin> <games.pgn>... [--min-elo N] [--max-ply N] [--min-game-ply N] [--min-games N] [--no-draws]"
+    );
+    process::exit(1)
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    match args.next().and_then(|value| value.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{flag} expects a number");
+            usage()
+        }
+    }
+}
+
+fn main() {
+    let mut filter = BookFilter::default();
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--min-elo" => filter.min_rating = Some(parse_value(&mut args, &arg)),
+            "--max-ply" => filter.max_book_ply = parse_value(&mut args, &arg),
+            "--min-game-ply" => filter.min_game_ply = parse_value(&mut args, &arg),
+            "--min-games" => filter.min_move_games = parse_value(&mut args, &arg),
+            "--no-draws" => filter.allowed_results.retain(|result| *result != PgnResult::Draw),
+            "-h" | "--help" => usage(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() < 2 {
+        usage();
+    }
+    let output = paths.remove(0);
+
+    let mut builder = BookBuilder::new(filter);
+    for path in &paths {
+        if let Err(e) = builder.add_pgn_file(path) {
+            eprintln!("Could not read {}: {e}", path.display());
+            process::exit(1);
+        }
+    }
+
+    match builder.write(&output) {
+        Ok(num_entries) => println!(
+            "Wrote {num_entries} entries to {} ({} games used, {} skipped)",
+            output.display(),
+            builder.games_used,
+            builder.games_skipped
+        ),
+        Err(e) => {
+            eprintln!("Could not write {}: {e}", output.display());
+            process::exit(1);
+        }
+    }
+}
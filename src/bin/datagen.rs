@@ -0,0 +1,105 @@
+// Generates training data from self-play games
+// Usage: datagen <output> [--games N] [--nodes N] [--depth N] [--threads N] [--seed N] [--opening-plies N] [--max-plies N] [--params <file>] [--config <file>]
+// Each quiet position goes on its own line as `<fen> | <score> | <best move> | [<result>]`
+
+use std::{
+    env,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::Instant,
+};
+
+use shallow_red_engine::eval_params::EvalParams;
+use shallow_red_engine::search_config::SearchConfig;
+use shallow_red_engine::tuning::datagen::{generate, DataOptions};
+use shallow_red_engine::tuning::selfplay::Player;
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: datagen <output> [--games N] [--nodes N] [--depth N] [--threads N] [--seed N] [--opening-plies N] [--max-plies N] [--params <file>] [--config <file>]"
+    );
+    process::exit(1)
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    match args.next().and_then(|value| value.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{flag} expects a value");
+            usage()
+        }
+    }
+}
+
+fn load_or_exit<T>(path: &Path, load: impl FnOnce(&Path) -> std::io::Result<T>) -> T {
+    load(path).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", path.display(), e);
+        process::exit(1)
+    })
+}
+
+fn main() {
+    let mut options = DataOptions::default();
+    let mut nodes = None;
+    let mut player = Player::default();
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => options.games = parse_value(&mut args, &arg),
+            "--nodes" => nodes = Some(parse_value(&mut args, &arg)),
+            "--depth" => options.limits.depth_limit = Some(parse_value(&mut args, &arg)),
+            "--threads" => options.threads = parse_value(&mut args, &arg),
+            "--seed" => options.seed = parse_value(&mut args, &arg),
+            "--opening-plies" => options.opening_plies = parse_value(&mut args, &arg),
+            "--max-plies" => options.limits.max_plies = parse_value(&mut args, &arg),
+            "--params" => {
+                let path: PathBuf = parse_value(&mut args, &arg);
+                player.eval_params = Arc::new(load_or_exit(&path, EvalParams::load));
+            }
+            "--config" => {
+                let path: PathBuf = parse_value(&mut args, &arg);
+                player.search_config = Arc::new(load_or_exit(&path, SearchConfig::load));
+            }
+            "-h" | "--help" => usage(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [output_path] = paths.as_slice() else {
+        usage()
+    };
+    // A depth on its own replaces the default node limit
+    if nodes.is_some() || options.limits.depth_limit.is_some() {
+        options.limits.node_limit = nodes;
+    }
+
+    let file = File::create(output_path).unwrap_or_else(|e| {
+        eprintln!("Could not write {}: {}", output_path.display(), e);
+        process::exit(1)
+    });
+    let mut output = BufWriter::new(file);
+
+    let t_start = Instant::now();
+    let result = generate(&player, &options, &mut output, |games, positions| {
+        if games % 10 == 0 || games == options.games {
+            println!(
+                "Game {}/{}, {} positions ({:.0}s)",
+                games,
+                options.games,
+                positions,
+                t_start.elapsed().as_secs_f32()
+            );
+        }
+    });
+    match result {
+        Ok(positions) => println!("Wrote {} positions to {}", positions, output_path.display()),
+        Err(e) => {
+            eprintln!("Could not write {}: {}", output_path.display(), e);
+            process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// Prints the static evaluation of a position broken down into its terms
+// Usage: eval_trace "<fen>" [--json]
+
+use std::{env, process, str::FromStr};
+
+use chess::Board;
+use shallow_red_engine::evaluation::evaluate_trace;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let json = args.iter().any(|arg| arg == "--json");
+    let Some(fen) = args.iter().find(|arg| *arg != "--json") else {
+        eprintln!("usage: eval_trace \"<fen>\" [--json]");
+        process::exit(1)
+    };
+    let board = match Board::from_str(fen) {
+        Ok(board) => board,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", fen, e);
+            process::exit(1)
+        }
+    };
+
+    let trace = evaluate_trace(board);
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&trace).unwrap()),
+        false => println!("{}", trace),
+    }
+}
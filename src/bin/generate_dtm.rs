@@ -0,0 +1,40 @@
+// Generates distance to mate tables for small endgames and saves them to a directory
+// Usage: generate_dtm <output dir> <signature>...   e.g. generate_dtm tables KQvK KRvK KPvK
+
+use std::{env, path::PathBuf, process, time::Instant};
+
+use shallow_red_engine::retrograde::{DtmTables, MaterialSignature};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (Some(output), signatures) = (args.next().map(PathBuf::from), args.collect::<Vec<String>>()) else {
+        eprintln!("usage: generate_dtm <output dir> <signature>...");
+        process::exit(1)
+    };
+    if signatures.is_empty() {
+        eprintln!("usage: generate_dtm <output dir> <signature>...");
+        process::exit(1)
+    }
+
+    let mut tables = DtmTables::new();
+    for signature in &signatures {
+        let t_start = Instant::now();
+        let result = signature
+            .parse::<MaterialSignature>()
+            .map_err(|e| e.to_string())
+            .and_then(|signature| tables.generate(&signature).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => println!("Generated {} in {:.1}s", signature, t_start.elapsed().as_secs_f32()),
+            Err(e) => {
+                eprintln!("Could not generate {}: {}", signature, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = tables.save(&output) {
+        eprintln!("Could not write tables to {}: {}", output.display(), e);
+        process::exit(1);
+    }
+    println!("Wrote {} to {}", tables.signatures().join(", "), output.display());
+}
@@ -0,0 +1,104 @@
+// Tunes the search margins and extensions with SPSA self-play
+// Usage: spsa_tune <output> <log.csv> [--config <file>] [--iterations N] [--games N] [--nodes N] [--threads N] [--seed N]
+// The output is written after every iteration, as TOML if it ends in .toml and JSON otherwise
+
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+use shallow_red_engine::search_config::SearchConfig;
+use shallow_red_engine::tuning::spsa::{default_params, Spsa, SpsaOptions};
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: spsa_tune <output> <log.csv> [--config <file>] [--iterations N] [--games N] [--nodes N] [--threads N] [--seed N]"
+    );
+    process::exit(1)
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    match args.next().and_then(|value| value.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{flag} expects a value");
+            usage()
+        }
+    }
+}
+
+fn write_line(log: &mut File, path: &Path, line: &str) {
+    if let Err(e) = writeln!(log, "{line}").and_then(|_| log.flush()) {
+        eprintln!("Could not write {}: {}", path.display(), e);
+        process::exit(1)
+    }
+}
+
+fn main() {
+    let mut options = SpsaOptions::default();
+    let mut config_path: Option<PathBuf> = None;
+    let mut seed: Option<u64> = None;
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(parse_value(&mut args, &arg)),
+            "--iterations" => options.iterations = parse_value(&mut args, &arg),
+            "--games" => options.game_pairs = parse_value(&mut args, &arg),
+            "--nodes" => options.limits.node_limit = Some(parse_value(&mut args, &arg)),
+            "--threads" => options.threads = parse_value(&mut args, &arg),
+            "--seed" => seed = Some(parse_value(&mut args, &arg)),
+            "-h" | "--help" => usage(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [output, log_path] = paths.as_slice() else {
+        usage()
+    };
+
+    // Start from the given settings, or the built in ones
+    let config = match &config_path {
+        Some(path) => SearchConfig::load(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            process::exit(1)
+        }),
+        None => SearchConfig::default(),
+    };
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut log = File::create(log_path).unwrap_or_else(|e| {
+        eprintln!("Could not write {}: {}", log_path.display(), e);
+        process::exit(1)
+    });
+
+    let params = default_params(&config);
+    let mut spsa = Spsa::new(config, params, options);
+    write_line(&mut log, log_path, &spsa.csv_header());
+
+    let t_start = Instant::now();
+    while spsa.iteration < spsa.options.iterations {
+        let score = spsa.step(&mut rng);
+        write_line(&mut log, log_path, &spsa.csv_row(&score));
+        println!(
+            "Iteration {} +{} -{} ={} ({:.0}s)",
+            spsa.iteration,
+            score.wins,
+            score.losses,
+            score.draws,
+            t_start.elapsed().as_secs_f32()
+        );
+        if let Err(e) = spsa.config().save(output) {
+            eprintln!("Could not write {}: {}", output.display(), e);
+            process::exit(1)
+        }
+    }
+    println!("Wrote {}", output.display());
+}
@@ -0,0 +1,96 @@
+// Tunes the evaluation weights against positions labelled with game results
+// Usage: texel_tune <positions> <output> [--params <file>] [--passes N] [--k K] [--only <prefix>]...
+// The output is written after every pass, as TOML if it ends in .toml and JSON otherwise
+
+use std::{env, path::PathBuf, process, time::Instant};
+
+use shallow_red_engine::eval_params::EvalParams;
+use shallow_red_engine::tuning::texel::{error, fit_k, load_positions, tune, TuneOptions};
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: texel_tune <positions> <output> [--params <file>] [--passes N] [--k K] [--only <prefix>]..."
+    );
+    process::exit(1)
+}
+
+fn parse_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    match args.next().and_then(|value| value.parse().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{flag} expects a value");
+            usage()
+        }
+    }
+}
+
+fn main() {
+    let mut options = TuneOptions::default();
+    let mut k = None;
+    let mut params_path: Option<PathBuf> = None;
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--params" => params_path = Some(parse_value(&mut args, &arg)),
+            "--passes" => options.max_passes = parse_value(&mut args, &arg),
+            "--k" => k = Some(parse_value(&mut args, &arg)),
+            "--only" => options.only.push(parse_value(&mut args, &arg)),
+            "-h" | "--help" => usage(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [positions_path, output] = paths.as_slice() else {
+        usage()
+    };
+
+    // Start from the given parameters, or the built in ones
+    let params = match &params_path {
+        Some(path) => EvalParams::load(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            process::exit(1)
+        }),
+        None => EvalParams::default(),
+    };
+
+    let t_start = Instant::now();
+    let positions = load_positions(positions_path, &params).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", positions_path.display(), e);
+        process::exit(1)
+    });
+    if positions.is_empty() {
+        eprintln!("No positions in {}", positions_path.display());
+        process::exit(1)
+    }
+    println!(
+        "Loaded {} positions in {:.1}s",
+        positions.len(),
+        t_start.elapsed().as_secs_f32()
+    );
+
+    options.k = k.unwrap_or_else(|| fit_k(&positions, &params));
+    println!(
+        "K {:.3}, starting error {:.6}",
+        options.k,
+        error(&positions, &params, options.k)
+    );
+
+    let tuned = tune(&positions, &params, &options, |pass, error, params| {
+        println!(
+            "Pass {} error {:.6} ({:.0}s)",
+            pass,
+            error,
+            t_start.elapsed().as_secs_f32()
+        );
+        if let Err(e) = params.save(output) {
+            eprintln!("Could not write {}: {}", output.display(), e);
+            process::exit(1)
+        }
+    });
+    if let Err(e) = tuned.save(output) {
+        eprintln!("Could not write {}: {}", output.display(), e);
+        process::exit(1)
+    }
+    println!("Wrote {}", output.display());
+}
@@ -0,0 +1,489 @@
+// Builds Polyglot opening books from PGN game collections
+// Games are filtered by result, rating and length, then every (position, move) pair within the
+// first few plies is counted and weighted by how well it scored for the side that played it
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use chess::{Board, ChessMove, Color};
+
+use super::polyglot::{encode_move, BookEntry};
+use super::zobrist::polyglot_key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgnResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    Unknown, // "*", game still in progress or abandoned
+}
+
+impl PgnResult {
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(PgnResult::WhiteWin),
+            "0-1" => Some(PgnResult::BlackWin),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub headers: HashMap<String, String>,
+    pub moves: Vec<String>, // SAN tokens of the main line
+    pub result: PgnResult,
+}
+
+impl PgnGame {
+    fn rating(&self, header: &str) -> Option<u32> {
+        self.headers.get(header).and_then(|elo| elo.parse().ok())
+    }
+
+    pub fn white_elo(&self) -> Option<u32> {
+        self.rating("WhiteElo")
+    }
+
+    pub fn black_elo(&self) -> Option<u32> {
+        self.rating("BlackElo")
+    }
+}
+
+fn parse_header(line: &str) -> Option<(String, String)> {
+    // [Key "Value"]
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (key, value) = inner.split_once(' ')?;
+    Some((key.to_string(), value.trim().trim_matches('"').to_string()))
+}
+
+fn strip_movetext(movetext: &str) -> String {
+    // Removes comments, variations and NAGs, leaving the main line tokens
+    let mut stripped = String::with_capacity(movetext.len());
+    let mut comment = false;
+    let mut line_comment = false;
+    let mut variation_depth = 0;
+    for c in movetext.chars() {
+        match c {
+            _ if line_comment => {
+                if c == '\n' {
+                    line_comment = false;
+                    stripped.push(' ');
+                }
+            }
+            '}' if comment => comment = false,
+            _ if comment => {}
+            '{' => comment = true,
+            ';' => line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+fn normalise_san(token: &str) -> String {
+    // The chess crate's SAN parser doesn't accept annotations, "=" promotions or zeros for castling
+    token
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('=', "")
+        .replace("0-0-0", "O-O-O")
+        .replace("0-0", "O-O")
+}
+
+fn strip_move_number(token: &str) -> &str {
+    // Drop move numbers ("12." and "12...") which may be glued to the move ("12.e4"), digits
+    // without a following dot are part of the move itself ("0-0")
+    let number_end = token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(token.len());
+    match token[number_end..].strip_prefix('.') {
+        Some(rest) if number_end > 0 => rest.trim_start_matches('.'),
+        _ => token,
+    }
+}
+
+fn parse_movetext(movetext: &str) -> (Vec<String>, Option<PgnResult>) {
+    let mut moves = Vec::new();
+    let mut result = None;
+    for token in strip_movetext(movetext).split_whitespace() {
+        if let Some(game_result) = PgnResult::from_token(token) {
+            result = Some(game_result);
+            continue;
+        }
+        if token.starts_with('$') {
+            continue; // NAG
+        }
+        let san = strip_move_number(token);
+        if !san.is_empty() {
+            moves.push(normalise_san(san));
+        }
+    }
+    (moves, result)
+}
+
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    // Parses every game in a PGN collection
+    let mut games = Vec::new();
+    let mut headers = HashMap::new();
+    let mut movetext = String::new();
+
+    let mut finish_game = |headers: &mut HashMap<String, String>, movetext: &mut String| {
+        if movetext.trim().is_empty() {
+            return;
+        }
+        let (moves, movetext_result) = parse_movetext(movetext);
+        let result = headers
+            .get("Result")
+            .and_then(|result| PgnResult::from_token(result))
+            .or(movetext_result)
+            .unwrap_or(PgnResult::Unknown);
+        games.push(PgnGame {
+            headers: std::mem::take(headers),
+            moves,
+            result,
+        });
+        movetext.clear();
+    };
+
+    for line in text.lines() {
+        if line.trim_start().starts_with('[') && !movetext.trim().is_empty() {
+            // Headers after movetext start the next game
+            finish_game(&mut headers, &mut movetext);
+        }
+        if let Some((key, value)) = parse_header(line).filter(|_| movetext.trim().is_empty()) {
+            headers.insert(key, value);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    finish_game(&mut headers, &mut movetext);
+
+    games
+}
+
+#[derive(Debug, Clone)]
+pub struct BookFilter {
+    pub allowed_results: Vec<PgnResult>, // Games with other results are skipped
+    pub min_rating: Option<u32>,          // Both players must be rated at least this (unrated games are skipped)
+    pub min_game_ply: usize,              // Skip games shorter than this (e.g. quick forfeits)
+    pub max_book_ply: usize,              // Only positions this early in the game go into the book
+    pub min_move_games: u32,              // Moves played fewer times than this are left out
+}
+
+impl Default for BookFilter {
+    fn default() -> Self {
+        Self {
+            allowed_results: vec![PgnResult::WhiteWin, PgnResult::BlackWin, PgnResult::Draw],
+            min_rating: None,
+            min_game_ply: 10,
+            max_book_ply: 20,
+            min_move_games: 2,
+        }
+    }
+}
+
+impl BookFilter {
+    pub fn accepts(&self, game: &PgnGame) -> bool {
+        let rated = match self.min_rating {
+            Some(min_rating) => {
+                game.white_elo().unwrap_or(0) >= min_rating
+                    && game.black_elo().unwrap_or(0) >= min_rating
+            }
+            None => true,
+        };
+        rated
+            && self.allowed_results.contains(&game.result)
+            && game.moves.len() >= self.min_game_ply
+    }
+}
+
+// Results of a move from the perspective of the side that played it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        // Score with draws counted as half a win
+        (self.wins as f32 + 0.5 * self.draws as f32) / self.games().max(1) as f32
+    }
+
+    fn points(&self) -> u32 {
+        // Polyglot convention, two points for a win and one for a draw
+        2 * self.wins + self.draws
+    }
+}
+
+#[derive(Default)]
+pub struct BookBuilder {
+    pub filter: BookFilter,
+    stats: HashMap<(u64, u16), MoveStats>,
+    pub games_used: u32,
+    pub games_skipped: u32,
+}
+
+impl BookBuilder {
+    pub fn new(filter: BookFilter) -> Self {
+        Self {
+            filter,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_game(&mut self, game: &PgnGame) {
+        if !self.filter.accepts(game) {
+            self.games_skipped += 1;
+            return;
+        }
+
+        let fen_start = game.headers.get("FEN").and_then(|fen| fen.parse::<Board>().ok());
+        let mut board = fen_start.unwrap_or_default();
+        for san in game.moves.iter().take(self.filter.max_book_ply) {
+            let mve = match ChessMove::from_san(&board, san) {
+                Ok(mve) => mve,
+                Err(_) => break, // Stop at anything we can't read, the earlier moves are still good
+            };
+
+            let entry = self
+                .stats
+                .entry((polyglot_key(&board), encode_move(&board, mve)))
+                .or_default();
+            match (game.result, board.side_to_move()) {
+                (PgnResult::WhiteWin, Color::White) | (PgnResult::BlackWin, Color::Black) => {
+                    entry.wins += 1
+                }
+                (PgnResult::WhiteWin, Color::Black) | (PgnResult::BlackWin, Color::White) => {
+                    entry.losses += 1
+                }
+                (PgnResult::Draw, _) | (PgnResult::Unknown, _) => entry.draws += 1,
+            }
+
+            board = board.make_move_new(mve);
+        }
+        self.games_used += 1;
+    }
+
+    pub fn add_pgn(&mut self, text: &str) {
+        for game in parse_pgn(text) {
+            self.add_game(&game);
+        }
+    }
+
+    pub fn add_pgn_file(&mut self, path: &Path) -> io::Result<()> {
+        // PGN files are not always valid UTF-8 (e.g. Latin-1 player names)
+        let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+        self.add_pgn(&text);
+        Ok(())
+    }
+
+    pub fn move_stats(&self, board: &Board, mve: ChessMove) -> Option<MoveStats> {
+        self.stats
+            .get(&(polyglot_key(board), encode_move(board, mve)))
+            .copied()
+    }
+
+    pub fn entries(&self) -> Vec<BookEntry> {
+        // Book entries sorted by key then descending weight, weights scaled to fit in a u16
+        let kept: Vec<(&(u64, u16), &MoveStats)> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.games() >= self.filter.min_move_games && stats.points() > 0)
+            .collect();
+        let max_points = kept.iter().map(|(_, stats)| stats.points()).max().unwrap_or(1);
+        let scale = (u16::MAX as f64 / max_points as f64).min(1.0);
+
+        let mut entries: Vec<BookEntry> = kept
+            .into_iter()
+            .map(|((key, raw_move), stats)| BookEntry {
+                key: *key,
+                raw_move: *raw_move,
+                weight: ((stats.points() as f64 * scale) as u16).max(1),
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            a.key
+                .cmp(&b.key)
+                .then(b.weight.cmp(&a.weight))
+                .then(a.raw_move.cmp(&b.raw_move))
+        });
+        entries
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<usize> {
+        // Writes the Polyglot book, returning the number of entries
+        let entries = self.entries();
+        let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+        fs::write(path, bytes)?;
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove, Piece, Square};
+
+    use super::*;
+    use crate::book::polyglot::{BookSelection, OpeningBook};
+
+    const PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[WhiteElo "2000"]
+[BlackElo "1900"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {main line} Nc6 (2... d6 3. d4) 3. Bb5 a6 $1 4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event "Test"]
+[WhiteElo "1500"]
+[BlackElo "2100"]
+[Result "0-1"]
+
+1.e4 c5 2.Nf3 d6 3.d4 cxd4 4.Nxd4 Nf6 5.Nc3 a6 0-1
+
+[Event "Test"]
+[WhiteElo "2200"]
+[BlackElo "2200"]
+[Result "1/2-1/2"]
+
+1. d4 d5 2. c4 e6 3. Nc3 Nf6 4. Bg5 Be7 5. e3 O-O 1/2-1/2
+"#;
+
+    #[test]
+    fn test_parse_pgn() {
+        let games = parse_pgn(PGN);
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].result, PgnResult::WhiteWin);
+        assert_eq!(games[0].white_elo(), Some(2000));
+        assert_eq!(games[0].headers.get("White").unwrap(), "A");
+        // The variation, comment and NAG are dropped
+        assert_eq!(
+            games[0].moves,
+            vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7"]
+        );
+        assert_eq!(games[1].moves[0], "e4");
+        assert_eq!(games[2].result, PgnResult::Draw);
+    }
+
+    #[test]
+    fn test_castling_with_zeros() {
+        // Zeros for castling are not move numbers, with or without a space after the number
+        let (moves, result) = parse_movetext("4.d3 d6 5. 0-0 Nf6 12.0-0-0 0-0 *");
+        assert_eq!(moves, vec!["d3", "d6", "O-O", "Nf6", "O-O-O", "O-O"]);
+        assert_eq!(result, Some(PgnResult::Unknown));
+        assert_eq!(strip_move_number("12...0-0"), "0-0");
+        assert_eq!(strip_move_number("12."), "");
+    }
+
+    #[test]
+    fn test_normalise_san() {
+        assert_eq!(normalise_san("e8=Q+"), "e8Q");
+        assert_eq!(normalise_san("0-0-0"), "O-O-O");
+        assert_eq!(normalise_san("O-O#"), "O-O");
+        assert_eq!(normalise_san("Nxe5!?"), "Nxe5");
+
+        let board = Board::from_str("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            ChessMove::from_san(&board, &normalise_san("e8=N")).unwrap(),
+            ChessMove::new(Square::E7, Square::E8, Some(Piece::Knight))
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let games = parse_pgn(PGN);
+        let filter = BookFilter {
+            min_rating: Some(1800),
+            ..Default::default()
+        };
+        assert!(filter.accepts(&games[0]));
+        assert!(!filter.accepts(&games[1])); // White is rated 1500
+
+        let filter = BookFilter {
+            allowed_results: vec![PgnResult::WhiteWin, PgnResult::BlackWin],
+            ..Default::default()
+        };
+        assert!(!filter.accepts(&games[2])); // Draw
+
+        let filter = BookFilter {
+            min_game_ply: 11,
+            ..Default::default()
+        };
+        assert!(!filter.accepts(&games[0])); // Only 10 plies
+    }
+
+    #[test]
+    fn test_move_stats() {
+        let mut builder = BookBuilder::new(BookFilter {
+            min_move_games: 1,
+            ..Default::default()
+        });
+        builder.add_pgn(PGN);
+        assert_eq!(builder.games_used, 3);
+
+        let board = Board::default();
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let stats = builder.move_stats(&board, e4).unwrap();
+        assert_eq!(stats, MoveStats { wins: 1, draws: 0, losses: 1 });
+        assert_eq!(stats.win_rate(), 0.5);
+
+        // Black's reply is scored for black
+        let e5 = ChessMove::new(Square::E7, Square::E5, None);
+        let stats = builder.move_stats(&board.make_move_new(e4), e5).unwrap();
+        assert_eq!(stats, MoveStats { wins: 0, draws: 0, losses: 1 });
+    }
+
+    #[test]
+    fn test_written_book() {
+        let mut builder = BookBuilder::new(BookFilter {
+            min_move_games: 1,
+            ..Default::default()
+        });
+        builder.add_pgn(PGN);
+
+        let book_path = std::env::temp_dir().join("shallow_red_test_built_book.bin");
+        let num_entries = builder.write(&book_path).unwrap();
+        let book = OpeningBook::open(&book_path).unwrap();
+        std::fs::remove_file(book_path).unwrap();
+        assert_eq!(book.len(), num_entries);
+
+        // e4 scored 1/2 from two games, d4 1/2 from one, so e4 has the most points
+        let board = Board::default();
+        assert_eq!(book.probe(&board).len(), 2);
+        assert_eq!(
+            book.pick_move(&board, BookSelection::BestOnly),
+            Some(ChessMove::new(Square::E2, Square::E4, None))
+        );
+
+        // Black lost with e5, which scores no points and is left out
+        let after_e4 = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+        assert_eq!(
+            book.pick_move(&after_e4, BookSelection::BestOnly),
+            Some(ChessMove::new(Square::C7, Square::C5, None))
+        );
+
+        // Castling is written in Polyglot form and read back as a legal move
+        let mut board = Board::default();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6"] {
+            board = board.make_move_new(ChessMove::from_san(&board, san).unwrap());
+        }
+        assert_eq!(
+            book.pick_move(&board, BookSelection::BestOnly),
+            Some(ChessMove::new(Square::E1, Square::G1, None))
+        );
+    }
+}
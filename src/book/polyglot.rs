@@ -0,0 +1,299 @@
+// Reading and probing Polyglot (.bin) opening books
+// Each entry is 16 big endian bytes: key (u64), move (u16), weight (u16), learn (u32), sorted by key
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use chess::{Board, ChessMove, File, MoveGen, Piece, Rank, Square};
+use rand::Rng;
+
+use super::zobrist::polyglot_key;
+
+pub const ENTRY_SIZE: usize = 16;
+
+// How to choose between the book moves for a position
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSelection {
+    BestOnly,       // Always play the highest weighted move
+    WeightedRandom, // Pick randomly, proportional to the move weights
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+pub struct OpeningBook {
+    entries: Vec<BookEntry>, // Sorted by key
+}
+
+impl OpeningBook {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Polyglot book size is not a multiple of the entry size",
+            ));
+        }
+        Ok(Self::from_entries(
+            bytes.chunks_exact(ENTRY_SIZE).map(BookEntry::from_bytes).collect(),
+        ))
+    }
+
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        // Entries are sorted by key (and by descending weight within a key, as Polyglot writes them)
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn probe(&self, board: &Board) -> Vec<(ChessMove, u16)> {
+        // Returns the legal book moves for a board along with their weights
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| decode_move(board, entry.raw_move).map(|mve| (mve, entry.weight)))
+            .collect()
+    }
+
+    pub fn pick_move(&self, board: &Board, selection: BookSelection) -> Option<ChessMove> {
+        // Zero weight entries are in the book but should not be played
+        let candidates: Vec<(ChessMove, u16)> = self
+            .probe(board)
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+
+        match selection {
+            BookSelection::BestOnly => candidates
+                .iter()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(mve, _)| *mve),
+            BookSelection::WeightedRandom => {
+                let total: u32 = candidates.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = rand::thread_rng().gen_range(0..total);
+                for (mve, weight) in &candidates {
+                    if pick < *weight as u32 {
+                        return Some(*mve);
+                    }
+                    pick -= *weight as u32;
+                }
+                None
+            }
+        }
+    }
+}
+
+fn promotion_piece(code: u16) -> Option<Piece> {
+    match code {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    }
+}
+
+fn promotion_code(piece: Option<Piece>) -> u16 {
+    match piece {
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(Piece::Queen) => 4,
+        _ => 0,
+    }
+}
+
+fn castle_destination(board: &Board, source: Square, dest: Square) -> Square {
+    // Polyglot writes castling as the king capturing its own rook (e1h1), the chess crate as e1g1
+    let is_king = board.piece_on(source) == Some(Piece::King);
+    let own_rook = board.piece_on(dest) == Some(Piece::Rook)
+        && board.color_on(dest) == Some(board.side_to_move());
+    if !is_king || !own_rook || source.get_file() != File::E {
+        return dest;
+    }
+    match dest.get_file() {
+        File::H => Square::make_square(dest.get_rank(), File::G),
+        File::A => Square::make_square(dest.get_rank(), File::C),
+        _ => dest,
+    }
+}
+
+pub fn decode_move(board: &Board, raw_move: u16) -> Option<ChessMove> {
+    // Returns the move if it is legal on the board
+    let square = |file_bits: u16, rank_bits: u16| {
+        Square::make_square(
+            Rank::from_index(rank_bits as usize),
+            File::from_index(file_bits as usize),
+        )
+    };
+    let dest = square(raw_move & 7, (raw_move >> 3) & 7);
+    let source = square((raw_move >> 6) & 7, (raw_move >> 9) & 7);
+    let promotion = promotion_piece((raw_move >> 12) & 7);
+
+    let mve = ChessMove::new(source, castle_destination(board, source, dest), promotion);
+    MoveGen::new_legal(board).find(|legal| *legal == mve)
+}
+
+pub fn encode_move(board: &Board, mve: ChessMove) -> u16 {
+    // Inverse of decode_move, castling is written as the king capturing its rook
+    let source = mve.get_source();
+    let mut dest = mve.get_dest();
+    let is_castle = board.piece_on(source) == Some(Piece::King)
+        && source.get_file() == File::E
+        && (dest.get_file() == File::G || dest.get_file() == File::C);
+    if is_castle {
+        let rook_file = if dest.get_file() == File::G { File::H } else { File::A };
+        dest = Square::make_square(dest.get_rank(), rook_file);
+    }
+
+    dest.get_file().to_index() as u16
+        | (dest.get_rank().to_index() as u16) << 3
+        | (source.get_file().to_index() as u16) << 6
+        | (source.get_rank().to_index() as u16) << 9
+        | promotion_code(mve.get_promotion()) << 12
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove, Piece, Square};
+
+    use super::*;
+    use crate::book::zobrist::polyglot_key;
+
+    fn entry(board: &Board, mve: ChessMove, weight: u16) -> BookEntry {
+        BookEntry {
+            key: polyglot_key(board),
+            raw_move: encode_move(board, mve),
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn test_entry_roundtrip() {
+        let entry = BookEntry {
+            key: 0x463b96181691fc9c,
+            raw_move: 0x031c,
+            weight: 12,
+            learn: 7,
+        };
+        assert_eq!(BookEntry::from_bytes(&entry.to_bytes()), entry);
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let board = Board::default();
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        // e2e4 in Polyglot: to e4 (file 4, rank 3), from e2 (file 4, rank 1)
+        assert_eq!(encode_move(&board, e2e4), 4 | 3 << 3 | 4 << 6 | 1 << 9);
+        assert_eq!(decode_move(&board, encode_move(&board, e2e4)), Some(e2e4));
+    }
+
+    #[test]
+    fn test_castle_encoding() {
+        let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let short = ChessMove::new(Square::E1, Square::G1, None);
+        let long = ChessMove::new(Square::E1, Square::C1, None);
+        // Written as king takes rook
+        assert_eq!(encode_move(&board, short) & 0x3f, Square::H1.to_index() as u16);
+        assert_eq!(decode_move(&board, encode_move(&board, short)), Some(short));
+        assert_eq!(decode_move(&board, encode_move(&board, long)), Some(long));
+    }
+
+    #[test]
+    fn test_promotion_encoding() {
+        let board = Board::from_str("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+        let promote = ChessMove::new(Square::E7, Square::E8, Some(Piece::Knight));
+        assert_eq!(encode_move(&board, promote) >> 12, 1);
+        assert_eq!(decode_move(&board, encode_move(&board, promote)), Some(promote));
+    }
+
+    #[test]
+    fn test_illegal_move_ignored() {
+        let board = Board::default();
+        let illegal = ChessMove::new(Square::E2, Square::E5, None);
+        assert_eq!(decode_move(&board, encode_move(&board, illegal)), None);
+    }
+
+    #[test]
+    fn test_probe_and_pick() {
+        let board = Board::default();
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let d2d4 = ChessMove::new(Square::D2, Square::D4, None);
+        let g1f3 = ChessMove::new(Square::G1, Square::F3, None);
+        let other = board.make_move_new(e2e4);
+
+        let entries = [
+            entry(&other, ChessMove::new(Square::E7, Square::E5, None), 5),
+            entry(&board, d2d4, 10),
+            entry(&board, e2e4, 30),
+            entry(&board, g1f3, 0),
+        ];
+        let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+        let book = OpeningBook::from_bytes(&bytes).unwrap();
+
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.probe(&board).len(), 3);
+        assert_eq!(book.pick_move(&board, BookSelection::BestOnly), Some(e2e4));
+
+        // Zero weight moves are never picked
+        for _ in 0..20 {
+            let mve = book.pick_move(&board, BookSelection::WeightedRandom).unwrap();
+            assert!(mve == e2e4 || mve == d2d4);
+        }
+
+        // Out of book
+        let out_of_book = other.make_move_new(ChessMove::new(Square::A7, Square::A6, None));
+        assert_eq!(book.pick_move(&out_of_book, BookSelection::BestOnly), None);
+    }
+
+    #[test]
+    fn test_bad_size() {
+        assert!(OpeningBook::from_bytes(&[0; 17]).is_err());
+    }
+}
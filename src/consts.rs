@@ -1,16 +1,60 @@
 
 
+// Defaults for the search settings are read into SearchConfig, the search itself reads them from there
+
 use std::time::Duration;
 
+use crate::utils::search_interface::InternalIterativeMode;
+
 use crate::utils::common::Eval;
 
-pub(crate) const UNDESIRABLE_EVAL_BLACK: Eval = Eval{score: i16::max_value() - 1};
-pub(crate) const UNDESIRABLE_EVAL_WHITE: Eval = Eval{score: i16::min_value() + 1};
+pub(crate) const UNDESIRABLE_EVAL_BLACK: Eval = Eval{score: i16::MAX - 1};
+pub(crate) const UNDESIRABLE_EVAL_WHITE: Eval = Eval{score: i16::MIN + 1};
 pub(crate) const DEPTH_LIM: u8 = 20;
 pub(crate) const EXTENSION_LIM: u8 = 4; // how many extra moves to do (should be devisable by 2)
+pub(crate) const ONE_PLY: u8 = 4; // Extensions are counted in fractions of a ply, this many make up a whole ply
 pub(crate) const QUIESENT_LIM: u8 = 4;
 pub(crate) static DEBUG_MODE: bool = false;
 pub(crate) static SEARCH_INFO: bool = true;
 pub(crate) const MAX_DEPTH_TO_CHECK_TIME: u8 = 5;
 pub(crate) const USE_CACHE: bool = true; // Flag to enable/disable move caching
-pub(crate) const TT_MAXTIME_LOOKUP: Duration = Duration::from_micros(1);
\ No newline at end of file
+pub(crate) const TT_MAXTIME_LOOKUP: Duration = Duration::from_micros(1);
+pub(crate) const MATE_THRESHOLD: i16 = i16::MAX - 1000; // Scores beyond this (either sign) are treated as forced mates
+pub(crate) const KPK_WIN_BONUS: i16 = 400; // Added for the pawn side in won KPK positions, kept below a queen so we still promote
+pub(crate) const TB_WIN_SCORE: i16 = 20000; // Tablebase wins, above any static eval but below mate scores
+
+// Forward pruning near the horizon, margins are indexed by remaining depth (in plies)
+pub(crate) const USE_FUTILITY_PRUNING: bool = true; // Flag to enable/disable frontier futility pruning of quiet moves
+pub(crate) const FUTILITY_MARGINS: [i16; 3] = [0, 200, 500];
+pub(crate) const USE_REVERSE_FUTILITY_PRUNING: bool = true; // Flag to enable/disable static null move pruning
+pub(crate) const REVERSE_FUTILITY_DEPTH: u8 = 3; // Deepest remaining depth where static null move pruning applies
+pub(crate) const REVERSE_FUTILITY_MARGIN: i16 = 120; // Margin per ply of remaining depth
+pub(crate) const USE_RAZORING: bool = true; // Flag to enable/disable razoring into the quiescent search
+pub(crate) const RAZOR_MARGINS: [i16; 3] = [0, 300, 550];
+
+// Internal iterative deepening/reductions at nodes without a hash move
+pub(crate) const INTERNAL_ITERATIVE_MODE: InternalIterativeMode = InternalIterativeMode::Deepening;
+pub(crate) const IID_MIN_DEPTH: u8 = 4; // Shallowest remaining depth where IID/IIR is applied
+pub(crate) const IID_REDUCTION: u8 = 2; // How much shallower the IID search is than the node
+
+// Extension weights, in fractions of a ply (see ONE_PLY)
+pub(crate) const CHECK_EXTENSION: u8 = 2;
+pub(crate) const SINGLE_REPLY_EXTENSION: u8 = 4;
+pub(crate) const RECAPTURE_EXTENSION: u8 = 2;
+pub(crate) const PAWN_SEVENTH_EXTENSION: u8 = 2;
+pub(crate) const SINGULAR_EXTENSION: u8 = 4;
+pub(crate) const SINGULAR_MIN_DEPTH: u8 = 6; // Shallowest remaining depth where we look for singular moves
+pub(crate) const SINGULAR_MARGIN: i16 = 20; // Margin below the TT score per ply of remaining depth
+
+// ProbCut, a good capture that beats beta by a margin at reduced depth is assumed to cut at full depth
+pub(crate) const USE_PROBCUT: bool = true; // Flag to enable/disable ProbCut (for ablation testing)
+pub(crate) const PROBCUT_MIN_DEPTH: u8 = 5; // Shallowest remaining depth where ProbCut is tried
+pub(crate) const PROBCUT_REDUCTION: u8 = 4; // How much shallower the ProbCut verification search is
+pub(crate) const PROBCUT_MARGIN: i16 = 200; // How far above beta the capture has to score
+
+// Multi-cut, if several of the first moves fail high at reduced depth assume the node fails high
+pub(crate) const USE_MULTI_CUT: bool = false; // Flag to enable/disable multi-cut, off since we don't predict cut nodes
+pub(crate) const MULTI_CUT_MIN_DEPTH: u8 = 5; // Shallowest remaining depth where multi-cut is tried
+pub(crate) const MULTI_CUT_REDUCTION: u8 = 3; // How much shallower the multi-cut searches are
+pub(crate) const MULTI_CUT_MOVES: usize = 6; // How many moves are tried
+pub(crate) const MULTI_CUT_REQUIRED: usize = 3; // How many of them have to fail high
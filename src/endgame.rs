@@ -0,0 +1,463 @@
+// Endgame knowledge
+// Recognises material that can't win, scales down the endgame score of drawish endings and has
+// dedicated evaluations for the basic mates, picked out by the material on the board
+
+use chess::{Board, Color, Piece, Square, EMPTY};
+
+use crate::eval_params::MaterialParams;
+use crate::retrograde::MaterialSignature;
+use crate::utils::bitboards::{contains, forward_file, FILE_A, FILE_H, LIGHT_SQUARES};
+use crate::utils::common::{abs_eval_from_color, Eval, Score};
+
+// Scale factors are out of SCALE_NORMAL and only applied to the endgame half of the score
+pub(crate) const SCALE_NORMAL: i16 = 64;
+const SCALE_DRAW: i16 = 0;
+const SCALE_OPPOSITE_BISHOPS: i16 = 16;
+const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i16 = 44;
+const SCALE_ROOK_ENDING: i16 = 40;
+const SCALE_ROOK_ENDING_BLOCKED: i16 = 16; // Defending king already in front of the only pawn
+
+// Won mates score above anything the normal evaluation gives, but below tablebase wins
+const KNOWN_WIN: i16 = 10000;
+const MATING_MAX_PIECES: u32 = 6; // Skip the dispatch when there's more than this on the board
+
+const PUSH_TO_EDGE_WEIGHT: i16 = 20; // Per step the losing king is away from the centre
+const PUSH_TO_CORNER_WEIGHT: i16 = 100; // Per step the losing king is closer to the right corner
+const PUSH_CLOSE_WEIGHT: i16 = 20; // Per step the kings are closer together
+const ROOK_BOX_WEIGHT: i16 = 3; // Per square taken out of the box a rook shuts the losing king in
+
+type MatingEval = fn(&Board, Color, &MaterialParams) -> i16;
+
+fn count(board: &Board, piece: Piece, color: Color) -> u32 {
+    (board.pieces(piece) & board.color_combined(color)).popcnt()
+}
+
+fn distance(sq_1: Square, sq_2: Square) -> usize {
+    let rank_diff = sq_1
+        .get_rank()
+        .to_index()
+        .abs_diff(sq_2.get_rank().to_index());
+    let file_diff = sq_1
+        .get_file()
+        .to_index()
+        .abs_diff(sq_2.get_file().to_index());
+    rank_diff.max(file_diff)
+}
+
+pub(crate) fn is_insufficient_material(board: &Board) -> bool {
+    // Neither side can ever mate: bare kings, a single minor piece, two knights, or only bishops
+    // that all stand on the same colour
+    let majors_or_pawns =
+        board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if majors_or_pawns != EMPTY {
+        return false;
+    }
+
+    let knights = board.pieces(Piece::Knight).popcnt();
+    let bishops = *board.pieces(Piece::Bishop);
+    let minors = knights + bishops.popcnt();
+    if minors <= 1 {
+        return true;
+    }
+
+    let two_knights_one_side = [Color::White, Color::Black]
+        .iter()
+        .any(|color| count(board, Piece::Knight, *color) == 2);
+    if knights == 2 && bishops == EMPTY && two_knights_one_side {
+        return true;
+    }
+
+    knights == 0 && (bishops & LIGHT_SQUARES == EMPTY || bishops & !LIGHT_SQUARES == EMPTY)
+}
+
+fn opposite_bishops(board: &Board) -> bool {
+    // One bishop each, on different coloured squares
+    if count(board, Piece::Bishop, Color::White) != 1
+        || count(board, Piece::Bishop, Color::Black) != 1
+    {
+        return false;
+    }
+    let bishops = *board.pieces(Piece::Bishop);
+    (bishops & LIGHT_SQUARES).popcnt() == 1
+}
+
+fn wrong_rook_pawn(board: &Board, strong_side: Color) -> bool {
+    // Bishop and rook pawns where the bishop can't cover the queening square and the defending
+    // king has already got to the corner
+    let pawns = board.pieces(Piece::Pawn) & board.color_combined(strong_side);
+    let bishops = board.pieces(Piece::Bishop) & board.color_combined(strong_side);
+    let others = board.color_combined(strong_side)
+        & (board.pieces(Piece::Knight) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen));
+    if pawns == EMPTY || bishops.popcnt() != 1 || others != EMPTY {
+        return false;
+    }
+
+    let (promotion_a, promotion_h) = match strong_side {
+        Color::White => (Square::A8, Square::H8),
+        Color::Black => (Square::A1, Square::H1),
+    };
+    let promotion = if pawns & !FILE_A == EMPTY {
+        promotion_a
+    } else if pawns & !FILE_H == EMPTY {
+        promotion_h
+    } else {
+        return false;
+    };
+
+    let bishop_on_light = bishops & LIGHT_SQUARES != EMPTY;
+    let promotion_on_light = contains(LIGHT_SQUARES, promotion);
+    bishop_on_light != promotion_on_light
+        && distance(board.king_square(!strong_side), promotion) <= 1
+}
+
+fn rook_ending_scale(board: &Board, strong_side: Color) -> Option<i16> {
+    // Rook against rook with few pawns is hard to win, and close to a dead draw when the defending
+    // king is already in front of the only pawn
+    let minors_or_queens =
+        board.pieces(Piece::Knight) | board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    if minors_or_queens != EMPTY
+        || count(board, Piece::Rook, Color::White) != 1
+        || count(board, Piece::Rook, Color::Black) != 1
+    {
+        return None;
+    }
+
+    let strong_pawns = board.pieces(Piece::Pawn) & board.color_combined(strong_side);
+    let weak_pawns = board.pieces(Piece::Pawn) & board.color_combined(!strong_side);
+    if strong_pawns.popcnt() > 3 || strong_pawns.popcnt() > weak_pawns.popcnt() + 1 {
+        return None;
+    }
+
+    if strong_pawns.popcnt() == 1 && weak_pawns == EMPTY {
+        let pawn = strong_pawns.to_square();
+        if contains(
+            forward_file(strong_side, pawn),
+            board.king_square(!strong_side),
+        ) {
+            return Some(SCALE_ROOK_ENDING_BLOCKED);
+        }
+    }
+    Some(SCALE_ROOK_ENDING)
+}
+
+pub(crate) fn scale_factor(board: &Board, strong_side: Color) -> i16 {
+    // How much of the endgame score the side that's ahead can expect to convert
+    if wrong_rook_pawn(board, strong_side) {
+        return SCALE_DRAW;
+    }
+
+    if opposite_bishops(board) {
+        let other_pieces =
+            board.pieces(Piece::Knight) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+        return match other_pieces == EMPTY {
+            true => SCALE_OPPOSITE_BISHOPS,
+            false => SCALE_OPPOSITE_BISHOPS_WITH_PIECES,
+        };
+    }
+
+    rook_ending_scale(board, strong_side).unwrap_or(SCALE_NORMAL)
+}
+
+pub(crate) fn endgame_scale(board: &Board, score: Score) -> i16 {
+    // Scale factor of the side the endgame score favours
+    let strong_side = match score.eg >= 0 {
+        true => Color::White,
+        false => Color::Black,
+    };
+    scale_factor(board, strong_side)
+}
+
+pub(crate) fn scale_endgame(score: Score, scale: i16) -> Score {
+    // Only the endgame half is scaled, the midgame half is left alone
+    Score::new(
+        score.mg,
+        (score.eg as i32 * scale as i32 / SCALE_NORMAL as i32) as i16,
+    )
+}
+
+fn material_value(board: &Board, color: Color, material: &MaterialParams) -> i16 {
+    // Endgame piece values, the mates are all played out with few pieces left
+    [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| count(board, piece, color) as i16 * material.value(piece).eg)
+        .sum()
+}
+
+fn centre_distance(sq: Square) -> i16 {
+    // Steps from the four centre squares, 0 in the centre up to 6 in a corner
+    let rank = sq.get_rank().to_index() as i16;
+    let file = sq.get_file().to_index() as i16;
+    (3 - rank).max(rank - 4) + (3 - file).max(file - 4)
+}
+
+fn push_to_edge(sq: Square) -> i16 {
+    PUSH_TO_EDGE_WEIGHT * centre_distance(sq)
+}
+
+fn push_close(sq_1: Square, sq_2: Square) -> i16 {
+    PUSH_CLOSE_WEIGHT * (7 - distance(sq_1, sq_2) as i16)
+}
+
+fn push_to_corner(sq: Square, light_bishop: bool) -> i16 {
+    // Only the two corners the bishop can reach can be mated in, so count the steps away from the
+    // long diagonal through the other two corners
+    let rank = sq.get_rank().to_index() as i16;
+    let file = sq.get_file().to_index() as i16;
+    let steps = match light_bishop {
+        true => (rank - file).abs(),     // a8 and h1
+        false => (7 - rank - file).abs(), // a1 and h8 are dark
+    };
+    PUSH_TO_CORNER_WEIGHT * steps
+}
+
+fn rook_box(rook_sq: Square, king_sq: Square) -> i16 {
+    // Squares in the box the rook's rank and file shut the king in, a king on the rook's line isn't
+    // cut off in that direction
+    let side = |rook: usize, king: usize| match king.cmp(&rook) {
+        std::cmp::Ordering::Less => rook as i16,
+        std::cmp::Ordering::Greater => 7 - rook as i16,
+        std::cmp::Ordering::Equal => 8,
+    };
+    side(rook_sq.get_file().to_index(), king_sq.get_file().to_index())
+        * side(rook_sq.get_rank().to_index(), king_sq.get_rank().to_index())
+}
+
+fn rook_cut_off(board: &Board, strong_side: Color) -> i16 {
+    // The smaller the box the closer the mate, the best placed rook counts
+    let weak_king = board.king_square(!strong_side);
+    let rooks = board.pieces(Piece::Rook) & board.color_combined(strong_side);
+    rooks
+        .map(|sq| ROOK_BOX_WEIGHT * (64 - rook_box(sq, weak_king)))
+        .max()
+        .unwrap_or(0)
+}
+
+fn eval_kxk(board: &Board, strong_side: Color, material: &MaterialParams) -> i16 {
+    // Enough material against a lone king, drive it to the edge and bring our king over
+    let strong_king = board.king_square(strong_side);
+    let weak_king = board.king_square(!strong_side);
+    KNOWN_WIN
+        + material_value(board, strong_side, material)
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+        + rook_cut_off(board, strong_side)
+}
+
+fn eval_kbnk(board: &Board, strong_side: Color, material: &MaterialParams) -> i16 {
+    // Bishop and knight can only mate in a corner of the bishop's colour
+    let strong_king = board.king_square(strong_side);
+    let weak_king = board.king_square(!strong_side);
+    let bishops = board.pieces(Piece::Bishop) & board.color_combined(strong_side);
+    let light_bishop = bishops & LIGHT_SQUARES != EMPTY;
+    let knight = (board.pieces(Piece::Knight) & board.color_combined(strong_side)).to_square();
+    KNOWN_WIN
+        + material_value(board, strong_side, material)
+        + push_to_edge(weak_king)
+        + push_to_corner(weak_king, light_bishop)
+        + push_close(strong_king, weak_king)
+        + push_close(knight, weak_king)
+}
+
+fn eval_kqkr(board: &Board, strong_side: Color, material: &MaterialParams) -> i16 {
+    // Queen against rook wins but takes a while, the rook falls once the king is on the edge
+    let strong_king = board.king_square(strong_side);
+    let weak_king = board.king_square(!strong_side);
+    material.queen.eg - material.rook.eg
+        + push_to_edge(weak_king)
+        + push_close(strong_king, weak_king)
+}
+
+fn mating_evaluator(
+    board: &Board,
+    signature: &MaterialSignature,
+    strong_side: Color,
+) -> Option<MatingEval> {
+    // Picks the evaluation for the strong side's material, None if it isn't one we know
+    let strong = signature.side(strong_side);
+    let weak = signature.side(!strong_side);
+    let strong_bishops = board.pieces(Piece::Bishop) & board.color_combined(strong_side);
+    let bishops_both_colours = strong_bishops & LIGHT_SQUARES != EMPTY
+        && strong_bishops & !LIGHT_SQUARES != EMPTY;
+
+    match (strong, weak) {
+        ([Piece::Bishop, Piece::Knight], []) => Some(eval_kbnk),
+        ([Piece::Queen], [Piece::Rook]) => Some(eval_kqkr),
+        (_, []) if strong.contains(&Piece::Queen) || strong.contains(&Piece::Rook) => {
+            Some(eval_kxk)
+        }
+        (_, []) if bishops_both_colours => Some(eval_kxk),
+        _ => None,
+    }
+}
+
+pub(crate) fn evaluate_mating(board: &Board, material: &MaterialParams) -> Option<Eval> {
+    // Dedicated evaluation for KQK, KRK, KBNK, KQKR and similar, None for everything else
+    if board.combined().popcnt() > MATING_MAX_PIECES {
+        return None;
+    }
+    let signature = MaterialSignature::from_board(board);
+    [Color::White, Color::Black].into_iter().find_map(|strong_side| {
+        mating_evaluator(board, &signature, strong_side).map(|evaluator| {
+            abs_eval_from_color(evaluator(board, strong_side, material), strong_side)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+
+    fn insufficient(fen: &str) -> bool {
+        is_insufficient_material(&Board::from_str(fen).unwrap())
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/8 w - - 0 1")); // KK
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1")); // KBK
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/5n2 w - - 0 1")); // KNK
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1")); // KNNK
+        assert!(insufficient("8/8/4k1b1/8/8/3K4/8/5B2 w - - 0 1")); // Bishops on light squares
+
+        assert!(!insufficient("8/8/4kb2/8/8/3K4/8/5B2 w - - 0 1")); // Opposite bishops
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/4BN2 w - - 0 1")); // KBNK
+        assert!(!insufficient("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1")); // KPK
+        assert!(!insufficient("8/8/4k3/8/8/3K4/8/5R2 w - - 0 1")); // KRK
+        assert!(!insufficient("8/8/4kn2/8/8/3K4/8/5N2 w - - 0 1")); // A knight each can mate
+        assert!(!insufficient(&Board::default().to_string()));
+    }
+
+    #[test]
+    fn test_opposite_bishops() {
+        let pure = Board::from_str("8/5k2/8/2b5/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&pure, Color::White), SCALE_OPPOSITE_BISHOPS);
+
+        let with_rooks = Board::from_str("r7/5k2/8/2b5/8/1P3K2/P3B3/R7 w - - 0 1").unwrap();
+        assert_eq!(
+            scale_factor(&with_rooks, Color::White),
+            SCALE_OPPOSITE_BISHOPS_WITH_PIECES
+        );
+
+        let same_colour = Board::from_str("8/5k2/8/3b4/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&same_colour, Color::White), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn test_wrong_rook_pawn() {
+        // Light squared bishop can't help the h pawn queen on dark h8
+        let wrong = Board::from_str("7k/8/8/8/7P/8/2B5/6K1 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&wrong, Color::White), SCALE_DRAW);
+
+        // The right bishop wins
+        let right = Board::from_str("7k/8/8/8/7P/8/3B4/6K1 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&right, Color::White), SCALE_NORMAL);
+
+        // Not a draw yet if the defending king is too far away
+        let far = Board::from_str("8/8/8/1k6/7P/8/2B5/6K1 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&far, Color::White), SCALE_NORMAL);
+
+        // Black's a pawn needs a dark squared bishop for a1
+        let black = Board::from_str("8/8/8/8/p7/2k5/8/K2b4 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&black, Color::Black), SCALE_DRAW);
+    }
+
+    #[test]
+    fn test_rook_endings() {
+        let one_pawn = Board::from_str("8/4k3/8/8/8/2P5/r7/2R1K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&one_pawn, Color::White), SCALE_ROOK_ENDING);
+
+        let blocked = Board::from_str("2k5/8/8/8/8/2P5/r7/2R1K3 w - - 0 1").unwrap();
+        assert_eq!(
+            scale_factor(&blocked, Color::White),
+            SCALE_ROOK_ENDING_BLOCKED
+        );
+
+        // Two clear extra pawns should win
+        let two_up = Board::from_str("8/4k3/8/8/8/2PP4/r7/2R1K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&two_up, Color::White), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn test_scale_endgame() {
+        // Only the endgame half is scaled, and only by the side that's ahead
+        let score = Score::new(100, 128);
+        let pure = Board::from_str("8/5k2/8/2b5/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        assert_eq!(
+            scale_endgame(score, endgame_scale(&pure, score)),
+            Score::new(100, 32)
+        );
+        let normal = Board::from_str("8/5k2/8/8/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        assert_eq!(endgame_scale(&normal, score), SCALE_NORMAL);
+        assert_eq!(scale_endgame(score, SCALE_NORMAL), score);
+    }
+
+    fn mating(fen: &str) -> Option<i16> {
+        let material = MaterialParams::default();
+        evaluate_mating(&Board::from_str(fen).unwrap(), &material).map(|eval| eval.score)
+    }
+
+    #[test]
+    fn test_mating_dispatch() {
+        assert!(mating("8/8/4k3/8/8/3K4/8/7Q w - - 0 1").unwrap() > KNOWN_WIN); // KQK
+        assert!(mating("8/8/4k3/8/8/3K4/8/7r w - - 0 1").unwrap() < -KNOWN_WIN); // KRK for black
+        assert!(mating("8/8/4k3/8/8/3K4/8/4BN2 w - - 0 1").unwrap() > KNOWN_WIN); // KBNK
+        assert!(mating("8/8/4k3/8/8/3K4/8/3BB3 w - - 0 1").unwrap() > KNOWN_WIN); // KBBK
+        assert!(mating("8/8/4k3/8/8/3K4/P7/5R2 w - - 0 1").unwrap() > KNOWN_WIN); // KRPK
+
+        let kqkr = mating("8/8/4k3/8/2r5/3K4/8/7Q w - - 0 1").unwrap();
+        assert!(kqkr > 0 && kqkr < KNOWN_WIN);
+
+        assert_eq!(mating("8/8/4k3/8/8/3K4/8/4B1B1 w - - 0 1"), None); // Same coloured bishops
+        assert_eq!(mating("8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1"), None);
+        assert_eq!(mating("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1"), None); // KPK has its own bitbase
+        assert_eq!(mating("8/8/4kr2/8/8/3K4/8/5R2 w - - 0 1"), None);
+        assert_eq!(mating(&Board::default().to_string()), None);
+    }
+
+    #[test]
+    fn test_kxk_drives_to_edge() {
+        assert!(push_to_edge(Square::E8) > push_to_edge(Square::E5));
+        assert!(push_to_edge(Square::A1) > push_to_edge(Square::E8));
+        assert!(push_close(Square::E6, Square::E8) > push_close(Square::A1, Square::E8));
+
+        // Bringing the rook closer shrinks the box the king is shut in
+        assert_eq!(rook_box(Square::D1, Square::E5), 28);
+        assert_eq!(rook_box(Square::D4, Square::E5), 16);
+        assert_eq!(rook_box(Square::E1, Square::E5), 56); // Not cut off along the file
+
+        // Our king walking over is worth more than leaving it in the corner
+        let close = mating("4k3/8/4K3/8/8/8/8/7R w - - 0 1").unwrap();
+        let far = mating("4k3/8/8/8/8/8/8/K6R w - - 0 1").unwrap();
+        assert!(close > far);
+    }
+
+    #[test]
+    fn test_kbnk_right_corner() {
+        // Dark squared bishop mates on a1 or h8, the light corners aren't worth pushing towards
+        let right = mating("7k/8/5K2/8/8/8/8/2B1N3 w - - 0 1").unwrap();
+        let wrong = mating("k7/8/2K5/8/8/8/8/2B1N3 w - - 0 1").unwrap();
+        assert!(right > wrong);
+
+        // Along the edge, each step towards the right corner scores higher
+        let scores: Vec<i16> = ["a8", "a7", "a6", "a5", "a4", "a3", "a2", "a1"]
+            .iter()
+            .map(|sq| push_to_corner(Square::from_str(sq).unwrap(), false))
+            .collect();
+        assert!(scores.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // And for a light squared bishop the other way round
+        assert!(push_to_corner(Square::A8, true) > push_to_corner(Square::A1, true));
+    }
+
+    #[test]
+    fn test_kqkr() {
+        // Black defends with the rook, same scores mirrored
+        let white = mating("8/8/4k3/8/2r5/3K4/8/7Q w - - 0 1").unwrap();
+        let black = mating("7q/8/3k4/2R5/8/4K3/8/8 b - - 0 1").unwrap();
+        assert_eq!(white, -black);
+    }
+}
@@ -6,8 +6,9 @@ use std::thread;
 use std::time::SystemTime;
 
 use crate::consts;
-use crate::evaluation::evaluate_board;
+use crate::evaluator::{Evaluator, HandcraftedEvaluator};
 use crate::managers::cache_manager::{Cache, CacheInputGrouping};
+use crate::managers::pawn_cache_manager::take_pawn_table_counters;
 use crate::managers::stats_manager::{Statistics, StatisticsDepth};
 use crate::search::find_best_move;
 use crate::utils::common::EngineReturn;
@@ -19,9 +20,63 @@ pub fn enter_engine(
     board: Board,
     settings: EngineSettings,
 ) -> (ChessMove, Option<EngineReturn>) {
+    // Use the evaluator we were given, or the handcrafted one with our parameters
+    let evaluator: Arc<dyn Evaluator> = match &settings.evaluator {
+        Some(evaluator) => evaluator.clone(),
+        None => Arc::new(HandcraftedEvaluator::new(settings.eval_params.clone())),
+    };
+
     if settings.verbose {
         println!("=============================================");
-        println!("Balance of board {}", evaluate_board(board).score);
+        println!("Balance of board {}", evaluator.evaluate(&board).score);
+    }
+
+    // Play straight from the opening book if we have one and are still in it
+    if let Some(book) = &settings.book {
+        if let Some(book_move) = book.pick_move(&board, settings.book_selection) {
+            if settings.verbose {
+                println!("Book move: {}", book_move);
+            }
+            return (
+                book_move,
+                Some(EngineReturn {
+                    engine_move: book_move.to_string(),
+                    engine_search_stats: None,
+                    engine_depth_stats: None,
+                    engine_score: None,
+                }),
+            );
+        }
+    }
+
+    // Play the tablebase move straight away if the position is in our tables
+    let tablebase = settings.tablebase.as_deref();
+    let tb_move = settings
+        .dtm_tables
+        .as_ref()
+        .and_then(|dtm_tables| dtm_tables.best_move(&board))
+        .map(|(tb_move, dtm)| (tb_move, format!("{:?}", dtm)))
+        .or_else(|| {
+            tablebase
+                .and_then(|tb| tb.root_move(&board))
+                .map(|(tb_move, tb_eval)| (tb_move, tb_eval.score.to_string()))
+        });
+    if let Some((tb_move, tb_result)) = tb_move {
+        if settings.verbose {
+            println!("Tablebase move: {}, result: {}", tb_move, tb_result);
+        }
+        return (
+            tb_move,
+            Some(EngineReturn {
+                engine_move: tb_move.to_string(),
+                engine_search_stats: Some(Statistics {
+                    tb_hits: 1,
+                    ..Default::default()
+                }),
+                engine_depth_stats: None,
+                engine_score: None,
+            }),
+        );
     }
 
     let start_time = SystemTime::now();
@@ -63,31 +118,34 @@ pub fn enter_engine(
 
     let mut best_score: Eval = Eval { score: 0 };
     let mut best_mve: ChessMove = Default::default();
+    let mut nodes_searched: u64 = 0; // Over every iteration, for the node limit
 
     while (t_start.elapsed().unwrap() < settings.time_limit)
-        && (terminal_depth <= consts::DEPTH_LIM)
+        && (terminal_depth <= settings.depth_limit.unwrap_or(consts::DEPTH_LIM))
+        && settings.node_limit.is_none_or(|limit| nodes_searched < limit)
         && (!abort_search)
     {
         // Run until we hit the timelimit
 
         // If we get a stop command from the UCI layer, bail out of deepening
-        match &settings.stop_engine_rcv {
-            Some(rcv) => abort_search = rcv.try_recv().unwrap_or(false),
-            None => {}
+        if let Some(rcv) = &settings.stop_engine_rcv {
+            abort_search = rcv.try_recv().unwrap_or(false)
         }
 
         if settings.verbose {
             println!("Current depth {}", terminal_depth);
         }
 
+        take_pawn_table_counters(); // Only count lookups made by this iteration
+        evaluator.new_search(&board);
         let search_result = find_best_move(
             board,
             SearchParameters {
                 depth: 0,
                 depth_lim: terminal_depth,
                 extension: 0,
-                alpha: i16::min_value() + 1,
-                beta: i16::max_value() - 1,
+                alpha: i16::MIN + 1,
+                beta: i16::MAX - 1,
                 color: color_i,
                 cache: cache.clone(),
                 t_start: &t_start,
@@ -97,6 +155,12 @@ pub fn enter_engine(
                 } else {
                     None
                 },
+                last_capture: None,
+                excluded_move: None,
+                tablebase,
+                dtm_tables: settings.dtm_tables.as_deref(),
+                evaluator: evaluator.as_ref(),
+                search_config: &settings.search_config,
             },
         );
 
@@ -107,6 +171,9 @@ pub fn enter_engine(
                 best_mve = search_output.best_move;
                 depth_stats.depth_reached += 1;
                 search_stats = search_output.node_stats;
+                nodes_searched += search_stats.searched_nodes as u64;
+                (search_stats.pawn_hash_hits, search_stats.pawn_hash_probes) =
+                    take_pawn_table_counters();
             }
             Err(_) => {
                 if settings.verbose {
@@ -144,8 +211,8 @@ pub fn enter_engine(
 
     if consts::SEARCH_INFO && settings.verbose {
         println!(
-            "Search stats. \n All nodes in problem: {}\n Nodes visited {}, reduction {}%, times used cache {}, time elapsed (ms) {}",
-            search_stats.all_nodes, search_stats.searched_nodes, percent_reduction, search_stats.caches_used, depth_stats.time_ms,
+            "Search stats. \n All nodes in problem: {}\n Nodes visited {}, reduction {}%, times used cache {}, tablebase hits {}, pawn hash hit rate {:.1}%, time elapsed (ms) {}",
+            search_stats.all_nodes, search_stats.searched_nodes, percent_reduction, search_stats.caches_used, search_stats.tb_hits, search_stats.pawn_hash_hit_rate() * 100.0, depth_stats.time_ms,
         )
     }
 
@@ -157,6 +224,7 @@ pub fn enter_engine(
             engine_move: best_mve.to_string(),
             engine_search_stats: Some(search_stats),
             engine_depth_stats: Some(depth_stats),
+            engine_score: Some(best_score.score),
         }),
     )
 }
@@ -166,6 +234,10 @@ mod tests {
     use std::{str::FromStr, sync::mpsc, sync::mpsc::Receiver, sync::mpsc::Sender};
 
     use super::*;
+    use crate::book::{
+        polyglot::{encode_move, BookEntry, BookSelection, OpeningBook},
+        zobrist::polyglot_key,
+    };
     use chess::{Board, Square};
 
     #[test]
@@ -191,6 +263,88 @@ mod tests {
         assert_eq!(board, board_orig); // Make sure the engine move is legal
     }
 
+    fn start_position_book(book_move: ChessMove) -> Arc<OpeningBook> {
+        // A book with a single move from the starting position
+        let board: Board = Board::default();
+        Arc::new(OpeningBook::from_entries(vec![BookEntry {
+            key: polyglot_key(&board),
+            raw_move: encode_move(&board, book_move),
+            weight: 1,
+            learn: 0,
+        }]))
+    }
+
+    #[test]
+    fn test_book_move() {
+        let board: Board = Board::default();
+        let book_move = ChessMove::new(Square::B1, Square::C3, None);
+        let settings = EngineSettings {
+            book: Some(start_position_book(book_move)),
+            book_selection: BookSelection::BestOnly,
+            ..Default::default()
+        };
+        let t_start = SystemTime::now();
+        let (eng_move, eng_return) = enter_engine(board, settings);
+
+        assert_eq!(eng_move, book_move);
+        assert!(eng_return.unwrap().engine_depth_stats.is_none()); // No search was run
+        assert!(t_start.elapsed().unwrap() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_out_of_book() {
+        // Positions the book doesn't have fall back to searching
+        let board: Board = Board::from_str("8/8/8/8/8/2k5/8/K6Q w - - 0 1").unwrap();
+        let settings = EngineSettings {
+            book: Some(start_position_book(ChessMove::new(Square::B1, Square::C3, None))),
+            time_limit: std::time::Duration::from_secs(1),
+            ..Default::default()
+        };
+        let (eng_move, eng_return) = enter_engine(board, settings);
+        assert!(board.legal(eng_move));
+        assert!(eng_return.unwrap().engine_depth_stats.is_some());
+    }
+
+    #[test]
+    fn test_pawn_hash_stats() {
+        // Most positions in a search share their pawns with one already evaluated
+        let settings = EngineSettings {
+            time_limit: std::time::Duration::from_secs(1),
+            ..Default::default()
+        };
+        let (_, eng_return) = enter_engine(Board::default(), settings);
+        let stats = eng_return.unwrap().engine_search_stats.unwrap();
+        assert!(stats.pawn_hash_probes > 0);
+        assert!(stats.pawn_hash_hit_rate() > 0.5);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        // The first iteration always crosses a one node limit, so no deeper one is started
+        let settings = EngineSettings {
+            node_limit: Some(1),
+            ..Default::default()
+        };
+        let (eng_move, eng_return) = enter_engine(Board::default(), settings);
+        assert!(Board::default().legal(eng_move));
+        assert_eq!(eng_return.unwrap().engine_depth_stats.unwrap().depth_reached, 1);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // Stops at the depth asked for, and reports the mate it found for White
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let settings = EngineSettings {
+            depth_limit: Some(3),
+            ..Default::default()
+        };
+        let (eng_move, eng_return) = enter_engine(board, settings);
+        let eng_return = eng_return.unwrap();
+        assert_eq!(eng_move, ChessMove::new(Square::H1, Square::H8, None));
+        assert_eq!(eng_return.engine_depth_stats.unwrap().depth_reached, 3);
+        assert!(eng_return.engine_score.unwrap() >= consts::MATE_THRESHOLD);
+    }
+
     #[test]
     fn test_queen_blunder() {
         // This sequence was a known queen blunder from a previous revision
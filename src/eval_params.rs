@@ -0,0 +1,156 @@
+// Every weight and table the handcrafted evaluation uses, so they can be changed without recompiling
+// The defaults are the values the engine has always played with, a file only has to list the
+// values it changes
+
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use chess::Piece;
+
+use crate::utils::common::Score;
+use crate::utils::config_file;
+
+pub use crate::evaluation::{KingEndgameParams, PieceParams, ThreatParams};
+pub use crate::king_safety::KingSafetyParams;
+pub use crate::mobility::MobilityParams;
+pub use crate::pawn_structure::PawnParams;
+pub use crate::psqt::{PieceTable, PsqtParams};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct MaterialParams {
+    pub pawn: Score,
+    pub knight: Score,
+    pub bishop: Score,
+    pub rook: Score,
+    pub queen: Score,
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        MaterialParams {
+            pawn: Score::new(100, 120),
+            knight: Score::new(300, 280),
+            bishop: Score::new(320, 320),
+            rook: Score::new(500, 530),
+            queen: Score::new(900, 930),
+        }
+    }
+}
+
+impl MaterialParams {
+    pub fn value(&self, piece: Piece) -> Score {
+        // The king is never traded so it has no material value
+        match piece {
+            Piece::Pawn => self.pawn,
+            Piece::Knight => self.knight,
+            Piece::Bishop => self.bishop,
+            Piece::Rook => self.rook,
+            Piece::Queen => self.queen,
+            Piece::King => Score::default(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct EvalParams {
+    pub material: MaterialParams,
+    pub psqt: PsqtParams,
+    pub pawns: PawnParams,
+    pub mobility: MobilityParams,
+    pub king_safety: KingSafetyParams,
+    pub pieces: PieceParams,
+    pub threats: ThreatParams,
+    pub king_endgame: KingEndgameParams,
+}
+
+impl EvalParams {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        // TOML if the file says so, JSON otherwise
+        config_file::load(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        config_file::save(self, path)
+    }
+}
+
+pub(crate) fn default_params() -> &'static EvalParams {
+    // Shared by everything that evaluates without being handed parameters
+    static DEFAULT_PARAMS: OnceLock<EvalParams> = OnceLock::new();
+    DEFAULT_PARAMS.get_or_init(EvalParams::default)
+}
+
+pub(crate) mod fixed_array {
+    // serde only handles arrays of up to 32 elements, longer tables go through a Vec
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        array.as_slice().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        let len = values.len();
+        values
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &format!("{} values", N).as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("eval_params_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut params = EvalParams::default();
+        params.material.knight = Score::new(310, 290);
+        params.psqt.knight.mg[27] = 33;
+        params.king_safety.safety_table[99] = 450;
+
+        for name in ["params.json", "params.toml"] {
+            let path = temp_path(name);
+            params.save(&path).unwrap();
+            assert_eq!(EvalParams::load(&path).unwrap(), params);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_partial_file() {
+        // Anything the file leaves out keeps its default
+        let path = temp_path("partial.toml");
+        std::fs::write(&path, "[material]\nqueen = { mg = 1000, eg = 1000 }\n").unwrap();
+        let params = EvalParams::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(params.material.queen, Score::new(1000, 1000));
+        assert_eq!(params.material.rook, MaterialParams::default().rook);
+        assert_eq!(params.psqt, PsqtParams::default());
+    }
+
+    #[test]
+    fn test_bad_table_length() {
+        let path = temp_path("short.json");
+        std::fs::write(&path, r#"{"psqt": {"pawn": {"mg": [1, 2, 3]}}}"#).unwrap();
+        let result = EvalParams::load(&path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
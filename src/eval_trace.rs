@@ -0,0 +1,207 @@
+// Breakdown of the static evaluation into its terms, to see why the engine likes a position
+// The evaluation reports every term to a tracer as it goes, the search uses one that does nothing
+
+use std::fmt;
+
+use chess::Board;
+
+use crate::endgame::SCALE_NORMAL;
+use crate::gamestate::{game_phase, MAX_PHASE};
+use crate::utils::common::{Eval, Score};
+
+pub(crate) trait Tracer {
+    // Terms are given per side, positive is good for that side
+    fn term(&mut self, _name: &'static str, _white: Score, _black: Score) {}
+    fn scale(&mut self, _scale: i16) {}
+    fn bonus(&mut self, _bonus: Eval) {}
+    fn note(&mut self, _note: &'static str) {}
+}
+
+pub(crate) struct NoTrace;
+
+impl Tracer for NoTrace {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TermTrace {
+    pub name: String,
+    pub white: Score,
+    pub black: Score,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub terms: Vec<TermTrace>,
+    pub phase: i16, // 0 (bare kings and pawns) to max_phase (all pieces on the board)
+    pub max_phase: i16,
+    pub scale: i16, // Applied to the endgame half of the total, out of scale_normal
+    pub scale_normal: i16,
+    pub bonus: i16,           // Added after tapering, e.g. for a won KPK
+    pub note: Option<String>, // Set when the position is scored without the terms
+    pub eval: i16,            // The final score, + is winning for White
+}
+
+impl EvalTrace {
+    pub(crate) fn new(board: &Board) -> Self {
+        EvalTrace {
+            terms: vec![],
+            phase: game_phase(board),
+            max_phase: MAX_PHASE,
+            scale: SCALE_NORMAL,
+            scale_normal: SCALE_NORMAL,
+            bonus: 0,
+            note: None,
+            eval: 0,
+        }
+    }
+
+    pub fn total(&self) -> Score {
+        // Sum of the terms before scaling, white minus black
+        self.terms.iter().fold(Score::default(), |total, term| {
+            total + term.white - term.black
+        })
+    }
+}
+
+impl Tracer for EvalTrace {
+    fn term(&mut self, name: &'static str, white: Score, black: Score) {
+        self.terms.push(TermTrace {
+            name: name.to_string(),
+            white,
+            black,
+        });
+    }
+
+    fn scale(&mut self, scale: i16) {
+        self.scale = scale;
+    }
+
+    fn bonus(&mut self, bonus: Eval) {
+        self.bonus = bonus.score;
+    }
+
+    fn note(&mut self, note: &'static str) {
+        self.note = Some(note.to_string());
+    }
+}
+
+fn write_row(f: &mut fmt::Formatter<'_>, name: &str, white: Score, black: Score) -> fmt::Result {
+    let total = white - black;
+    writeln!(
+        f,
+        "{:<16}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+        name, white.mg, white.eg, black.mg, black.eg, total.mg, total.eg
+    )
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = format!("{:-<16}+{:-<15}+{:-<15}+{:-<15}", "", "", "", "");
+        writeln!(
+            f,
+            "{:<16}|{:^15}|{:^15}|{:^15}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:<16}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+        writeln!(f, "{}", rule)?;
+        for term in &self.terms {
+            write_row(f, &term.name, term.white, term.black)?;
+        }
+        writeln!(f, "{}", rule)?;
+        let white_total = self
+            .terms
+            .iter()
+            .fold(Score::default(), |total, term| total + term.white);
+        let black_total = self
+            .terms
+            .iter()
+            .fold(Score::default(), |total, term| total + term.black);
+        write_row(f, "Total", white_total, black_total)?;
+
+        writeln!(f)?;
+        writeln!(f, "Phase {} of {}", self.phase, self.max_phase)?;
+        writeln!(f, "Endgame scale {} of {}", self.scale, self.scale_normal)?;
+        if self.bonus != 0 {
+            writeln!(f, "Bonus {:+}", self.bonus)?;
+        }
+        if let Some(note) = &self.note {
+            writeln!(f, "Scored as {}", note)?;
+        }
+        write!(f, "Evaluation {:+} (white's view)", self.eval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+    use crate::evaluation::{evaluate_board, evaluate_trace};
+
+    #[test]
+    fn test_trace_matches_evaluation() {
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1",
+            "r3k2r/pp3ppp/2n1b3/3p4/3P4/2N1B3/PP3PPP/R3K2R b KQkq - 0 1",
+            "8/5k2/8/2b5/8/1P3K2/P3B3/8 w - - 0 1",
+            "8/4k3/8/4K3/4P3/8/8/8 b - - 0 1",
+            "8/8/8/8/8/8/2k5/K6Q w - - 0 1",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(evaluate_trace(board).eval, evaluate_board(board).score);
+        }
+    }
+
+    #[test]
+    fn test_trace_terms() {
+        // The start position is level on every term
+        let trace = evaluate_trace(Board::default());
+        assert_eq!(trace.phase, MAX_PHASE);
+        assert_eq!(trace.note, None);
+        assert!(trace.terms.iter().any(|term| term.name == "Material"));
+        assert!(trace.terms.iter().any(|term| term.name == "PSQT knight"));
+        for term in &trace.terms {
+            if term.name != "King endgame" {
+                assert_eq!(term.white, term.black, "{}", term.name);
+            }
+        }
+
+        // Positions scored outright say why and have no terms
+        let mate = Board::from_str("8/8/8/8/8/8/2k5/K6Q w - - 0 1").unwrap();
+        let trace = evaluate_trace(mate);
+        assert_eq!(trace.note.as_deref(), Some("basic mate"));
+        assert!(trace.terms.is_empty());
+    }
+
+    #[test]
+    fn test_trace_total() {
+        // Without scaling or a bonus the terms add up to the evaluation
+        let board =
+            Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+                .unwrap();
+        let trace = evaluate_trace(board);
+        assert_eq!(trace.scale, SCALE_NORMAL);
+        assert_ne!(trace.eval, 0);
+        assert_eq!(trace.total().taper(trace.phase).score, trace.eval);
+    }
+
+    #[test]
+    fn test_trace_serde() {
+        let trace = evaluate_trace(Board::default());
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(serde_json::from_str::<EvalTrace>(&json).unwrap(), trace);
+    }
+
+    #[test]
+    fn test_trace_table() {
+        let table = evaluate_trace(Board::default()).to_string();
+        assert!(table.lines().count() > 10);
+        assert!(table.contains("Mobility"));
+        assert!(table.ends_with("Evaluation +0 (white's view)"));
+    }
+}
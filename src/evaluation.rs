@@ -1,94 +1,151 @@
+use crate::consts;
+use crate::endgame;
+use crate::eval_params::{default_params, EvalParams, MaterialParams};
+use crate::eval_trace::{EvalTrace, NoTrace, Tracer};
 use crate::gamestate;
-use crate::psqt::get_psqt_score;
-use crate::utils::common::{abs_eval_from_color, max, min};
-use crate::{gamestate::GameState, utils::common::Eval};
-use chess::{Board, BoardStatus, Color, Piece, Square};
-
-fn evaluate_board_material(board: &Board) -> Eval {
-    // List of values
-    let v_pawn: i16 = 100;
-    let v_knight: i16 = 300;
-    let v_bishop: i16 = 320;
-    let v_rook: i16 = 500;
-    let v_queen: i16 = 900;
-
-    let v_king: i16 = i16::MAX; // Temporary, ensure that the king is super valuable
-
-    let mut score: i16 = 0;
-
-    // Material
-    let black_pawns =
-        (board.pieces(Piece::Pawn) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_pawns =
-        (board.pieces(Piece::Pawn) & board.color_combined(Color::White)).popcnt() as i16;
-
-    let black_knight =
-        (board.pieces(Piece::Knight) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_knight =
-        (board.pieces(Piece::Knight) & board.color_combined(Color::White)).popcnt() as i16;
-
-    let black_bishop =
-        (board.pieces(Piece::Bishop) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_bishop =
-        (board.pieces(Piece::Bishop) & board.color_combined(Color::White)).popcnt() as i16;
-
-    let black_rook =
-        (board.pieces(Piece::Rook) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_rook =
-        (board.pieces(Piece::Rook) & board.color_combined(Color::White)).popcnt() as i16;
-
-    let black_queen =
-        (board.pieces(Piece::Queen) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_queen =
-        (board.pieces(Piece::Queen) & board.color_combined(Color::White)).popcnt() as i16;
-
-    let black_king =
-        (board.pieces(Piece::King) & board.color_combined(Color::Black)).popcnt() as i16;
-    let white_king =
-        (board.pieces(Piece::King) & board.color_combined(Color::White)).popcnt() as i16;
-
-    score += (white_pawns - black_pawns) * v_pawn;
-    score += (white_knight - black_knight) * v_knight;
-    score += (white_bishop - black_bishop) * v_bishop;
-    score += (white_rook - black_rook) * v_rook;
-    score += (white_queen - black_queen) * v_queen;
-    score += (white_king - black_king) * v_king; // Temporary
-
-    Eval { score }
-}
-
-fn evaluate_board_psqt(board: &Board, gamestate: GameState) -> Eval {
-    // Piece-square table
-
-    let mut psqt_eval: Eval = Eval { score: 0 };
-
-    // Iterate through white pieces
-    for sq in *board.color_combined(Color::White) {
-        // We should expect to find a piece but just to confirm
-        match board.piece_on(sq) {
-            Some(piece_on_sq) => {
-                psqt_eval += get_psqt_score(piece_on_sq, Color::White, sq, gamestate)
-            }
-            None => {
-                println!("No piece found when expected, white. Square {}", sq)
-            }
+use crate::king_safety;
+use crate::kpk;
+use crate::mobility::{self, piece_attacks};
+use crate::managers::pawn_cache_manager::probe_pawn_table;
+use crate::pawn_structure::{self, PawnInfo};
+use crate::psqt::{get_psqt_score, PsqtParams};
+use crate::utils::bitboards::{
+    contains, pawn_attack_span, pawn_attacks, relative_rank, LIGHT_SQUARES,
+};
+use crate::utils::common::{abs_eval_from_color, max, min, Eval, Score};
+use chess::{
+    between, get_bishop_rays, get_king_moves, get_knight_moves, get_rook_moves, get_rook_rays,
+    BitBoard, Board, BoardStatus, Color, Piece, Square, ALL_PIECES, EMPTY, NUM_PIECES,
+};
+
+const BISHOP_PAIR: Score = Score::new(30, 50);
+const ROOK_OPEN_FILE: Score = Score::new(25, 10);
+const ROOK_HALF_OPEN_FILE: Score = Score::new(12, 6);
+const ROOK_ON_SEVENTH: Score = Score::new(10, 25);
+const CONNECTED_ROOKS: Score = Score::new(10, 5);
+const KNIGHT_OUTPOST: Score = Score::new(20, 12);
+const BAD_BISHOP_PAWN: Score = Score::new(-4, -6); // Per blocked pawn on the bishop's colour
+const TRAPPED_BISHOP: Score = Score::new(-80, -80);
+const TRAPPED_ROOK: Score = Score::new(-40, -10);
+
+// Threats, kept apart from the material and piece-square values so they can be tuned on their own
+const THREAT_BY_PAWN: Score = Score::new(40, 30); // Per enemy piece attacked by one of our pawns
+const THREAT_MINOR_ON_MAJOR: Score = Score::new(30, 20); // Per enemy rook or queen a minor attacks
+const HANGING_PIECE: Score = Score::new(25, 15); // Per enemy piece we attack that isn't defended
+const PINNED_PIECE: Score = Score::new(-15, -10); // Per piece of ours pinned to our king
+const SAFE_CHECK_KNIGHT: Score = Score::new(12, 4);
+const SAFE_CHECK_BISHOP: Score = Score::new(8, 3);
+const SAFE_CHECK_ROOK: Score = Score::new(15, 5);
+const SAFE_CHECK_QUEEN: Score = Score::new(10, 4);
+
+// Pulls the king towards the enemy king and away from the edge once pieces come off
+const KING_DISTANCE_WEIGHT: i16 = 10;
+const KING_POSITION_WEIGHT: i16 = 5;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PieceParams {
+    pub bishop_pair: Score,
+    pub rook_open_file: Score,
+    pub rook_half_open_file: Score,
+    pub rook_on_seventh: Score,
+    pub connected_rooks: Score,
+    pub knight_outpost: Score,
+    pub bad_bishop_pawn: Score,
+    pub trapped_bishop: Score,
+    pub trapped_rook: Score,
+}
+
+impl Default for PieceParams {
+    fn default() -> Self {
+        PieceParams {
+            bishop_pair: BISHOP_PAIR,
+            rook_open_file: ROOK_OPEN_FILE,
+            rook_half_open_file: ROOK_HALF_OPEN_FILE,
+            rook_on_seventh: ROOK_ON_SEVENTH,
+            connected_rooks: CONNECTED_ROOKS,
+            knight_outpost: KNIGHT_OUTPOST,
+            bad_bishop_pawn: BAD_BISHOP_PAWN,
+            trapped_bishop: TRAPPED_BISHOP,
+            trapped_rook: TRAPPED_ROOK,
         }
     }
+}
 
-    // Iterate through black pieces
-    for sq in *board.color_combined(Color::Black) {
-        // We should expect to find a piece but just to confirm
-        match board.piece_on(sq) {
-            Some(piece_on_sq) => {
-                psqt_eval += get_psqt_score(piece_on_sq, Color::Black, sq, gamestate)
-            }
-            None => {
-                println!("No piece found when expected, black. Square {}", sq)
-            }
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ThreatParams {
+    pub by_pawn: Score,
+    pub minor_on_major: Score,
+    pub hanging_piece: Score,
+    pub pinned_piece: Score,
+    pub safe_check_knight: Score,
+    pub safe_check_bishop: Score,
+    pub safe_check_rook: Score,
+    pub safe_check_queen: Score,
+}
+
+impl Default for ThreatParams {
+    fn default() -> Self {
+        ThreatParams {
+            by_pawn: THREAT_BY_PAWN,
+            minor_on_major: THREAT_MINOR_ON_MAJOR,
+            hanging_piece: HANGING_PIECE,
+            pinned_piece: PINNED_PIECE,
+            safe_check_knight: SAFE_CHECK_KNIGHT,
+            safe_check_bishop: SAFE_CHECK_BISHOP,
+            safe_check_rook: SAFE_CHECK_ROOK,
+            safe_check_queen: SAFE_CHECK_QUEEN,
         }
     }
+}
 
-    psqt_eval
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct KingEndgameParams {
+    pub distance_weight: i16, // Per step the kings are closer than five apart, when up material
+    pub position_weight: i16, // Per step our king is further from the edge than theirs
+}
+
+impl Default for KingEndgameParams {
+    fn default() -> Self {
+        KingEndgameParams {
+            distance_weight: KING_DISTANCE_WEIGHT,
+            position_weight: KING_POSITION_WEIGHT,
+        }
+    }
+}
+
+// Names of the piece-square table terms in a trace, indexed by piece
+const PSQT_TERMS: [&str; NUM_PIECES] = [
+    "PSQT pawn",
+    "PSQT knight",
+    "PSQT bishop",
+    "PSQT rook",
+    "PSQT queen",
+    "PSQT king",
+];
+
+fn evaluate_board_material(board: &Board, color: Color, params: &MaterialParams) -> Score {
+    // Material of one side
+    let own = board.color_combined(color);
+    let count = |piece: Piece| (board.pieces(piece) & own).popcnt() as i16;
+
+    params.pawn * count(Piece::Pawn)
+        + params.knight * count(Piece::Knight)
+        + params.bishop * count(Piece::Bishop)
+        + params.rook * count(Piece::Rook)
+        + params.queen * count(Piece::Queen)
+}
+
+fn evaluate_board_psqt(board: &Board, color: Color, piece: Piece, params: &PsqtParams) -> Score {
+    // Piece-square table score of one kind of piece for one side
+    // The tables give black's squares as negative values
+    let mut psqt_eval = Score::default();
+    for sq in board.pieces(piece) & board.color_combined(color) {
+        psqt_eval += get_psqt_score(piece, color, sq, params);
+    }
+    psqt_eval.for_colour(color)
 }
 
 fn chebyshev_dist(sq_1: Square, sq_2: Square) -> i16 {
@@ -103,43 +160,284 @@ fn edge_distance(sq: Square) -> i16 {
     let rank = sq.get_rank().to_index();
     let file = sq.get_file().to_index();
 
-    let to_nearest_rank = min(rank - 0, 7 - rank);
-    let to_nearest_file = min(file - 0, 7 - file);
+    let to_nearest_rank = min(rank, 7 - rank);
+    let to_nearest_file = min(file, 7 - file);
     min(to_nearest_file, to_nearest_rank) as i16
 }
 
-fn endgame_king_heuristics(board: &Board, gamestate: GameState, up_material: bool) -> Eval {
-    match gamestate {
-        GameState::_Opening => Eval { score: 0 },
-        GameState::Middle => Eval { score: 0 },
-        GameState::End => {
-            // Get squares of both kings
-            let king_sq_w = board.king_square(Color::White);
-            let king_sq_b = board.king_square(Color::Black);
-            let king_distance_bonus = if up_material {
-                // Compute the king distance heuristic
-                let dist = chebyshev_dist(king_sq_w, king_sq_b);
-
-                let dist_weight = 10; // Want to be as close as possible to enemy king to cut it off
-                let score = (5 - dist) * dist_weight;
-                abs_eval_from_color(score, board.side_to_move())
-            } else {
-                abs_eval_from_color(0, board.side_to_move())
-            };
+fn endgame_king_heuristics(board: &Board, up_material: bool, params: &KingEndgameParams) -> Score {
+    // Scored for the side to move
+    // Only counts towards the endgame half of the score, so it fades in as pieces come off
+    // Get squares of both kings
+    let king_sq_w = board.king_square(Color::White);
+    let king_sq_b = board.king_square(Color::Black);
+    let king_distance_bonus = if up_material {
+        // Compute the king distance heuristic
+        // Want to be as close as possible to enemy king to cut it off
+        let dist = chebyshev_dist(king_sq_w, king_sq_b);
+        (5 - dist) * params.distance_weight
+    } else {
+        0
+    };
+
+    // Compute the king position heuristic
+    let (opponent_king_sq, self_king_sq) = match board.side_to_move() {
+        Color::White => (king_sq_b, king_sq_w),
+        Color::Black => (king_sq_w, king_sq_b),
+    };
+
+    let king_position_bonus =
+        params.position_weight * (edge_distance(self_king_sq) - edge_distance(opponent_king_sq));
+    Score::new(0, king_distance_bonus + king_position_bonus)
+}
 
-            // Compute the king position heuristic
-            let (opponent_king_sq, self_king_sq) = match board.side_to_move() {
-                Color::White => (king_sq_b, king_sq_w),
-                Color::Black => (king_sq_w, king_sq_b),
-            };
+fn bishop_pair(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Two bishops cover both square colours between them
+    let bishops = board.pieces(Piece::Bishop) & board.color_combined(color);
+    if bishops & LIGHT_SQUARES != EMPTY && bishops & !LIGHT_SQUARES != EMPTY {
+        params.bishop_pair
+    } else {
+        Score::default()
+    }
+}
+
+fn rooks_on_files(board: &Board, pawn_info: &PawnInfo, color: Color, params: &PieceParams) -> Score {
+    // Rooks want files without pawns, or at least without our own
+    let mut score = Score::default();
+    for sq in board.pieces(Piece::Rook) & board.color_combined(color) {
+        if contains(pawn_info.open_files, sq) {
+            score += params.rook_open_file;
+        } else if contains(pawn_info.half_open_files[color.to_index()], sq) {
+            score += params.rook_half_open_file;
+        }
+    }
+    score
+}
+
+fn rooks_on_seventh(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Only worth it when the enemy king is stuck on the back rank or there are pawns to eat
+    let enemy_king_on_back_rank = relative_rank(color, board.king_square(!color)) == 7;
+    let pawns_on_seventh = (board.pieces(Piece::Pawn) & board.color_combined(!color))
+        .any(|pawn| relative_rank(color, pawn) == 6);
+    let mut score = Score::default();
+    for sq in board.pieces(Piece::Rook) & board.color_combined(color) {
+        if relative_rank(color, sq) == 6 && (enemy_king_on_back_rank || pawns_on_seventh) {
+            score += params.rook_on_seventh;
+        }
+    }
+    score
+}
 
-            let position_weight = 5;
-            let king_position_diff =
-                position_weight * (edge_distance(self_king_sq) - edge_distance(opponent_king_sq));
-            let king_position_bonus = abs_eval_from_color(king_position_diff, board.side_to_move());
-            king_distance_bonus + king_position_bonus
+fn connected_rooks(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Two rooks defending each other along a rank or file
+    let rooks = board.pieces(Piece::Rook) & board.color_combined(color);
+    for sq in rooks {
+        if get_rook_moves(sq, *board.combined()) & rooks != EMPTY {
+            return params.connected_rooks;
         }
     }
+    Score::default()
+}
+
+fn knight_outposts(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Knights on the fourth to sixth rank, defended by a pawn and out of reach of enemy pawns
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let defended = pawn_attacks(color, own_pawns);
+    let mut score = Score::default();
+    for sq in board.pieces(Piece::Knight) & board.color_combined(color) {
+        if (3..=5).contains(&relative_rank(color, sq))
+            && contains(defended, sq)
+            && pawn_attack_span(color, sq) & enemy_pawns == EMPTY
+        {
+            score += params.knight_outpost;
+        }
+    }
+    score
+}
+
+fn bad_bishops(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Bishops hemmed in by their own blocked pawns on squares of the same colour
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let blocked_pawns = own_pawns
+        .filter(|sq| sq.forward(color).is_some_and(|stop| contains(*board.combined(), stop)))
+        .fold(EMPTY, |blocked, sq| blocked | BitBoard::from_square(sq));
+
+    let mut score = Score::default();
+    for sq in board.pieces(Piece::Bishop) & board.color_combined(color) {
+        let same_colour = match contains(LIGHT_SQUARES, sq) {
+            true => LIGHT_SQUARES,
+            false => !LIGHT_SQUARES,
+        };
+        score += params.bad_bishop_pawn * (blocked_pawns & same_colour).popcnt() as i16;
+    }
+    score
+}
+
+fn trapped_pieces(board: &Board, color: Color, params: &PieceParams) -> Score {
+    // Bishops that grabbed a pawn on a7/h7 and got shut in, and rooks stuck in the corner behind
+    // a king that has given up castling
+    let own = board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let relative = |sq: Square| match color {
+        Color::White => sq,
+        Color::Black => chess::ALL_SQUARES[sq.to_index() ^ 56],
+    };
+    let mut score = Score::default();
+
+    let bishops = board.pieces(Piece::Bishop) & own;
+    for (bishop_sq, pawn_sq) in [(Square::A7, Square::B6), (Square::H7, Square::G6)] {
+        if contains(bishops, relative(bishop_sq)) && contains(enemy_pawns, relative(pawn_sq)) {
+            score += params.trapped_bishop;
+        }
+    }
+
+    let rooks = board.pieces(Piece::Rook) & own;
+    let king_sq = board.king_square(color);
+    let castle_rights = board.castle_rights(color);
+    let kingside = [Square::F1, Square::G1].map(relative).contains(&king_sq)
+        && !castle_rights.has_kingside()
+        && [Square::G1, Square::H1, Square::H2]
+            .map(relative)
+            .iter()
+            .any(|sq| contains(rooks, *sq) && sq.get_file() > king_sq.get_file());
+    let queenside = [Square::B1, Square::C1].map(relative).contains(&king_sq)
+        && !castle_rights.has_queenside()
+        && [Square::A1, Square::B1, Square::A2]
+            .map(relative)
+            .iter()
+            .any(|sq| contains(rooks, *sq) && sq.get_file() < king_sq.get_file());
+    if kingside || queenside {
+        score += params.trapped_rook;
+    }
+    score
+}
+
+fn evaluate_positional(
+    board: &Board,
+    pawn_info: &PawnInfo,
+    color: Color,
+    params: &PieceParams,
+) -> Score {
+    // Piece specific positional terms for one side
+    bishop_pair(board, color, params)
+        + rooks_on_files(board, pawn_info, color, params)
+        + rooks_on_seventh(board, color, params)
+        + connected_rooks(board, color, params)
+        + knight_outposts(board, color, params)
+        + bad_bishops(board, color, params)
+        + trapped_pieces(board, color, params)
+}
+
+fn attacked_by(board: &Board, color: Color) -> BitBoard {
+    // Every square attacked by one side
+    let own = board.color_combined(color);
+    let mut attacks = pawn_attacks(color, board.pieces(Piece::Pawn) & own);
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for sq in board.pieces(piece) & own {
+            attacks |= piece_attacks(board, piece, sq);
+        }
+    }
+    attacks | get_king_moves(board.king_square(color))
+}
+
+fn pawn_threats(board: &Board, color: Color, params: &ThreatParams) -> Score {
+    // Enemy pieces our pawns attack, they will usually have to move
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pieces =
+        board.color_combined(!color) & !board.pieces(Piece::Pawn) & !board.pieces(Piece::King);
+    params.by_pawn * (pawn_attacks(color, own_pawns) & enemy_pieces).popcnt() as i16
+}
+
+fn minor_threats(board: &Board, color: Color, params: &ThreatParams) -> Score {
+    // Enemy rooks and queens attacked by our knights and bishops
+    let own = board.color_combined(color);
+    let enemy_majors =
+        board.color_combined(!color) & (board.pieces(Piece::Rook) | board.pieces(Piece::Queen));
+    let mut minor_attacks = EMPTY;
+    for piece in [Piece::Knight, Piece::Bishop] {
+        for sq in board.pieces(piece) & own {
+            minor_attacks |= piece_attacks(board, piece, sq);
+        }
+    }
+    params.minor_on_major * (minor_attacks & enemy_majors).popcnt() as i16
+}
+
+fn hanging_pieces(
+    board: &Board,
+    color: Color,
+    attacks: &[BitBoard; 2],
+    params: &ThreatParams,
+) -> Score {
+    // Enemy pieces we attack that nothing of theirs defends
+    let enemy_pieces = board.color_combined(!color) & !board.pieces(Piece::King);
+    let hanging = enemy_pieces & attacks[color.to_index()] & !attacks[(!color).to_index()];
+    params.hanging_piece * hanging.popcnt() as i16
+}
+
+fn pinned_pieces(board: &Board, color: Color, params: &ThreatParams) -> Score {
+    // Our pieces that can't leave the line between an enemy slider and our king
+    let king_sq = board.king_square(color);
+    let enemy = board.color_combined(!color);
+    let queens = board.pieces(Piece::Queen);
+    let diagonal = enemy & (board.pieces(Piece::Bishop) | queens) & get_bishop_rays(king_sq);
+    let straight = enemy & (board.pieces(Piece::Rook) | queens) & get_rook_rays(king_sq);
+
+    let mut pinned = EMPTY;
+    for slider in diagonal | straight {
+        let blockers = between(king_sq, slider) & board.combined();
+        if blockers.popcnt() == 1 && blockers & board.color_combined(color) != EMPTY {
+            pinned |= blockers;
+        }
+    }
+    params.pinned_piece * pinned.popcnt() as i16
+}
+
+fn safe_checks(
+    board: &Board,
+    color: Color,
+    attacks: &[BitBoard; 2],
+    params: &ThreatParams,
+) -> Score {
+    // For each kind of piece, whether one of ours can check from a square the enemy doesn't cover
+    let enemy_king = board.king_square(!color);
+    let own = board.color_combined(color);
+    let occupied = *board.combined();
+    let safe = !attacks[(!color).to_index()] & !own;
+
+    let knight_checks = get_knight_moves(enemy_king);
+    let bishop_checks = chess::get_bishop_moves(enemy_king, occupied);
+    let rook_checks = get_rook_moves(enemy_king, occupied);
+    let queen_checks = bishop_checks | rook_checks;
+
+    let mut score = Score::default();
+    for (piece, check_squares, bonus) in [
+        (Piece::Knight, knight_checks, params.safe_check_knight),
+        (Piece::Bishop, bishop_checks, params.safe_check_bishop),
+        (Piece::Rook, rook_checks, params.safe_check_rook),
+        (Piece::Queen, queen_checks, params.safe_check_queen),
+    ] {
+        let can_check = (board.pieces(piece) & own)
+            .any(|sq| piece_attacks(board, piece, sq) & check_squares & safe != EMPTY);
+        if can_check {
+            score += bonus;
+        }
+    }
+    score
+}
+
+fn evaluate_threats(
+    board: &Board,
+    color: Color,
+    attacks: &[BitBoard; 2],
+    params: &ThreatParams,
+) -> Score {
+    // Threat terms for one side, attacks holds every square each side attacks (by colour index)
+    pawn_threats(board, color, params)
+        + minor_threats(board, color, params)
+        + hanging_pieces(board, color, attacks, params)
+        + pinned_pieces(board, color, params)
+        + safe_checks(board, color, attacks, params)
 }
 
 fn up_substantial_material(material_eval: Eval, side_to_move: Color) -> bool {
@@ -150,26 +448,141 @@ fn up_substantial_material(material_eval: Eval, side_to_move: Color) -> bool {
 
 pub fn evaluate_board(board: Board) -> Eval {
     // Returns the current score on the board where white winning is positive and black winning is negative
+    evaluate(&board, default_params(), &mut NoTrace)
+}
+
+pub fn evaluate_board_with(board: Board, params: &EvalParams) -> Eval {
+    // Same as evaluate_board, with the weights and tables taken from params
+    evaluate(&board, params, &mut NoTrace)
+}
+
+pub fn evaluate_trace(board: Board) -> EvalTrace {
+    // Same as evaluate_board, but keeps every term for both sides so the score can be picked apart
+    evaluate_trace_with(board, default_params())
+}
 
-    let current_gamestate: GameState = gamestate::gamestate(&board); // Get the current gamestate
+pub fn evaluate_trace_with(board: Board, params: &EvalParams) -> EvalTrace {
+    let mut trace = EvalTrace::new(&board);
+    trace.eval = evaluate(&board, params, &mut trace).score;
+    trace
+}
 
+fn evaluate(board: &Board, params: &EvalParams, tracer: &mut impl Tracer) -> Eval {
     match board.status() {
         BoardStatus::Checkmate => {
             // We are always in checkmate with the current side to move
             // Since checkmate ends the game, we only need to asses it once
             // Since we assess after a move, it is safe to check at the child node level
+            tracer.note("checkmate");
             abs_eval_from_color(i16::MIN + 1, board.side_to_move())
         }
         BoardStatus::Stalemate => {
+            tracer.note("stalemate");
             Eval { score: 0 } // Stalemate is a draw game
         }
         BoardStatus::Ongoing => {
-            let material_eval = evaluate_board_material(&board);
-            let psqt_eval = evaluate_board_psqt(&board, current_gamestate);
+            if endgame::is_insufficient_material(board) {
+                tracer.note("insufficient material");
+                return Eval { score: 0 }; // Nobody can mate
+            }
+
+            // King and pawn versus king is looked up, draws are exact and wins get a bonus
+            let kpk_bonus = match kpk::probe(board) {
+                Some((_, false)) => {
+                    tracer.note("drawn KPK");
+                    return Eval { score: 0 };
+                }
+                Some((strong_side, true)) => abs_eval_from_color(consts::KPK_WIN_BONUS, strong_side),
+                None => Eval { score: 0 },
+            };
+
+            // The basic mates have their own evaluations that know where to drive the king
+            if let Some(mating_eval) = endgame::evaluate_mating(board, &params.material) {
+                tracer.note("basic mate");
+                return mating_eval;
+            }
+
+            // How far into the endgame we are, midgame and endgame terms are blended by it
+            let phase = gamestate::game_phase(board);
+            let pawn_info = probe_pawn_table(board);
+            let material = [Color::White, Color::Black]
+                .map(|color| evaluate_board_material(board, color, &params.material));
+            let material_eval = (material[0] - material[1]).taper(phase);
             let up_material = up_substantial_material(material_eval, board.side_to_move());
-            let king_eg_eval = endgame_king_heuristics(&board, current_gamestate, up_material);
+            let king_eg_eval = endgame_king_heuristics(board, up_material, &params.king_endgame);
+
+            // Every term is scored for both sides, the total is white minus black
+            let mut total = Score::default();
+            let mut add_term = |name: &'static str, white: Score, black: Score| {
+                tracer.term(name, white, black);
+                total += white - black;
+            };
+            add_term("Material", material[0], material[1]);
+            for (piece, name) in ALL_PIECES.into_iter().zip(PSQT_TERMS) {
+                add_term(
+                    name,
+                    evaluate_board_psqt(board, Color::White, piece, &params.psqt),
+                    evaluate_board_psqt(board, Color::Black, piece, &params.psqt),
+                );
+            }
+            add_term(
+                "Pawn structure",
+                pawn_structure::evaluate_pawn_structure(
+                    board,
+                    &pawn_info,
+                    Color::White,
+                    &params.pawns,
+                ),
+                pawn_structure::evaluate_pawn_structure(
+                    board,
+                    &pawn_info,
+                    Color::Black,
+                    &params.pawns,
+                ),
+            );
+            add_term(
+                "Mobility",
+                mobility::evaluate_mobility(board, Color::White, &params.mobility),
+                mobility::evaluate_mobility(board, Color::Black, &params.mobility),
+            );
+            add_term(
+                "King safety",
+                king_safety::evaluate_king_safety(
+                    board,
+                    &pawn_info,
+                    phase,
+                    Color::White,
+                    &params.king_safety,
+                ),
+                king_safety::evaluate_king_safety(
+                    board,
+                    &pawn_info,
+                    phase,
+                    Color::Black,
+                    &params.king_safety,
+                ),
+            );
+            add_term(
+                "Pieces",
+                evaluate_positional(board, &pawn_info, Color::White, &params.pieces),
+                evaluate_positional(board, &pawn_info, Color::Black, &params.pieces),
+            );
+            let attacks = [Color::White, Color::Black].map(|color| attacked_by(board, color));
+            add_term(
+                "Threats",
+                evaluate_threats(board, Color::White, &attacks, &params.threats),
+                evaluate_threats(board, Color::Black, &attacks, &params.threats),
+            );
+            match board.side_to_move() {
+                Color::White => add_term("King endgame", king_eg_eval, Score::default()),
+                Color::Black => add_term("King endgame", Score::default(), king_eg_eval),
+            }
 
-            material_eval + psqt_eval + king_eg_eval
+            // Drawish endings keep less of their edge
+            let scale = endgame::endgame_scale(board, total);
+            tracer.scale(scale);
+            tracer.bonus(kpk_bonus);
+            endgame::scale_endgame(total, scale).taper(phase) + kpk_bonus
         }
     }
 }
@@ -179,20 +592,28 @@ mod tests {
     use super::*;
     use crate::utils::common::Eval;
     use chess::Board;
+    use std::str::FromStr;
 
     #[test]
     fn test_default_board_material() {
+        let params = MaterialParams::default();
         let initial_board = Board::default();
-        assert_eq!(evaluate_board_material(&initial_board), Eval { score: 0 })
+        assert_eq!(
+            evaluate_board_material(&initial_board, Color::White, &params),
+            evaluate_board_material(&initial_board, Color::Black, &params)
+        )
     }
 
     #[test]
     fn test_default_board_psqt() {
+        let params = PsqtParams::default();
         let initial_board = Board::default();
-        assert_eq!(
-            evaluate_board_psqt(&initial_board, GameState::Middle),
-            Eval { score: 0 }
-        )
+        for piece in ALL_PIECES {
+            assert_eq!(
+                evaluate_board_psqt(&initial_board, Color::White, piece, &params),
+                evaluate_board_psqt(&initial_board, Color::Black, piece, &params)
+            )
+        }
     }
 
     #[test]
@@ -201,6 +622,45 @@ mod tests {
         assert_eq!(evaluate_board(initial_board), Eval { score: 0 })
     }
 
+    #[test]
+    fn test_taper() {
+        let score = Score::new(100, -100);
+        assert_eq!(score.taper(gamestate::MAX_PHASE), Eval { score: 100 });
+        assert_eq!(score.taper(0), Eval { score: -100 });
+        assert_eq!(score.taper(gamestate::MAX_PHASE / 2), Eval { score: 0 });
+        assert_eq!(score.taper(gamestate::MAX_PHASE / 4), Eval { score: -50 });
+    }
+
+    #[test]
+    fn test_king_heuristics_endgame_only() {
+        let params = KingEndgameParams::default();
+        let board = Board::from_str("8/8/8/8/3k4/8/8/K6Q w - - 0 1").unwrap();
+        let king_eval = endgame_king_heuristics(&board, false, &params);
+        assert_eq!(king_eval.mg, 0);
+        assert!(king_eval.eg < 0); // White's king is stuck in the corner
+    }
+
+    #[test]
+    fn test_queen_trade_continuous() {
+        // Trading queens used to flip the whole evaluation onto the endgame tables
+        let before = Board::from_str("r1b1kb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1B1KB1R w KQkq - 0 1").unwrap();
+        let with_queens = Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1").unwrap();
+        let diff = evaluate_board(before).score - evaluate_board(with_queens).score;
+        assert!(diff.abs() < 20);
+    }
+
+    #[test]
+    fn test_kpk() {
+        // Drawn KPK is scored as a draw, won KPK is scored well above the material
+        let drawn = Board::from_str("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1").unwrap();
+        let won = Board::from_str("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(evaluate_board(drawn), Eval { score: 0 });
+        assert!(evaluate_board(won).score > consts::KPK_WIN_BONUS);
+
+        let black_won = Board::from_str("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1").unwrap();
+        assert!(evaluate_board(black_won).score < -consts::KPK_WIN_BONUS);
+    }
+
     #[test]
     fn test_chebyshev_dist() {
         assert_eq!(chebyshev_dist(Square::F6, Square::F5), 1);
@@ -215,4 +675,212 @@ mod tests {
         assert_eq!(edge_distance(Square::E1), 0);
         assert_eq!(edge_distance(Square::E3), 2);
     }
+
+    fn pawn_info(board: &Board) -> PawnInfo {
+        pawn_structure::analyse_pawns(
+            board.pieces(Piece::Pawn) & board.color_combined(Color::White),
+            board.pieces(Piece::Pawn) & board.color_combined(Color::Black),
+        )
+    }
+
+    #[test]
+    fn test_bishop_pair() {
+        let params = PieceParams::default();
+        let pair = Board::from_str("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        let same_colour = Board::from_str("4k3/8/8/8/8/3B4/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(bishop_pair(&pair, Color::White, &params), BISHOP_PAIR);
+        assert_eq!(bishop_pair(&same_colour, Color::White, &params), Score::default());
+        assert_eq!(bishop_pair(&Board::default(), Color::Black, &params), BISHOP_PAIR);
+    }
+
+    #[test]
+    fn test_rooks_on_files() {
+        let params = PieceParams::default();
+        // The c rook is on an open file, the f and h rooks are stuck behind their own pawns
+        let board = Board::from_str("4k3/pp2pppp/8/8/8/8/PP3PPP/2R1KR1R w - - 0 1").unwrap();
+        let info = pawn_info(&board);
+        assert_eq!(rooks_on_files(&board, &info, Color::White, &params), ROOK_OPEN_FILE);
+
+        // Doubling up adds a rook on the half open d file
+        let board = Board::from_str("4k3/pp1ppppp/8/8/8/8/PP2PPPP/2RRK3 w - - 0 1").unwrap();
+        let info = pawn_info(&board);
+        assert_eq!(
+            rooks_on_files(&board, &info, Color::White, &params),
+            ROOK_OPEN_FILE + ROOK_HALF_OPEN_FILE
+        );
+    }
+
+    #[test]
+    fn test_rooks_on_seventh() {
+        let params = PieceParams::default();
+        let king_cut_off = Board::from_str("6k1/R7/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+        let nothing_there = Board::from_str("8/R7/6k1/8/8/8/8/6K1 w - - 0 1").unwrap();
+        let pawns = Board::from_str("8/R5p1/8/6k1/8/8/8/6K1 w - - 0 1").unwrap();
+        assert_eq!(rooks_on_seventh(&king_cut_off, Color::White, &params), ROOK_ON_SEVENTH);
+        assert_eq!(rooks_on_seventh(&nothing_there, Color::White, &params), Score::default());
+        assert_eq!(rooks_on_seventh(&pawns, Color::White, &params), ROOK_ON_SEVENTH);
+
+        // Black's seventh rank is the second
+        let black = Board::from_str("6k1/8/8/8/8/8/r7/6K1 w - - 0 1").unwrap();
+        assert_eq!(rooks_on_seventh(&black, Color::Black, &params), ROOK_ON_SEVENTH);
+    }
+
+    #[test]
+    fn test_connected_rooks() {
+        let params = PieceParams::default();
+        let connected = Board::from_str("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+        let split = Board::from_str("4k3/8/8/8/8/8/8/R2B1RK1 w - - 0 1").unwrap();
+        assert_eq!(connected_rooks(&connected, Color::White, &params), CONNECTED_ROOKS);
+        assert_eq!(connected_rooks(&split, Color::White, &params), Score::default());
+        assert_eq!(connected_rooks(&Board::default(), Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_knight_outposts() {
+        let params = PieceParams::default();
+        // d5 is defended by e4 and no black pawn can ever chase the knight away
+        let outpost = Board::from_str("4k3/pp4pp/8/3N4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let chased = Board::from_str("4k3/pp2p1pp/8/3N4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let undefended = Board::from_str("4k3/pp4pp/8/3N4/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(knight_outposts(&outpost, Color::White, &params), KNIGHT_OUTPOST);
+        assert_eq!(knight_outposts(&chased, Color::White, &params), Score::default());
+        assert_eq!(knight_outposts(&undefended, Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_bad_bishops() {
+        let params = PieceParams::default();
+        // Blocked pawns on c3 and e3 are both on dark squares, like the c1 bishop
+        let board = Board::from_str("4k3/8/8/8/2p1p3/2P1P3/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(bad_bishops(&board, Color::White, &params), BAD_BISHOP_PAWN * 2);
+
+        // The light squared bishop doesn't mind
+        let board = Board::from_str("4k3/8/8/8/2p1p3/2P1P3/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(bad_bishops(&board, Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_trapped_pieces() {
+        let params = PieceParams::default();
+        let bishop = Board::from_str("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(trapped_pieces(&bishop, Color::White, &params), TRAPPED_BISHOP);
+
+        let black_bishop = Board::from_str("4k3/8/8/8/8/6P1/7b/4K3 w - - 0 1").unwrap();
+        assert_eq!(trapped_pieces(&black_bishop, Color::Black, &params), TRAPPED_BISHOP);
+
+        // King walked to f1 without castling and shut the rook in
+        let rook = Board::from_str("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1").unwrap();
+        assert_eq!(trapped_pieces(&rook, Color::White, &params), TRAPPED_ROOK);
+
+        // With the right to castle it isn't trapped
+        let castling = Board::from_str("4k3/8/8/8/8/8/5PPP/4K2R w K - 0 1").unwrap();
+        assert_eq!(trapped_pieces(&castling, Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_positional_symmetric() {
+        let params = PieceParams::default();
+        let board = Board::default();
+        assert_eq!(
+            evaluate_positional(&board, &pawn_info(&board), Color::White, &params),
+            evaluate_positional(&board, &pawn_info(&board), Color::Black, &params)
+        );
+    }
+
+    fn attacks(board: &Board) -> [BitBoard; 2] {
+        [Color::White, Color::Black].map(|color| attacked_by(board, color))
+    }
+
+    #[test]
+    fn test_attacked_by() {
+        let attacks = attacked_by(&Board::default(), Color::White);
+        assert!(contains(attacks, Square::F3)); // Pawns and the knight
+        assert!(contains(attacks, Square::D2)); // Pieces defending each other count
+        assert!(!contains(attacks, Square::E4));
+    }
+
+    #[test]
+    fn test_pawn_threats() {
+        let params = ThreatParams::default();
+        // The e4 pawn forks the knight and bishop, the attacked black pawn doesn't count
+        let board = Board::from_str("4k3/8/8/3n1b2/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(pawn_threats(&board, Color::White, &params), THREAT_BY_PAWN * 2);
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(pawn_threats(&board, Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_minor_threats() {
+        let params = ThreatParams::default();
+        // The knight on c7 hits the rook and the bishop hits the queen
+        let board = Board::from_str("r5k1/2N5/8/8/8/8/6q1/4K2B w - - 0 1").unwrap();
+        assert_eq!(minor_threats(&board, Color::White, &params), THREAT_MINOR_ON_MAJOR * 2);
+    }
+
+    #[test]
+    fn test_hanging_pieces() {
+        let params = ThreatParams::default();
+        // The knight on c6 is attacked by the bishop with nothing defending it
+        let board = Board::from_str("4k3/8/2n5/8/4B3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, Color::White, &attacks(&board), &params), HANGING_PIECE);
+
+        // Once the pawn on b7 defends it, it isn't hanging anymore
+        let board = Board::from_str("4k3/1p6/2n5/8/4B3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            hanging_pieces(&board, Color::White, &attacks(&board), &params),
+            Score::default()
+        );
+    }
+
+    #[test]
+    fn test_pinned_pieces() {
+        let params = ThreatParams::default();
+        // The knight on c3 is pinned by the bishop on b4, the rook on e2 by the rook on e8
+        let board = Board::from_str("4r1k1/8/8/8/1b6/2N5/4R3/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, Color::White, &params), PINNED_PIECE * 2);
+
+        // Two pieces in the way means neither is pinned
+        let board = Board::from_str("6k1/8/8/8/1b6/2N5/3P4/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, Color::White, &params), Score::default());
+        assert_eq!(pinned_pieces(&Board::default(), Color::White, &params), Score::default());
+    }
+
+    #[test]
+    fn test_safe_checks() {
+        let params = ThreatParams::default();
+        // The rook can check from a8 along the eighth rank, the knight's only check on f6 is
+        // covered by g7
+        let board = Board::from_str("4k3/6p1/8/8/6N1/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(safe_checks(&board, Color::White, &attacks(&board), &params), SAFE_CHECK_ROOK);
+        let board = Board::default();
+        assert_eq!(
+            safe_checks(&board, Color::White, &attacks(&board), &params),
+            Score::default()
+        );
+    }
+
+    #[test]
+    fn test_threats_symmetric() {
+        let params = ThreatParams::default();
+        let board = Board::default();
+        assert_eq!(
+            evaluate_threats(&board, Color::White, &attacks(&board), &params),
+            evaluate_threats(&board, Color::Black, &attacks(&board), &params)
+        );
+    }
+
+    #[test]
+    fn test_insufficient_material_draw() {
+        let lone_bishop = Board::from_str("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1").unwrap();
+        assert_eq!(evaluate_board(lone_bishop), Eval { score: 0 });
+    }
+
+    #[test]
+    fn test_opposite_bishops_scaled() {
+        // An extra pawn is worth much less with opposite coloured bishops
+        let opposite = Board::from_str("8/5k2/8/2b5/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        let same = Board::from_str("8/5k2/8/3b4/8/1P3K2/P3B3/8 w - - 0 1").unwrap();
+        assert!(evaluate_board(opposite).score > 0);
+        assert!(evaluate_board(opposite).score < evaluate_board(same).score);
+    }
 }
@@ -0,0 +1,146 @@
+// The static evaluation the search calls, behind a trait so other evaluators can be plugged in
+// The search tells the evaluator about every move it plays on the way down and back up, so an
+// evaluator can keep its own state in step with the board instead of starting from scratch.
+// Evaluators are shared, so that state needs interior mutability (per thread if shared between
+// searches running at once), and evaluate must still be right for a board it wasn't told about
+
+use std::sync::Arc;
+
+use chess::{Board, ChessMove};
+
+use crate::eval_params::{default_params, EvalParams, MaterialParams};
+use crate::evaluation::evaluate_board_with;
+use crate::utils::common::Eval;
+
+pub trait Evaluator: Send + Sync {
+    // Score of the board, positive for White
+    fn evaluate(&self, board: &Board) -> Eval;
+
+    // Shown when the engine settings are printed
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    // A search is starting from board, anything left over from the last one can go
+    fn new_search(&self, _board: &Board) {}
+
+    // The search is about to look at the position after mve is played on board
+    fn make_move(&self, _board: &Board, _mve: ChessMove) {}
+
+    // The search is done with the position from the last make_move, back to the one before it
+    fn unmake_move(&self) {}
+
+    // Piece values the search orders and filters captures by
+    fn material(&self) -> &MaterialParams {
+        &default_params().material
+    }
+}
+
+// The built in evaluation
+#[derive(Debug, Clone, Default)]
+pub struct HandcraftedEvaluator {
+    pub params: Arc<EvalParams>,
+}
+
+impl HandcraftedEvaluator {
+    pub fn new(params: Arc<EvalParams>) -> Self {
+        HandcraftedEvaluator { params }
+    }
+}
+
+impl Evaluator for HandcraftedEvaluator {
+    fn evaluate(&self, board: &Board) -> Eval {
+        evaluate_board_with(*board, &self.params)
+    }
+
+    fn name(&self) -> &str {
+        "handcrafted"
+    }
+
+    fn material(&self) -> &MaterialParams {
+        &self.params.material
+    }
+}
+
+pub(crate) fn with_move<T>(
+    evaluator: &dyn Evaluator,
+    board: &Board,
+    mve: ChessMove,
+    search: impl FnOnce() -> T,
+) -> T {
+    // Runs the search of a child position between the evaluator's make and unmake hooks
+    evaluator.make_move(board, mve);
+    let result = search();
+    evaluator.unmake_move();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::engine::enter_engine;
+    use crate::evaluation::evaluate_board;
+    use crate::utils::engine_interface::EngineSettings;
+
+    #[derive(Default)]
+    struct TrackingEvaluator {
+        boards: Mutex<Vec<Board>>, // The board the search is on, as told by the hooks
+        evaluations: Mutex<u64>,
+    }
+
+    impl Evaluator for TrackingEvaluator {
+        fn evaluate(&self, board: &Board) -> Eval {
+            assert_eq!(self.boards.lock().unwrap().last(), Some(board));
+            *self.evaluations.lock().unwrap() += 1;
+            evaluate_board(*board)
+        }
+
+        fn new_search(&self, board: &Board) {
+            *self.boards.lock().unwrap() = vec![*board];
+        }
+
+        fn make_move(&self, board: &Board, mve: ChessMove) {
+            let mut boards = self.boards.lock().unwrap();
+            assert_eq!(boards.last(), Some(board));
+            let child = board.make_move_new(mve);
+            boards.push(child);
+        }
+
+        fn unmake_move(&self) {
+            self.boards.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn test_handcrafted_evaluator() {
+        let board =
+            Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+                .unwrap();
+        assert_eq!(
+            HandcraftedEvaluator::default().evaluate(&board),
+            evaluate_board(board)
+        );
+    }
+
+    #[test]
+    fn test_hooks_follow_search() {
+        // Every position the search evaluates is the one the hooks say it is on
+        let evaluator = Arc::new(TrackingEvaluator::default());
+        let settings = EngineSettings {
+            time_limit: Duration::from_secs(1),
+            evaluator: Some(evaluator.clone()),
+            ..Default::default()
+        };
+        let board =
+            Board::from_str("r3k2r/pp3ppp/2n1b3/3p4/3P4/2N1B3/PP3PPP/R3K2R w KQkq - 0 1").unwrap();
+        let (eng_move, _) = enter_engine(board, settings);
+
+        assert!(board.legal(eng_move));
+        assert!(*evaluator.evaluations.lock().unwrap() > 0);
+        assert_eq!(*evaluator.boards.lock().unwrap(), vec![board]);
+    }
+}
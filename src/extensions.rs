@@ -1,84 +1,240 @@
-use chess::{BitBoard, Board, BoardStatus, Piece, EMPTY};
+use chess::{BitBoard, Board, BoardStatus, ChessMove, Color, Piece, Square, EMPTY};
 
 use crate::consts;
+use crate::search_config::SearchConfig;
 
-pub(crate) fn should_extend(board: &Board, current_extension: u8) -> bool {
-    // Returns true if the search should be extended
-    // let bb_7th_rank: BitBoard = BitBoard(0xff000000000000);
-    // let bb_1st_rank: BitBoard = BitBoard(0b1111111100000000);
+// Extensions are measured in fractions of a ply (see consts::ONE_PLY) and accumulate along a path
+// of the search tree until the config's extension budget is used up
 
+pub(crate) struct ExtensionContext {
+    pub(crate) num_moves: usize,           // Number of legal moves at the node
+    pub(crate) last_capture: Option<Square>, // Square the previous move captured on, if any
+    pub(crate) singular_move: Option<ChessMove>, // Move the singular search found to be singular
+}
+
+pub(crate) fn should_extend(
+    board: &Board,
+    current_extension: u8,
+    config: &SearchConfig,
+) -> Option<u8> {
+    // Returns the extension needed to search past the horizon when we reach it in check
     let should_extend_for_check = *board.checkers() != EMPTY;
 
-    // let should_extend_for_promote_black =
-    //     (board.pieces(Piece::Pawn) & board.color_combined(chess::Color::Black) & bb_1st_rank)
-    //         .popcnt()
-    //         > 0;
+    // Round up to the next whole ply so that the node is no longer at the horizon
+    let to_next_ply = consts::ONE_PLY - current_extension % consts::ONE_PLY;
+    let not_constrained = current_extension + to_next_ply <= config.extension_budget()
+        && board.status() == BoardStatus::Ongoing;
+
+    if should_extend_for_check && not_constrained {
+        Some(to_next_ply)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn check_extension(child_board: &Board, config: &SearchConfig) -> u8 {
+    // The move gives check
+    if *child_board.checkers() != EMPTY {
+        config.check_extension
+    } else {
+        0
+    }
+}
+
+pub(crate) fn single_reply_extension(num_moves: usize, config: &SearchConfig) -> u8 {
+    // Only one legal move at this node, it is forced so searching it deeper is cheap
+    if num_moves == 1 {
+        config.single_reply_extension
+    } else {
+        0
+    }
+}
 
-    // let should_extend_for_promote_white =
-    //     (board.pieces(Piece::Pawn) & board.color_combined(chess::Color::White) & bb_7th_rank)
-    //         .popcnt()
-    //         > 0;
+pub(crate) fn recapture_extension(
+    board: &Board,
+    mve: ChessMove,
+    last_capture: Option<Square>,
+    config: &SearchConfig,
+) -> u8 {
+    // The move captures back on the square the previous move captured on
+    match last_capture {
+        Some(sq) if mve.get_dest() == sq && board.piece_on(sq).is_some() => config.recapture_extension,
+        _ => 0,
+    }
+}
 
-    // let should_extend_for_promote = match board.side_to_move(){
-    //     chess::Color::White => should_extend_for_promote_white,
-    //     chess::Color::Black => should_extend_for_promote_black,
-    // };
+pub(crate) fn pawn_seventh_extension(board: &Board, mve: ChessMove, config: &SearchConfig) -> u8 {
+    // A pawn is pushed to the seventh rank (relative to the mover), one step from promoting
+    let seventh_rank: BitBoard = match board.side_to_move() {
+        Color::White => BitBoard(0x00ff_0000_0000_0000),
+        Color::Black => BitBoard(0x0000_0000_0000_ff00),
+    };
+
+    let is_pawn = board.piece_on(mve.get_source()) == Some(Piece::Pawn);
+    if is_pawn && (BitBoard::from_square(mve.get_dest()) & seventh_rank) != EMPTY {
+        config.pawn_seventh_extension
+    } else {
+        0
+    }
+}
 
-    let not_constrained =
-        current_extension < consts::EXTENSION_LIM && board.status() == BoardStatus::Ongoing;
+pub(crate) fn singular_extension(
+    mve: ChessMove,
+    singular_move: Option<ChessMove>,
+    config: &SearchConfig,
+) -> u8 {
+    // The move was the only one to hold the TT score in the excluded-move search
+    if singular_move == Some(mve) {
+        config.singular_extension
+    } else {
+        0
+    }
+}
 
-    // (should_extend_for_check || should_extend_for_promote)
-    //     && not_constrained
-    (should_extend_for_check) && not_constrained
+pub(crate) fn move_extension(
+    board: &Board,
+    child_board: &Board,
+    mve: ChessMove,
+    context: &ExtensionContext,
+    config: &SearchConfig,
+) -> u8 {
+    // Combined extension for a move, a single move never extends more than one ply
+    let extension = check_extension(child_board, config)
+        + single_reply_extension(context.num_moves, config)
+        + recapture_extension(board, mve, context.last_capture, config)
+        + pawn_seventh_extension(board, mve, config)
+        + singular_extension(mve, context.singular_move, config);
+
+    extension.min(consts::ONE_PLY)
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use std::str::FromStr;
-
-//     use chess::Board;
-
-//     use crate::{consts, extensions::should_extend};
-
-//     #[test]
-//     fn test_default_board() {
-//         let board = Board::default();
-//         assert!(!should_extend(&board, 0))
-//     }
-
-//     #[test]
-//     fn test_white_pawn() {
-//         let board = Board::from_str("3K4/6P1/8/8/8/8/8/3k4 w - - 0 1").unwrap();
-//         assert!(should_extend(&board, 0))
-//     }
-
-//     #[test]
-//     fn test_white_pawn_blacks_turn() {
-//         let board = Board::from_str("3K4/6P1/8/8/8/8/8/3k4 b - - 0 1").unwrap();
-//         assert!(!should_extend(&board, 0))
-//     }
-
-//     #[test]
-//     fn test_black_pawn() {
-//         let board = Board::from_str("3K4/8/8/8/8/8/6p1/3k4 b - - 0 1").unwrap();
-//         assert!(should_extend(&board, 0))
-//     }
-
-//     #[test]
-//     fn test_black_pawn_whites_turn() {
-//         let board = Board::from_str("3K4/8/8/8/8/8/6p1/3k4 w - - 0 1").unwrap();
-//         assert!(!should_extend(&board, 0))
-//     }
-
-//     #[test]
-//     fn test_limit() {
-//         let board = Board::from_str("3K4/8/8/8/8/8/6p1/3k4 b - - 0 1").unwrap();
-//         assert!(!should_extend(&board, consts::EXTENSION_LIM))
-//     }
-
-//     #[test]
-//     fn test_check() {
-//         let board = Board::from_str("3K4/8/8/8/6Q1/8/8/3k4 b - - 0 1").unwrap();
-//         assert!(should_extend(&board, 0))
-//     }
-// }
+pub(crate) fn apply_extension(current_extension: u8, extension: u8, config: &SearchConfig) -> u8 {
+    // Add an extension to the path, constrained by the extension budget
+    (current_extension + extension).min(config.extension_budget())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove, MoveGen, Square};
+
+    use crate::consts;
+
+    use super::*;
+
+    #[test]
+    fn test_default_board() {
+        let config = SearchConfig::default();
+        let board = Board::default();
+        assert_eq!(should_extend(&board, 0, &config), None)
+    }
+
+    #[test]
+    fn test_check() {
+        let config = SearchConfig::default();
+        let board = Board::from_str("3K4/8/8/8/6Q1/8/8/3k4 b - - 0 1").unwrap();
+        assert_eq!(should_extend(&board, 0, &config), Some(consts::ONE_PLY));
+        // A partial extension is rounded up to the next ply
+        assert_eq!(should_extend(&board, 1, &config), Some(consts::ONE_PLY - 1));
+    }
+
+    #[test]
+    fn test_limit() {
+        let config = SearchConfig::default();
+        let board = Board::from_str("3K4/8/8/8/6Q1/8/8/3k4 b - - 0 1").unwrap();
+        assert_eq!(should_extend(&board, config.extension_budget(), &config), None)
+    }
+
+    #[test]
+    fn test_check_extension() {
+        let config = SearchConfig::default();
+        let board = Board::from_str("3K4/8/8/7Q/8/8/8/k7 w - - 0 1").unwrap();
+        let checking = board.make_move_new(ChessMove::new(Square::H5, Square::H1, None));
+        let quiet = board.make_move_new(ChessMove::new(Square::H5, Square::H6, None));
+        assert_eq!(check_extension(&checking, &config), config.check_extension);
+        assert_eq!(check_extension(&quiet, &config), 0);
+    }
+
+    #[test]
+    fn test_single_reply() {
+        let config = SearchConfig::default();
+        // Black king in the corner can only escape to one square
+        let board = Board::from_str("k6R/8/2K5/8/8/8/8/8 b - - 0 1").unwrap();
+        let num_moves = MoveGen::new_legal(&board).len();
+        assert_eq!(single_reply_extension(num_moves, &config), config.single_reply_extension);
+        assert_eq!(single_reply_extension(MoveGen::new_legal(&Board::default()).len(), &config), 0);
+    }
+
+    #[test]
+    fn test_recapture() {
+        let config = SearchConfig::default();
+        // White has just captured on d5, black recaptures with the pawn
+        let board = Board::from_str("3k4/8/4p3/3N4/8/8/8/3K4 b - - 0 1").unwrap();
+        let recapture = ChessMove::new(Square::E6, Square::D5, None);
+        let other = ChessMove::new(Square::D8, Square::D7, None);
+        assert_eq!(
+            recapture_extension(&board, recapture, Some(Square::D5), &config),
+            config.recapture_extension
+        );
+        assert_eq!(recapture_extension(&board, other, Some(Square::D5), &config), 0);
+        assert_eq!(recapture_extension(&board, recapture, None, &config), 0);
+    }
+
+    #[test]
+    fn test_white_pawn_seventh() {
+        let config = SearchConfig::default();
+        let board = Board::from_str("3K4/8/6P1/8/8/8/8/3k4 w - - 0 1").unwrap();
+        let push = ChessMove::new(Square::G6, Square::G7, None);
+        assert_eq!(pawn_seventh_extension(&board, push, &config), config.pawn_seventh_extension);
+        let king_move = ChessMove::new(Square::D8, Square::C7, None);
+        assert_eq!(pawn_seventh_extension(&board, king_move, &config), 0);
+    }
+
+    #[test]
+    fn test_black_pawn_seventh() {
+        let config = SearchConfig::default();
+        let board = Board::from_str("3K4/8/8/8/8/6p1/8/3k4 b - - 0 1").unwrap();
+        let push = ChessMove::new(Square::G3, Square::G2, None);
+        assert_eq!(pawn_seventh_extension(&board, push, &config), config.pawn_seventh_extension);
+
+        // Pushes short of the seventh rank are not extended
+        let board = Board::from_str("3K4/8/8/8/8/8/6P1/3k4 w - - 0 1").unwrap();
+        let push = ChessMove::new(Square::G2, Square::G3, None);
+        assert_eq!(pawn_seventh_extension(&board, push, &config), 0);
+    }
+
+    #[test]
+    fn test_singular() {
+        let config = SearchConfig::default();
+        let mve = ChessMove::new(Square::E2, Square::E4, None);
+        let other = ChessMove::new(Square::D2, Square::D4, None);
+        assert_eq!(singular_extension(mve, Some(mve), &config), config.singular_extension);
+        assert_eq!(singular_extension(other, Some(mve), &config), 0);
+        assert_eq!(singular_extension(mve, None, &config), 0);
+    }
+
+    #[test]
+    fn test_move_extension_capped() {
+        let config = SearchConfig::default();
+        // A singular checking move at a single reply node is still only extended by a ply
+        let board = Board::from_str("8/3k4/8/4P3/8/8/8/3K4 w - - 0 1").unwrap();
+        let mve = ChessMove::new(Square::E5, Square::E6, None);
+        let context = ExtensionContext {
+            num_moves: 1,
+            last_capture: None,
+            singular_move: Some(mve),
+        };
+        let extension = move_extension(&board, &board.make_move_new(mve), mve, &context, &config);
+        assert_eq!(extension, consts::ONE_PLY);
+    }
+
+    #[test]
+    fn test_budget() {
+        let config = SearchConfig::default();
+        let budget = config.extension_budget();
+        assert_eq!(apply_extension(0, consts::ONE_PLY, &config), consts::ONE_PLY);
+        assert_eq!(apply_extension(budget - 1, consts::ONE_PLY, &config), budget);
+        assert_eq!(apply_extension(budget, consts::ONE_PLY, &config), budget);
+    }
+}
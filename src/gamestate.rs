@@ -1,32 +1,26 @@
-// This file contains utilities to monitor the game state (opening/middle/endgame)
+// This file contains utilities to monitor the game state (how far we are from the endgame)
 
-use chess::{Board, Piece, Color};
+use chess::{Board, Piece};
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum GameState {
-    _Opening,
-    Middle,
-    End,
-}
-
-pub fn gamestate(board: &Board) -> GameState {
-
-    let white_has_queen = (board.pieces(Piece::Queen) & board.color_combined(Color::White)).popcnt() >= 1;
-    let black_has_queen = (board.pieces(Piece::Queen) & board.color_combined(Color::Black)).popcnt() >= 1;
-    
-    let neither_side_has_a_queen =  !white_has_queen && !black_has_queen;
+// Phase runs from 0 (only kings and pawns left) up to MAX_PHASE (every piece still on the board)
+pub(crate) const MAX_PHASE: i16 = 256;
 
-    // If a side has a queen, but only one minor piece left we can also consider this endgame
-    let white_has_multiple_minor = ((board.pieces(Piece::Knight) | board.pieces(Piece::Bishop)) & board.color_combined(Color::White)).popcnt() > 1;
-    let black_has_multiple_minor = ((board.pieces(Piece::Knight) | board.pieces(Piece::Bishop)) & board.color_combined(Color::Black)).popcnt() > 1;
+// Contribution of each remaining piece, a full set of pieces adds up to PHASE_TOTAL
+const KNIGHT_PHASE: i16 = 1;
+const BISHOP_PHASE: i16 = 1;
+const ROOK_PHASE: i16 = 2;
+const QUEEN_PHASE: i16 = 4;
+const PHASE_TOTAL: i16 = 4 * KNIGHT_PHASE + 4 * BISHOP_PHASE + 4 * ROOK_PHASE + 2 * QUEEN_PHASE;
 
-    // If neither side has a queen, or if either colour has a queen BUT only has one minor piece we declare endgame
-    if neither_side_has_a_queen || ((!white_has_multiple_minor && white_has_queen) || (!black_has_multiple_minor && black_has_queen)) {
-        return GameState::End
-    }
+pub fn game_phase(board: &Board) -> i16 {
+    // Computed from the non-pawn material of both sides, promotions can't push it past MAX_PHASE
+    let material = board.pieces(Piece::Knight).popcnt() as i16 * KNIGHT_PHASE
+        + board.pieces(Piece::Bishop).popcnt() as i16 * BISHOP_PHASE
+        + board.pieces(Piece::Rook).popcnt() as i16 * ROOK_PHASE
+        + board.pieces(Piece::Queen).popcnt() as i16 * QUEEN_PHASE;
 
-    // Otherwise middle game, TODO add opening
-    GameState::Middle
+    let material = material.min(PHASE_TOTAL);
+    (material * MAX_PHASE + PHASE_TOTAL / 2) / PHASE_TOTAL
 }
 
 #[cfg(test)]
@@ -36,37 +30,39 @@ mod tests {
     use chess::Board;
 
     #[test]
-    fn test_endgame_kings(){
-        let fen = "8/K7/4P3/8/8/8/4p3/k7 w - - 0 1";
-        let board: Board = Board::from_str(fen).unwrap();
-        assert_eq!(gamestate(&board), GameState::End)
+    fn test_starting_position(){
+        assert_eq!(game_phase(&Board::default()), MAX_PHASE)
     }
 
     #[test]
-    fn test_endgame_multipiece(){
-        let fen = "8/KQ4n1/4P3/1R6/3r4/8/4p1N1/kq6 w - - 0 1";
+    fn test_endgame_kings(){
+        let fen = "8/K7/4P3/8/8/8/4p3/k7 w - - 0 1";
         let board: Board = Board::from_str(fen).unwrap();
-        assert_eq!(gamestate(&board), GameState::End)
+        assert_eq!(game_phase(&board), 0)
     }
 
     #[test]
-    fn test_asymetric_endgame_middlepiece(){
-        let fen = "8/KQ6/4P3/1R6/8/8/6N1/k7 w - - 0 1";
-        let board: Board = Board::from_str(fen).unwrap();
-        assert_eq!(gamestate(&board), GameState::End)       
+    fn test_queen_trade(){
+        // Trading queens moves a third of the way towards the endgame
+        let board = Board::from_str("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+        assert_eq!(game_phase(&board), 171)
     }
 
     #[test]
-    fn test_middlegame(){
-        let fen = "7B/KQ6/4P3/1R6/8/8/k5N1/1q1nr2b w - - 0 1";
-        let board: Board = Board::from_str(fen).unwrap();
-        assert_eq!(gamestate(&board), GameState::Middle)   
+    fn test_monotonic(){
+        // Removing pieces never moves the phase back towards the midgame
+        let middle = Board::from_str("7B/KQ6/4P3/1R6/8/8/k5N1/1q1nr2b w - - 0 1").unwrap();
+        let fewer = Board::from_str("7B/KQ6/4P3/1R6/8/8/k7/1q2r2b w - - 0 1").unwrap();
+        let no_queens = Board::from_str("7B/K7/4P3/1R6/8/8/k7/4r2b w - - 0 1").unwrap();
+        assert!(game_phase(&middle) > game_phase(&fewer));
+        assert!(game_phase(&fewer) > game_phase(&no_queens));
+        assert!(game_phase(&no_queens) > 0);
     }
 
     #[test]
-    fn test_asymmetric_middlegame(){
-        let fen = "7B/K7/8/8/8/8/k7/1q1nr2b w - - 0 1";
+    fn test_promotions_capped(){
+        let fen = "QQQQ4/8/8/7k/8/7K/8/qqqq4 w - - 0 1";
         let board: Board = Board::from_str(fen).unwrap();
-        assert_eq!(gamestate(&board), GameState::Middle)   
+        assert_eq!(game_phase(&board), MAX_PHASE)
     }
-}
\ No newline at end of file
+}
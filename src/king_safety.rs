@@ -0,0 +1,344 @@
+// King safety evaluation
+// Looks at the pawns in front of each king, enemy pawns advancing on it, open files next to it
+// and how many enemy pieces are bearing down on the squares around it
+// Only matters while there is enough material left to mount an attack
+
+use chess::{get_file, get_king_moves, BitBoard, Board, Color, File, Piece, Square, EMPTY};
+
+use crate::eval_params::fixed_array;
+use crate::gamestate::MAX_PHASE;
+use crate::mobility::piece_attacks;
+use crate::pawn_structure::PawnInfo;
+use crate::utils::bitboards::{contains, forward_ranks, relative_rank};
+use crate::utils::common::Score;
+
+// Below this phase there isn't enough material left for a king hunt
+const KING_SAFETY_MIN_PHASE: i16 = MAX_PHASE / 4;
+
+// Indexed by the relative rank of our closest pawn in front of the king, 0 means no pawn
+const PAWN_SHELTER: [i16; 8] = [-30, 25, 15, 5, 0, 0, 0, 0];
+
+// Indexed by the relative rank (from our side) of the closest enemy pawn in front of the king
+const PAWN_STORM: [i16; 8] = [0, 10, 30, 20, 10, 5, 0, 0];
+const PAWN_STORM_BLOCKED: i16 = 5; // Storming pawn stuck right in front of one of our pawns
+
+const OPEN_FILE_NEAR_KING: i16 = -20;
+const HALF_OPEN_FILE_NEAR_KING: i16 = -10;
+
+// Attack units per attacked square around the king
+const KNIGHT_ATTACK_WEIGHT: i16 = 2;
+const BISHOP_ATTACK_WEIGHT: i16 = 2;
+const ROOK_ATTACK_WEIGHT: i16 = 3;
+const QUEEN_ATTACK_WEIGHT: i16 = 5;
+
+// Penalty for the attack units on the king zone, grows slowly at first then quickly
+#[rustfmt::skip]
+const SAFETY_TABLE: [i16; 100] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct KingSafetyParams {
+    pub pawn_shelter: [i16; 8],
+    pub pawn_storm: [i16; 8],
+    pub pawn_storm_blocked: i16,
+    pub open_file: i16,
+    pub half_open_file: i16,
+    pub knight_attack_weight: i16,
+    pub bishop_attack_weight: i16,
+    pub rook_attack_weight: i16,
+    pub queen_attack_weight: i16,
+    #[serde(with = "fixed_array")]
+    pub safety_table: [i16; 100],
+}
+
+impl Default for KingSafetyParams {
+    fn default() -> Self {
+        KingSafetyParams {
+            pawn_shelter: PAWN_SHELTER,
+            pawn_storm: PAWN_STORM,
+            pawn_storm_blocked: PAWN_STORM_BLOCKED,
+            open_file: OPEN_FILE_NEAR_KING,
+            half_open_file: HALF_OPEN_FILE_NEAR_KING,
+            knight_attack_weight: KNIGHT_ATTACK_WEIGHT,
+            bishop_attack_weight: BISHOP_ATTACK_WEIGHT,
+            rook_attack_weight: ROOK_ATTACK_WEIGHT,
+            queen_attack_weight: QUEEN_ATTACK_WEIGHT,
+            safety_table: SAFETY_TABLE,
+        }
+    }
+}
+
+fn shelter_files(king_sq: Square) -> [BitBoard; 3] {
+    // The king's file and its neighbours, a king on the edge uses the three files nearest it
+    let centre = king_sq.get_file().to_index().clamp(1, 6);
+    [centre - 1, centre, centre + 1].map(|file| get_file(File::from_index(file)))
+}
+
+fn closest_pawn_rank(pawns: BitBoard, color: Color) -> usize {
+    // Lowest relative rank out of the pawns, 0 if there are none
+    pawns.map(|sq| relative_rank(color, sq)).min().unwrap_or(0)
+}
+
+fn pawn_shelter(board: &Board, color: Color, params: &KingSafetyParams) -> i16 {
+    // Our pawns level with or in front of the king on each file around it
+    let king_sq = board.king_square(color);
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let in_front = forward_ranks(color, king_sq) | chess::get_rank(king_sq.get_rank());
+
+    let mut shelter = 0;
+    for file in shelter_files(king_sq) {
+        let file_pawns = own_pawns & in_front & file;
+        shelter += params.pawn_shelter[closest_pawn_rank(file_pawns, color)];
+    }
+    shelter
+}
+
+fn pawn_storm(board: &Board, color: Color, params: &KingSafetyParams) -> i16 {
+    // Enemy pawns advancing on the files around our king, returned as a penalty
+    let king_sq = board.king_square(color);
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let in_front = forward_ranks(color, king_sq);
+
+    let mut storm = 0;
+    for file in shelter_files(king_sq) {
+        let file_pawns = enemy_pawns & in_front & file;
+        let Some(closest) = file_pawns.min_by_key(|sq| relative_rank(color, *sq)) else {
+            continue;
+        };
+        let blocked = closest
+            .backward(color)
+            .is_some_and(|behind| contains(own_pawns, behind));
+        storm += match blocked {
+            true => params.pawn_storm_blocked,
+            false => params.pawn_storm[relative_rank(color, closest)],
+        };
+    }
+    -storm
+}
+
+fn open_files_near_king(
+    board: &Board,
+    pawn_info: &PawnInfo,
+    color: Color,
+    params: &KingSafetyParams,
+) -> i16 {
+    // Files around the king with no pawns, or none of ours, give the enemy rooks a way in
+    let mut score = 0;
+    for file in shelter_files(board.king_square(color)) {
+        if file & pawn_info.open_files != EMPTY {
+            score += params.open_file;
+        } else if file & pawn_info.half_open_files[color.to_index()] != EMPTY {
+            score += params.half_open_file;
+        }
+    }
+    score
+}
+
+fn king_zone(king_sq: Square) -> BitBoard {
+    get_king_moves(king_sq) | BitBoard::from_square(king_sq)
+}
+
+fn king_attack(board: &Board, color: Color, params: &KingSafetyParams) -> (i16, i16) {
+    // Number of enemy pieces attacking the zone around our king, and the attack units they add up to
+    let zone = king_zone(board.king_square(color));
+    let mut attackers = 0;
+    let mut units = 0;
+    for (piece, weight) in [
+        (Piece::Knight, params.knight_attack_weight),
+        (Piece::Bishop, params.bishop_attack_weight),
+        (Piece::Rook, params.rook_attack_weight),
+        (Piece::Queen, params.queen_attack_weight),
+    ] {
+        for sq in board.pieces(piece) & board.color_combined(!color) {
+            let attacked = piece_attacks(board, piece, sq) & zone;
+            if attacked != EMPTY {
+                attackers += 1;
+                units += weight * attacked.popcnt() as i16;
+            }
+        }
+    }
+    (attackers, units)
+}
+
+fn king_attack_penalty(board: &Board, color: Color, params: &KingSafetyParams) -> i16 {
+    // A single attacker is rarely dangerous on its own
+    let (attackers, units) = king_attack(board, color, params);
+    if attackers < 2 {
+        return 0;
+    }
+    let table = &params.safety_table;
+    -table[(units.max(0) as usize).min(table.len() - 1)]
+}
+
+fn king_safety_colour(
+    board: &Board,
+    pawn_info: &PawnInfo,
+    color: Color,
+    params: &KingSafetyParams,
+) -> i16 {
+    // Safety of one side's king, positive is good for that side
+    pawn_shelter(board, color, params)
+        + pawn_storm(board, color, params)
+        + open_files_near_king(board, pawn_info, color, params)
+        + king_attack_penalty(board, color, params)
+}
+
+pub(crate) fn evaluate_king_safety(
+    board: &Board,
+    pawn_info: &PawnInfo,
+    phase: i16,
+    color: Color,
+    params: &KingSafetyParams,
+) -> Score {
+    // King safety of one side, only counted in the midgame
+    if phase < KING_SAFETY_MIN_PHASE {
+        return Score::default();
+    }
+    Score::new(king_safety_colour(board, pawn_info, color, params), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+    use crate::gamestate::game_phase;
+    use crate::pawn_structure::analyse_pawns;
+
+    fn params() -> KingSafetyParams {
+        KingSafetyParams::default()
+    }
+
+    fn pawn_info(board: &Board) -> PawnInfo {
+        analyse_pawns(
+            board.pieces(Piece::Pawn) & board.color_combined(Color::White),
+            board.pieces(Piece::Pawn) & board.color_combined(Color::Black),
+        )
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let board = Board::default();
+        let info = pawn_info(&board);
+        assert_eq!(
+            evaluate_king_safety(&board, &info, MAX_PHASE, Color::White, &params()),
+            evaluate_king_safety(&board, &info, MAX_PHASE, Color::Black, &params())
+        );
+    }
+
+    #[test]
+    fn test_pawn_shelter() {
+        // Pushing the g pawn weakens the shelter, losing it entirely is worse still
+        let intact = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        let pushed = Board::from_str("6k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1").unwrap();
+        let missing = Board::from_str("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_shelter(&intact, Color::White, &params()),
+            3 * PAWN_SHELTER[1]
+        );
+        assert!(
+            pawn_shelter(&intact, Color::White, &params())
+                > pawn_shelter(&pushed, Color::White, &params())
+        );
+        assert!(
+            pawn_shelter(&pushed, Color::White, &params())
+                > pawn_shelter(&missing, Color::White, &params())
+        );
+
+        // Same for black, a king in the corner still looks at three files
+        let black = Board::from_str("7k/6pp/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_shelter(&black, Color::Black, &params()),
+            2 * PAWN_SHELTER[1] + PAWN_SHELTER[0]
+        );
+    }
+
+    #[test]
+    fn test_pawn_storm() {
+        // Black pawns on g4 and h4 are bearing down on the white king
+        let storm = Board::from_str("6k1/5p2/8/8/6pp/8/5PPP/6K1 w - - 0 1").unwrap();
+        let quiet = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_storm(&storm, Color::White, &params()),
+            -2 * PAWN_STORM[3] - PAWN_STORM[6]
+        );
+        assert!(
+            pawn_storm(&storm, Color::White, &params())
+                < pawn_storm(&quiet, Color::White, &params())
+        );
+
+        // Once a storming pawn runs into our pawn it is much less of a threat
+        let blocked = Board::from_str("6k1/8/8/8/8/6p1/6P1/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_storm(&blocked, Color::White, &params()),
+            -PAWN_STORM_BLOCKED
+        );
+    }
+
+    #[test]
+    fn test_open_files() {
+        // The g file is open and the h file half open around the white king
+        let board = Board::from_str("6k1/5p1p/8/8/8/8/5P2/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            open_files_near_king(&board, &pawn_info(&board), Color::White, &params()),
+            OPEN_FILE_NEAR_KING + HALF_OPEN_FILE_NEAR_KING
+        );
+    }
+
+    #[test]
+    fn test_king_attack() {
+        // Queen and knight both hit the squares around the castled king
+        let board = Board::from_str("6k1/5ppp/8/6NQ/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        let (attackers, units) = king_attack(&board, Color::Black, &params());
+        assert_eq!(attackers, 2);
+        assert!(units > 0);
+        assert!(king_attack_penalty(&board, Color::Black, &params()) < 0);
+
+        // The knight on its own isn't counted
+        let lone = Board::from_str("6k1/5ppp/8/6N1/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        assert_eq!(king_attack(&lone, Color::Black, &params()).0, 1);
+        assert_eq!(king_attack_penalty(&lone, Color::Black, &params()), 0);
+    }
+
+    #[test]
+    fn test_safety_table() {
+        // Penalty never shrinks as the attack units go up
+        assert!(SAFETY_TABLE.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_disabled_in_endgame() {
+        // An exposed king only matters while there are pieces around to attack it
+        let board = Board::from_str("6k1/8/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let phase = game_phase(&board);
+        assert!(phase < KING_SAFETY_MIN_PHASE);
+        assert_eq!(
+            evaluate_king_safety(&board, &pawn_info(&board), phase, Color::Black, &params()),
+            Score::default()
+        );
+        assert_ne!(
+            evaluate_king_safety(
+                &board,
+                &pawn_info(&board),
+                MAX_PHASE,
+                Color::Black,
+                &params()
+            ),
+            Score::default()
+        );
+    }
+}
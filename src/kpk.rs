@@ -0,0 +1,246 @@
+// King and pawn versus king bitbase
+// Built once on first use by iterating from the positions we know the result of (promotions,
+// stalemates and pawn captures) back through the rest until nothing changes
+
+use std::sync::OnceLock;
+
+use chess::{get_king_moves, get_pawn_attacks, BitBoard, Board, Color, Piece, Square, ALL_SQUARES, EMPTY};
+
+// Positions are stored with the strong side as white and the pawn on files a-d
+// Index bits: white king (6), black king (6), side to move (1), pawn file (2), 7 - pawn rank (3)
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+
+static BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum KpkResult {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn index(white_to_move: bool, black_king: usize, white_king: usize, pawn: usize) -> usize {
+    white_king
+        | black_king << 6
+        | (!white_to_move as usize) << 12
+        | (pawn % 8) << 13
+        | (6 - pawn / 8) << 15
+}
+
+fn king_attacks(sq: usize) -> BitBoard {
+    get_king_moves(ALL_SQUARES[sq])
+}
+
+fn pawn_attacks(sq: usize) -> BitBoard {
+    get_pawn_attacks(ALL_SQUARES[sq], Color::White, !EMPTY)
+}
+
+fn contains(bitboard: BitBoard, sq: usize) -> bool {
+    bitboard & BitBoard(1 << sq) != EMPTY
+}
+
+fn distance(sq_1: usize, sq_2: usize) -> usize {
+    let rank_diff = (sq_1 / 8).abs_diff(sq_2 / 8);
+    let file_diff = (sq_1 % 8).abs_diff(sq_2 % 8);
+    rank_diff.max(file_diff)
+}
+
+fn initial_result(idx: usize) -> KpkResult {
+    // Classify the positions that can be decided without looking at any moves
+    let white_king = idx & 0x3f;
+    let black_king = (idx >> 6) & 0x3f;
+    let white_to_move = (idx >> 12) & 1 == 0;
+    let pawn = (6 - (idx >> 15)) * 8 + ((idx >> 13) & 3);
+
+    if distance(white_king, black_king) <= 1
+        || white_king == pawn
+        || black_king == pawn
+        || (white_to_move && contains(pawn_attacks(pawn), black_king))
+    {
+        // Kings touching, pieces on the same square or black in check with white to move
+        return KpkResult::Invalid;
+    }
+
+    if white_to_move && pawn / 8 == 6 {
+        // Promotes and the new queen can't be taken
+        let promotion = pawn + 8;
+        if white_king != promotion
+            && black_king != promotion
+            && (distance(black_king, promotion) > 1 || contains(king_attacks(white_king), promotion))
+        {
+            return KpkResult::Win;
+        }
+    }
+
+    if !white_to_move {
+        let black_moves = king_attacks(black_king) & !(king_attacks(white_king) | pawn_attacks(pawn));
+        if black_moves == EMPTY {
+            return KpkResult::Draw; // Stalemate
+        }
+        if contains(king_attacks(black_king), pawn) && !contains(king_attacks(white_king), pawn) {
+            return KpkResult::Draw; // Black takes the undefended pawn
+        }
+    }
+
+    KpkResult::Unknown
+}
+
+fn classify(idx: usize, db: &[KpkResult]) -> KpkResult {
+    // Result of a position from the results of the positions it can move to
+    // White needs one winning move, black needs one drawing move
+    let white_king = idx & 0x3f;
+    let black_king = (idx >> 6) & 0x3f;
+    let white_to_move = (idx >> 12) & 1 == 0;
+    let pawn = (6 - (idx >> 15)) * 8 + ((idx >> 13) & 3);
+
+    let mut successors: Vec<KpkResult> = Vec::with_capacity(10);
+    if white_to_move {
+        for sq in king_attacks(white_king) {
+            successors.push(db[index(false, black_king, sq.to_index(), pawn)]);
+        }
+        // Single push (pushes to the eighth rank are handled by the initial classification)
+        if pawn / 8 < 6 {
+            successors.push(db[index(false, black_king, white_king, pawn + 8)]);
+        }
+        // Double push
+        if pawn / 8 == 1 && pawn + 8 != white_king && pawn + 8 != black_king {
+            successors.push(db[index(false, black_king, white_king, pawn + 16)]);
+        }
+    } else {
+        for sq in king_attacks(black_king) {
+            successors.push(db[index(true, sq.to_index(), white_king, pawn)]);
+        }
+    }
+
+    let (good, bad) = match white_to_move {
+        true => (KpkResult::Win, KpkResult::Draw),
+        false => (KpkResult::Draw, KpkResult::Win),
+    };
+    if successors.contains(&good) {
+        good
+    } else if successors.contains(&KpkResult::Unknown) {
+        KpkResult::Unknown
+    } else {
+        bad
+    }
+}
+
+fn generate() -> Vec<u64> {
+    let mut db: Vec<KpkResult> = (0..MAX_INDEX).map(initial_result).collect();
+
+    // Keep resolving positions until a pass makes no progress, everything left over is a draw
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..MAX_INDEX {
+            if db[idx] == KpkResult::Unknown {
+                db[idx] = classify(idx, &db);
+                changed |= db[idx] != KpkResult::Unknown;
+            }
+        }
+    }
+
+    let mut bitbase = vec![0u64; MAX_INDEX / 64];
+    for (idx, result) in db.iter().enumerate() {
+        if *result == KpkResult::Win {
+            bitbase[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    bitbase
+}
+
+fn probe_normalised(white_to_move: bool, white_king: usize, pawn: usize, black_king: usize) -> bool {
+    let bitbase = BITBASE.get_or_init(generate);
+    let idx = index(white_to_move, black_king, white_king, pawn);
+    bitbase[idx / 64] & (1 << (idx % 64)) != 0
+}
+
+pub(crate) fn probe(board: &Board) -> Option<(Color, bool)> {
+    // For king and pawn versus king, returns the side with the pawn and whether it wins
+    let pawns = *board.pieces(Piece::Pawn);
+    if board.combined().popcnt() != 3 || pawns.popcnt() != 1 {
+        return None;
+    }
+    let pawn_sq = pawns.to_square();
+    let strong_side = board.color_on(pawn_sq)?;
+
+    // Flip so the strong side is white, then mirror so the pawn is on files a-d
+    let normalise = |sq: Square| {
+        let mut sq = sq.to_index();
+        if strong_side == Color::Black {
+            sq ^= 56;
+        }
+        if pawn_sq.get_file().to_index() > 3 {
+            sq ^= 7;
+        }
+        sq
+    };
+
+    let win = probe_normalised(
+        board.side_to_move() == strong_side,
+        normalise(board.king_square(strong_side)),
+        normalise(pawn_sq),
+        normalise(board.king_square(!strong_side)),
+    );
+    Some((strong_side, win))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, Color};
+
+    use super::*;
+
+    fn probe_fen(fen: &str) -> Option<(Color, bool)> {
+        probe(&Board::from_str(fen).unwrap())
+    }
+
+    #[test]
+    fn test_not_kpk() {
+        assert_eq!(probe_fen("8/8/8/4k3/8/8/4P3/4KQ2 w - - 0 1"), None);
+        assert_eq!(probe_fen("8/8/8/4k3/8/8/8/4K3 w - - 0 1"), None);
+        assert_eq!(probe(&Board::default()), None);
+    }
+
+    #[test]
+    fn test_opposition() {
+        // Whoever has to move gives up the opposition
+        assert_eq!(probe_fen("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some((Color::White, false)));
+        assert_eq!(probe_fen("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"), Some((Color::White, true)));
+    }
+
+    #[test]
+    fn test_king_on_sixth() {
+        // King in front of the pawn on the sixth wins whoever is to move
+        assert_eq!(probe_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some((Color::White, true)));
+        assert_eq!(probe_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some((Color::White, true)));
+    }
+
+    #[test]
+    fn test_rook_pawn() {
+        // The defending king in the corner holds against a rook pawn
+        assert_eq!(probe_fen("k7/8/K7/P7/8/8/8/8 w - - 0 1"), Some((Color::White, false)));
+        assert_eq!(probe_fen("7k/8/7K/7P/8/8/8/8 w - - 0 1"), Some((Color::White, false)));
+    }
+
+    #[test]
+    fn test_square_rule() {
+        // The black king is too far away to catch the pawn
+        assert_eq!(probe_fen("8/8/8/8/P7/8/8/K6k b - - 0 1"), Some((Color::White, true)));
+    }
+
+    #[test]
+    fn test_pawn_captured() {
+        assert_eq!(probe_fen("8/8/8/8/8/8/3kP3/K7 b - - 0 1"), Some((Color::White, false)));
+    }
+
+    #[test]
+    fn test_black_pawn() {
+        // Colour flipped opposition positions
+        assert_eq!(probe_fen("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), Some((Color::Black, false)));
+        assert_eq!(probe_fen("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1"), Some((Color::Black, true)));
+    }
+}
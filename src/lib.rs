@@ -1,11 +1,26 @@
 pub mod ordering;
 pub mod engine;
 pub mod evaluation;
+pub mod eval_params;
+pub mod eval_trace;
+pub mod evaluator;
+pub mod nnue;
 pub(crate) mod consts;
 pub(crate) mod quiescent;
 pub mod search; // Make search public for performance testing
+pub mod search_config;
 pub(crate) mod psqt;
 pub(crate) mod gamestate;
+pub(crate) mod pawn_structure;
+pub(crate) mod mobility;
+pub(crate) mod king_safety;
+pub(crate) mod endgame;
 pub mod utils;
 pub mod managers;
-pub(crate) mod extensions;
\ No newline at end of file
+pub mod book;
+pub(crate) mod extensions;
+pub(crate) mod pruning;
+pub(crate) mod kpk;
+pub mod retrograde;
+pub mod tablebase;
+pub mod tuning;
\ No newline at end of file
@@ -12,6 +12,23 @@ pub struct Cache {
 }
 
 impl Cache {
+    pub fn with_size(entries: usize) -> Self {
+        // Number of entries, must be a power of two
+        Self {
+            cache: CacheTable::new(
+                entries,
+                CacheData {
+                    move_depth: 0,
+                    search_depth: 0,
+                    evaluation: Eval { score: 0 },
+                    flag: BoundType::LowerBound,
+                    pv_move: None,
+                    cutoff_move: None,
+                },
+            ),
+        }
+    }
+
     // This function should run in a seperate thread and constantly check for new data to load into the cache
     pub fn cache_manager_server(arc_cache: Arc<RwLock<Cache>>, channel_rx: Receiver<CacheEntry>) {
         let binding = arc_cache.clone();
@@ -51,19 +68,7 @@ impl Cache {
 
 impl Default for Cache {
     fn default() -> Self {
-        Self {
-            cache: CacheTable::new(
-                67108864,
-                CacheData {
-                    move_depth: 0,
-                    search_depth: 0,
-                    evaluation: Eval { score: 0 },
-                    flag: BoundType::LowerBound,
-                    pv_move: None,
-                    cutoff_move: None,
-                },
-            ),
-        }
+        Cache::with_size(67108864)
     }
 }
 
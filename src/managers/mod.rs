@@ -1,2 +1,3 @@
 pub mod cache_manager;
+pub mod pawn_cache_manager;
 pub mod stats_manager;
\ No newline at end of file
@@ -0,0 +1,140 @@
+// Pawn hash table
+// The pawn structure only changes on pawn moves and captures, so most evaluations can reuse the
+// analysis from an earlier position with the same pawns
+// The chess crate's own pawn hash isn't implemented, so the key is built from the Polyglot numbers
+
+use std::cell::RefCell;
+
+use chess::{Board, Color, Piece};
+
+use crate::book::zobrist::piece_key;
+use crate::pawn_structure::{analyse_pawns, PawnInfo};
+
+const PAWN_TABLE_SIZE: usize = 1 << 14; // Entries, must be a power of two
+
+thread_local! {
+    // The search runs on a single thread, each thread gets a table of its own
+    static PAWN_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable::new(PAWN_TABLE_SIZE));
+}
+
+pub(crate) struct PawnTable {
+    entries: Vec<Option<(u64, PawnInfo)>>,
+    hits: i32,
+    probes: i32,
+}
+
+impl PawnTable {
+    pub(crate) fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two());
+        PawnTable {
+            entries: vec![None; size],
+            hits: 0,
+            probes: 0,
+        }
+    }
+
+    pub(crate) fn probe(&mut self, board: &Board) -> PawnInfo {
+        // Returns the stored analysis if we have seen these pawns before, otherwise works it out
+        // and replaces whatever was in the slot
+        let key = pawn_key(board);
+        let slot = (key as usize) & (self.entries.len() - 1);
+        self.probes += 1;
+
+        if let Some((entry_key, info)) = self.entries[slot] {
+            if entry_key == key {
+                self.hits += 1;
+                return info;
+            }
+        }
+
+        let info = analyse_pawns(
+            board.pieces(Piece::Pawn) & board.color_combined(Color::White),
+            board.pieces(Piece::Pawn) & board.color_combined(Color::Black),
+        );
+        self.entries[slot] = Some((key, info));
+        info
+    }
+
+    pub(crate) fn take_counters(&mut self) -> (i32, i32) {
+        // Hits and probes since the last call
+        let counters = (self.hits, self.probes);
+        self.hits = 0;
+        self.probes = 0;
+        counters
+    }
+}
+
+pub(crate) fn pawn_key(board: &Board) -> u64 {
+    // Zobrist key of the pawns only
+    let mut key: u64 = 0;
+    for color in [Color::White, Color::Black] {
+        for sq in board.pieces(Piece::Pawn) & board.color_combined(color) {
+            key ^= piece_key(Piece::Pawn, color, sq);
+        }
+    }
+    key
+}
+
+pub(crate) fn probe_pawn_table(board: &Board) -> PawnInfo {
+    PAWN_TABLE.with(|table| table.borrow_mut().probe(board))
+}
+
+pub(crate) fn take_pawn_table_counters() -> (i32, i32) {
+    PAWN_TABLE.with(|table| table.borrow_mut().take_counters())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove, Square};
+
+    use super::*;
+
+    #[test]
+    fn test_pawn_key() {
+        // Piece moves leave the key alone, pawn moves change it
+        let board = Board::default();
+        let knight_move = board.make_move_new(ChessMove::new(Square::G1, Square::F3, None));
+        let pawn_move = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+        assert_eq!(pawn_key(&board), pawn_key(&knight_move));
+        assert_ne!(pawn_key(&board), pawn_key(&pawn_move));
+
+        let no_pawns = Board::from_str("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(pawn_key(&no_pawns), 0);
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut table = PawnTable::new(64);
+        let board = Board::default();
+        let knight_move = board.make_move_new(ChessMove::new(Square::G1, Square::F3, None));
+
+        let info = table.probe(&board);
+        assert_eq!(table.probe(&knight_move), info);
+        assert_eq!(table.take_counters(), (1, 2));
+        assert_eq!(table.take_counters(), (0, 0));
+    }
+
+    #[test]
+    fn test_matches_analysis() {
+        // A tiny table forces collisions, every answer must still match a fresh analysis
+        let mut table = PawnTable::new(2);
+        let fens = [
+            "4k3/p7/8/3P4/1P6/8/7p/4K3 w - - 0 1",
+            "4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1",
+            "4k3/1p4pp/8/8/PP6/8/7P/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        ];
+        for _ in 0..2 {
+            for fen in fens {
+                let board = Board::from_str(fen).unwrap();
+                let fresh = analyse_pawns(
+                    board.pieces(Piece::Pawn) & board.color_combined(Color::White),
+                    board.pieces(Piece::Pawn) & board.color_combined(Color::Black),
+                );
+                assert_eq!(table.probe(&board), fresh);
+            }
+        }
+    }
+}
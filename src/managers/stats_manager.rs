@@ -5,6 +5,19 @@ pub struct Statistics {
     pub all_nodes: i32,
     pub searched_nodes: i32,
     pub caches_used: i32,
+    pub tb_hits: i32, // Nodes resolved by an endgame tablebase probe
+    pub pawn_hash_hits: i32,
+    pub pawn_hash_probes: i32,
+}
+
+impl Statistics {
+    pub fn pawn_hash_hit_rate(&self) -> f32 {
+        // Fraction of pawn structure lookups answered by the pawn hash table
+        if self.pawn_hash_probes == 0 {
+            return 0.0;
+        }
+        self.pawn_hash_hits as f32 / self.pawn_hash_probes as f32
+    }
 }
 
 impl  AddAssign for Statistics {
@@ -12,6 +25,9 @@ impl  AddAssign for Statistics {
         self.all_nodes += rhs.all_nodes;
         self.searched_nodes += rhs.searched_nodes;
         self.caches_used += rhs.caches_used;
+        self.tb_hits += rhs.tb_hits;
+        self.pawn_hash_hits += rhs.pawn_hash_hits;
+        self.pawn_hash_probes += rhs.pawn_hash_probes;
     }
 }
 
@@ -27,10 +43,17 @@ mod tests{
 
     #[test]
     fn test_stats_add_assign(){
-        let mut a: Statistics = Statistics { all_nodes: 1, searched_nodes: 2, caches_used: 3 };
-        let b: Statistics = Statistics { all_nodes: 4, searched_nodes: 5, caches_used: 6 };
+        let mut a: Statistics = Statistics { all_nodes: 1, searched_nodes: 2, caches_used: 3, tb_hits: 1, pawn_hash_hits: 4, pawn_hash_probes: 5 };
+        let b: Statistics = Statistics { all_nodes: 4, searched_nodes: 5, caches_used: 6, tb_hits: 2, pawn_hash_hits: 1, pawn_hash_probes: 1 };
 
         a += b;
-        assert_eq!(a, Statistics{ all_nodes: 5, searched_nodes: 7, caches_used: 9 })
+        assert_eq!(a, Statistics{ all_nodes: 5, searched_nodes: 7, caches_used: 9, tb_hits: 3, pawn_hash_hits: 5, pawn_hash_probes: 6 })
+    }
+
+    #[test]
+    fn test_pawn_hash_hit_rate(){
+        assert_eq!(Statistics::default().pawn_hash_hit_rate(), 0.0);
+        let stats = Statistics { pawn_hash_hits: 3, pawn_hash_probes: 4, ..Default::default() };
+        assert_eq!(stats.pawn_hash_hit_rate(), 0.75);
     }
 }
\ No newline at end of file
@@ -0,0 +1,206 @@
+// Piece mobility evaluation
+// Counts the squares each piece attacks, leaving out squares held by our own pieces or attacked
+// by enemy pawns since a piece can't safely go there
+
+use chess::{
+    get_bishop_moves, get_knight_moves, get_rook_moves, BitBoard, Board, Color, Piece, Square,
+};
+
+use crate::utils::bitboards::pawn_attacks;
+use crate::utils::common::Score;
+
+// Indexed by the number of squares the piece can reach
+const KNIGHT_MOBILITY: [Score; 9] = [
+    Score::new(-30, -40),
+    Score::new(-15, -20),
+    Score::new(-5, -8),
+    Score::new(0, 0),
+    Score::new(5, 5),
+    Score::new(10, 10),
+    Score::new(15, 14),
+    Score::new(18, 16),
+    Score::new(20, 18),
+];
+
+const BISHOP_MOBILITY: [Score; 14] = [
+    Score::new(-25, -35),
+    Score::new(-12, -18),
+    Score::new(-4, -6),
+    Score::new(2, 2),
+    Score::new(8, 8),
+    Score::new(13, 13),
+    Score::new(17, 17),
+    Score::new(20, 21),
+    Score::new(23, 24),
+    Score::new(25, 26),
+    Score::new(27, 28),
+    Score::new(29, 30),
+    Score::new(30, 31),
+    Score::new(31, 32),
+];
+
+const ROOK_MOBILITY: [Score; 15] = [
+    Score::new(-15, -40),
+    Score::new(-8, -20),
+    Score::new(-3, -8),
+    Score::new(0, 2),
+    Score::new(2, 10),
+    Score::new(4, 17),
+    Score::new(6, 23),
+    Score::new(8, 28),
+    Score::new(10, 32),
+    Score::new(12, 35),
+    Score::new(13, 38),
+    Score::new(14, 40),
+    Score::new(15, 42),
+    Score::new(16, 43),
+    Score::new(16, 44),
+];
+
+const QUEEN_MOBILITY: [Score; 28] = [
+    Score::new(-10, -20),
+    Score::new(-6, -12),
+    Score::new(-3, -6),
+    Score::new(-1, -2),
+    Score::new(0, 2),
+    Score::new(1, 5),
+    Score::new(2, 8),
+    Score::new(3, 11),
+    Score::new(4, 14),
+    Score::new(5, 16),
+    Score::new(6, 18),
+    Score::new(7, 20),
+    Score::new(8, 22),
+    Score::new(8, 24),
+    Score::new(9, 25),
+    Score::new(9, 26),
+    Score::new(10, 27),
+    Score::new(10, 28),
+    Score::new(11, 29),
+    Score::new(11, 30),
+    Score::new(12, 30),
+    Score::new(12, 31),
+    Score::new(12, 31),
+    Score::new(13, 32),
+    Score::new(13, 32),
+    Score::new(13, 33),
+    Score::new(14, 33),
+    Score::new(14, 34),
+];
+
+fn mobility_area(board: &Board, color: Color) -> BitBoard {
+    // Squares that count towards mobility for this colour's pieces
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    !(*board.color_combined(color) | pawn_attacks(!color, enemy_pawns))
+}
+
+pub(crate) fn piece_attacks(board: &Board, piece: Piece, sq: Square) -> BitBoard {
+    // Squares attacked by a knight or slider, sliders are stopped by anything in the way
+    let occupied = *board.combined();
+    match piece {
+        Piece::Knight => get_knight_moves(sq),
+        Piece::Bishop => get_bishop_moves(sq, occupied),
+        Piece::Rook => get_rook_moves(sq, occupied),
+        Piece::Queen => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+        _ => chess::EMPTY,
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct MobilityParams {
+    // Indexed by the number of squares the piece reaches
+    pub knight: [Score; 9],
+    pub bishop: [Score; 14],
+    pub rook: [Score; 15],
+    pub queen: [Score; 28],
+}
+
+impl Default for MobilityParams {
+    fn default() -> Self {
+        MobilityParams {
+            knight: KNIGHT_MOBILITY,
+            bishop: BISHOP_MOBILITY,
+            rook: ROOK_MOBILITY,
+            queen: QUEEN_MOBILITY,
+        }
+    }
+}
+
+pub(crate) fn evaluate_mobility(board: &Board, color: Color, params: &MobilityParams) -> Score {
+    // Mobility score for one side, positive is good for that side
+    let area = mobility_area(board, color);
+    let mut score = Score::default();
+    for (piece, table) in [
+        (Piece::Knight, &params.knight[..]),
+        (Piece::Bishop, &params.bishop[..]),
+        (Piece::Rook, &params.rook[..]),
+        (Piece::Queen, &params.queen[..]),
+    ] {
+        for sq in board.pieces(piece) & board.color_combined(color) {
+            let squares = (piece_attacks(board, piece, sq) & area).popcnt() as usize;
+            score += table[squares.min(table.len() - 1)];
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+
+    fn mobility(board: &Board, color: Color) -> Score {
+        evaluate_mobility(board, color, &MobilityParams::default())
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let board = Board::default();
+        assert_eq!(
+            mobility(&board, Color::White),
+            mobility(&board, Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_knight_centre() {
+        // A centralised knight reaches eight squares, one on the rim only two
+        let centre = Board::from_str("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let rim = Board::from_str("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert_eq!(mobility(&centre, Color::White), KNIGHT_MOBILITY[8]);
+        assert_eq!(mobility(&rim, Color::White), KNIGHT_MOBILITY[2]);
+    }
+
+    #[test]
+    fn test_pawn_attacks_excluded() {
+        // The b7 and f7 pawns take away c6 and e6 from the knight
+        let board = Board::from_str("4k3/1p3p2/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let white = mobility(&board, Color::White);
+        assert_eq!(white, KNIGHT_MOBILITY[6]);
+    }
+
+    #[test]
+    fn test_own_pieces_excluded() {
+        // The rook on a1 is hemmed in by its own pieces
+        let board = Board::from_str("4k3/8/8/8/8/8/P7/RN2K3 w - - 0 1").unwrap();
+        let mut rook_only = Score::default();
+        let area = mobility_area(&board, Color::White);
+        for sq in board.pieces(Piece::Rook) & board.color_combined(Color::White) {
+            rook_only +=
+                ROOK_MOBILITY[(piece_attacks(&board, Piece::Rook, sq) & area).popcnt() as usize];
+        }
+        assert_eq!(rook_only, ROOK_MOBILITY[0]);
+    }
+
+    #[test]
+    fn test_queen_open_board() {
+        // A queen in the middle of an empty board is far more mobile than one stuck in the corner
+        let centre = Board::from_str("4k3/8/8/8/3Q4/8/8/4K3 w - - 0 1").unwrap();
+        let corner = Board::from_str("4k3/8/8/8/8/8/PP6/QN2K3 w - - 0 1").unwrap();
+        assert!(mobility(&centre, Color::White).eg > mobility(&corner, Color::White).eg);
+    }
+}
@@ -0,0 +1,499 @@
+// Efficiently updatable neural network evaluation
+// The network is one hidden layer seen from both sides (768 -> hidden x 2 -> 1): every piece on a
+// square is an input, once from White's point of view and once from Black's with the board
+// flipped. The hidden layer (the accumulator) is kept in step with the search as it makes and
+// unmakes moves, so a move only adds and takes away a few weight rows instead of redoing the sum.
+// Weights are quantised to i16 and the maths is all integer, in loops the compiler can vectorise
+
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::Path,
+    sync::{Arc, Weak},
+};
+
+use chess::{Board, ChessMove, Color, Square, ALL_COLORS, ALL_PIECES};
+
+use crate::consts::MATE_THRESHOLD;
+use crate::evaluator::Evaluator;
+use crate::utils::common::{abs_eval_from_color, Eval};
+
+pub const NUM_FEATURES: usize = 768; // Colour x piece x square
+const QA: i32 = 255; // Accumulator quantisation, also where the clipped ReLU tops out
+const QB: i32 = 64; // Output weight quantisation
+const EVAL_SCALE: i32 = 400; // Network output to centipawns
+const HIDDEN_ALIGN: usize = 16; // Whole vector registers of i16 for the hidden layer loops
+
+const FILE_MAGIC: &[u8; 4] = b"SRNN";
+const FILE_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 12; // Magic, version and hidden size
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn feature(perspective: Color, colour: Color, piece_index: usize, square: Square) -> usize {
+    // Our pieces come first and the board is flipped for Black, so both sides share the weights
+    let (side, square) = match perspective {
+        Color::White => (colour.to_index(), square.to_index()),
+        Color::Black => ((!colour).to_index(), square.to_index() ^ 56),
+    };
+    side * 384 + piece_index * 64 + square
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [Vec<i16>; 2], // Hidden layer from White's and Black's point of view
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>, // A row of hidden values per feature
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>, // Side to move's half of the hidden layer, then the other side's
+    output_bias: i16,         // Quantised like the output weights
+}
+
+impl Network {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        // Header, then little endian i16s: feature weights, feature biases, output weights and bias
+        let header = bytes
+            .get(..HEADER_SIZE)
+            .filter(|header| header.starts_with(FILE_MAGIC))
+            .ok_or_else(|| invalid("Not a network file".to_string()))?;
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != FILE_VERSION {
+            return Err(invalid(format!("Unsupported network version {}", version)));
+        }
+        let hidden = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        if hidden == 0 || !hidden.is_multiple_of(HIDDEN_ALIGN) {
+            return Err(invalid(format!(
+                "Hidden layer size {} is not a multiple of {}",
+                hidden, HIDDEN_ALIGN
+            )));
+        }
+
+        let values = &bytes[HEADER_SIZE..];
+        let num_values = NUM_FEATURES * hidden + hidden + 2 * hidden + 1;
+        if values.len() != 2 * num_values {
+            return Err(invalid(format!(
+                "Expected {} bytes of weights for a hidden layer of {}, found {}",
+                2 * num_values,
+                hidden,
+                values.len()
+            )));
+        }
+        let mut values = values
+            .chunks_exact(2)
+            .map(|value| i16::from_le_bytes([value[0], value[1]]));
+        let mut take = |count: usize| values.by_ref().take(count).collect::<Vec<i16>>();
+
+        Ok(Network {
+            hidden,
+            feature_weights: take(NUM_FEATURES * hidden),
+            feature_biases: take(hidden),
+            output_weights: take(2 * hidden),
+            output_bias: take(1)[0],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let values = self
+            .feature_weights
+            .iter()
+            .chain(&self.feature_biases)
+            .chain(&self.output_weights)
+            .chain([&self.output_bias]);
+        let mut bytes = [
+            &FILE_MAGIC[..],
+            &FILE_VERSION.to_le_bytes(),
+            &(self.hidden as u32).to_le_bytes(),
+        ]
+        .concat();
+        bytes.extend(values.flat_map(|value| value.to_le_bytes()));
+        bytes
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn feature_row(&self, index: usize) -> &[i16] {
+        &self.feature_weights[index * self.hidden..(index + 1) * self.hidden]
+    }
+
+    pub fn new_accumulator(&self) -> Accumulator {
+        Accumulator {
+            values: [vec![0; self.hidden], vec![0; self.hidden]],
+        }
+    }
+
+    pub fn refresh(&self, board: &Board, accumulator: &mut Accumulator) {
+        // The hidden layer from scratch, the biases plus a row for every piece
+        for perspective in ALL_COLORS {
+            let values = &mut accumulator.values[perspective.to_index()];
+            values.copy_from_slice(&self.feature_biases);
+            for colour in ALL_COLORS {
+                for (piece_index, piece) in ALL_PIECES.iter().enumerate() {
+                    for square in board.pieces(*piece) & board.color_combined(colour) {
+                        let row =
+                            self.feature_row(feature(perspective, colour, piece_index, square));
+                        add_row(values, row);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn update(
+        &self,
+        parent: &Accumulator,
+        board: &Board,
+        child: &Board,
+        accumulator: &mut Accumulator,
+    ) {
+        // The child's hidden layer from its parent's, only the pieces that changed square.
+        // Comparing the boards covers castling, en passant and promotions without special cases
+        for perspective in ALL_COLORS {
+            let values = &mut accumulator.values[perspective.to_index()];
+            values.copy_from_slice(&parent.values[perspective.to_index()]);
+            for colour in ALL_COLORS {
+                for (piece_index, piece) in ALL_PIECES.iter().enumerate() {
+                    let before = board.pieces(*piece) & board.color_combined(colour);
+                    let after = child.pieces(*piece) & child.color_combined(colour);
+                    for square in before & !after {
+                        let row =
+                            self.feature_row(feature(perspective, colour, piece_index, square));
+                        sub_row(values, row);
+                    }
+                    for square in after & !before {
+                        let row =
+                            self.feature_row(feature(perspective, colour, piece_index, square));
+                        add_row(values, row);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn output(&self, accumulator: &Accumulator, side_to_move: Color) -> Eval {
+        // Score for the side to move through the clipped ReLU and output layer, made absolute
+        let (ours, theirs) = self.output_weights.split_at(self.hidden);
+        let sum = crelu_dot(&accumulator.values[side_to_move.to_index()], ours)
+            + crelu_dot(&accumulator.values[(!side_to_move).to_index()], theirs)
+            + self.output_bias as i32 * QA;
+        let score = (sum as i64 * EVAL_SCALE as i64 / (QA * QB) as i64)
+            .clamp(-(MATE_THRESHOLD as i64) + 1, MATE_THRESHOLD as i64 - 1);
+        abs_eval_from_color(score as i16, side_to_move)
+    }
+
+    pub fn evaluate(&self, board: &Board) -> Eval {
+        // Without an accumulator to start from
+        let mut accumulator = self.new_accumulator();
+        self.refresh(board, &mut accumulator);
+        self.output(&accumulator, board.side_to_move())
+    }
+}
+
+fn add_row(values: &mut [i16], row: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_add(*weight);
+    }
+}
+
+fn sub_row(values: &mut [i16], row: &[i16]) {
+    for (value, weight) in values.iter_mut().zip(row) {
+        *value = value.wrapping_sub(*weight);
+    }
+}
+
+fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(value, weight)| (*value as i32).clamp(0, QA) * *weight as i32)
+        .sum()
+}
+
+#[derive(Default)]
+struct AccumulatorStack {
+    network: Weak<Network>, // Network the entries were built with, weak so its address isn't reused
+    entries: Vec<(u64, Accumulator)>, // Board hash and its accumulator, reused between searches
+    len: usize,
+}
+
+impl AccumulatorStack {
+    fn for_network(&mut self, network: &Arc<Network>) -> &mut Self {
+        // Another network's entries are no use, start over
+        let same_hidden = self
+            .entries
+            .first()
+            .is_none_or(|(_, accumulator)| accumulator.values[0].len() == network.hidden);
+        if !self.network.ptr_eq(&Arc::downgrade(network)) || !same_hidden {
+            *self = AccumulatorStack {
+                network: Arc::downgrade(network),
+                ..Default::default()
+            };
+        }
+        self
+    }
+
+    fn top(&self) -> Option<&(u64, Accumulator)> {
+        self.len.checked_sub(1).map(|index| &self.entries[index])
+    }
+
+    fn push(&mut self, network: &Network, board: &Board, mve: Option<ChessMove>) {
+        if self.len == self.entries.len() {
+            self.entries.push((0, network.new_accumulator()));
+        }
+        let (parents, children) = self.entries.split_at_mut(self.len);
+        let (hash, accumulator) = &mut children[0];
+        match (mve, parents.last()) {
+            // Update from the parent if it's the board the move is played on
+            (Some(mve), Some((parent_hash, parent))) if *parent_hash == board.get_hash() => {
+                let child = board.make_move_new(mve);
+                network.update(parent, board, &child, accumulator);
+                *hash = child.get_hash();
+            }
+            (Some(mve), _) => {
+                let child = board.make_move_new(mve);
+                network.refresh(&child, accumulator);
+                *hash = child.get_hash();
+            }
+            (None, _) => {
+                network.refresh(board, accumulator);
+                *hash = board.get_hash();
+            }
+        }
+        self.len += 1;
+    }
+}
+
+thread_local! {
+    // Searches run on the thread that called the engine, each thread keeps a stack of its own
+    static ACCUMULATORS: RefCell<AccumulatorStack> = RefCell::new(AccumulatorStack::default());
+}
+
+// Evaluates with a network, keeping the accumulators up to date through the search hooks
+#[derive(Debug, Clone)]
+pub struct NnueEvaluator {
+    pub network: Arc<Network>,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Arc<Network>) -> Self {
+        NnueEvaluator { network }
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(Arc::new(Network::open(path)?)))
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&self, board: &Board) -> Eval {
+        ACCUMULATORS.with_borrow_mut(|stack| {
+            let stack = stack.for_network(&self.network);
+            match stack.top() {
+                Some((hash, accumulator)) if *hash == board.get_hash() => {
+                    self.network.output(accumulator, board.side_to_move())
+                }
+                _ => self.network.evaluate(board),
+            }
+        })
+    }
+
+    fn name(&self) -> &str {
+        "nnue"
+    }
+
+    fn new_search(&self, board: &Board) {
+        ACCUMULATORS.with_borrow_mut(|stack| {
+            let stack = stack.for_network(&self.network);
+            stack.len = 0;
+            stack.push(&self.network, board, None);
+        })
+    }
+
+    fn make_move(&self, board: &Board, mve: ChessMove) {
+        ACCUMULATORS.with_borrow_mut(|stack| {
+            stack
+                .for_network(&self.network)
+                .push(&self.network, board, Some(mve))
+        })
+    }
+
+    fn unmake_move(&self) {
+        ACCUMULATORS.with_borrow_mut(|stack| {
+            let stack = stack.for_network(&self.network);
+            stack.len = stack.len.saturating_sub(1);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::engine::enter_engine;
+    use crate::utils::engine_interface::EngineSettings;
+
+    fn random_network(hidden: usize, seed: u64) -> Network {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut values = |count: usize, range: i16| {
+            (0..count)
+                .map(|_| rng.gen_range(-range..=range))
+                .collect::<Vec<i16>>()
+        };
+        Network {
+            hidden,
+            feature_weights: values(NUM_FEATURES * hidden, 40),
+            feature_biases: values(hidden, 40),
+            output_weights: values(2 * hidden, 60),
+            output_bias: values(1, 200)[0],
+        }
+    }
+
+    fn mirror(fen: &str) -> String {
+        // Colours swapped and the board flipped top to bottom, for FENs without castling or en passant
+        let fields = fen.split(' ').collect::<Vec<&str>>();
+        let swap_case = |c: char| match c.is_ascii_uppercase() {
+            true => c.to_ascii_lowercase(),
+            false => c.to_ascii_uppercase(),
+        };
+        let placement = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| rank.chars().map(swap_case).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("/");
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} {}", placement, side, fields[2..].join(" "))
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let network = random_network(32, 1);
+        let bytes = network.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+        assert!(Network::from_bytes(b"not a network").is_err());
+        let mut odd_size = bytes.clone();
+        odd_size[8] = 33;
+        assert!(Network::from_bytes(&odd_size).is_err());
+    }
+
+    #[test]
+    fn test_update_matches_refresh() {
+        // Captures, castling, en passant and a promotion, updated and refreshed side by side
+        let network = random_network(32, 2);
+        let mut board =
+            Board::from_str("r3k2r/1P3ppp/8/3pP3/8/8/5PPP/R3K2R w KQkq d6 0 1").unwrap();
+        let mut accumulator = network.new_accumulator();
+        network.refresh(&board, &mut accumulator);
+
+        for mve in ["e5d6", "e8g8", "e1c1", "f7f5", "b7a8q", "f8a8"] {
+            let mve = ChessMove::from_str(mve).unwrap();
+            assert!(board.legal(mve));
+            let child = board.make_move_new(mve);
+            let mut updated = network.new_accumulator();
+            network.update(&accumulator, &board, &child, &mut updated);
+
+            let mut refreshed = network.new_accumulator();
+            network.refresh(&child, &mut refreshed);
+            assert_eq!(updated, refreshed);
+            board = child;
+            accumulator = updated;
+        }
+    }
+
+    #[test]
+    fn test_colour_symmetry() {
+        // The same position with the colours swapped is the same score for the other side
+        let network = random_network(32, 3);
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w - - 4 4",
+            "8/5pk1/6p1/3R4/8/6P1/r4PK1/8 b - - 0 40",
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let mirrored = Board::from_str(&mirror(fen)).unwrap();
+            assert_eq!(
+                network.evaluate(&board).score,
+                -network.evaluate(&mirrored).score
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluator_hooks() {
+        // Through the hooks, on a board it wasn't told about, and after unmaking
+        let evaluator = NnueEvaluator::new(Arc::new(random_network(16, 4)));
+        let board = Board::default();
+        evaluator.new_search(&board);
+        assert_eq!(
+            evaluator.evaluate(&board),
+            evaluator.network.evaluate(&board)
+        );
+
+        let e4 = ChessMove::from_str("e2e4").unwrap();
+        let after_e4 = board.make_move_new(e4);
+        evaluator.make_move(&board, e4);
+        assert_eq!(
+            evaluator.evaluate(&after_e4),
+            evaluator.network.evaluate(&after_e4)
+        );
+
+        let elsewhere = Board::from_str("8/8/4k3/8/8/2K5/8/7Q w - - 0 1").unwrap();
+        assert_eq!(
+            evaluator.evaluate(&elsewhere),
+            evaluator.network.evaluate(&elsewhere)
+        );
+
+        evaluator.unmake_move();
+        assert_eq!(
+            evaluator.evaluate(&board),
+            evaluator.network.evaluate(&board)
+        );
+    }
+
+    #[test]
+    fn test_accumulators_follow_network() {
+        // A new network, possibly at a dropped one's address, must not reuse its accumulators
+        let board = Board::default();
+        let small = NnueEvaluator::new(Arc::new(random_network(16, 6)));
+        small.new_search(&board);
+        drop(small);
+        let large = NnueEvaluator::new(Arc::new(random_network(32, 7)));
+        assert_eq!(large.evaluate(&board), large.network.evaluate(&board));
+
+        let other = NnueEvaluator::new(Arc::new(random_network(32, 8)));
+        other.new_search(&board);
+        assert_eq!(large.evaluate(&board), large.network.evaluate(&board));
+    }
+
+    #[test]
+    fn test_search_with_network() {
+        let network = Arc::new(random_network(16, 5));
+        let settings = EngineSettings {
+            evaluator: Some(Arc::new(NnueEvaluator::new(network))),
+            node_limit: Some(2000),
+            ..Default::default()
+        };
+        let board = Board::default();
+        let (eng_move, _) = enter_engine(board, settings);
+        assert!(board.legal(eng_move));
+    }
+}
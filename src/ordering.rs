@@ -1,24 +1,26 @@
-use crate::managers::cache_manager::BoundType;
-use crate::utils::common::Eval;
 use chess::{Board, ChessMove, MoveGen, Piece};
 use itertools::Itertools;
 
-fn get_piece_weight(piece: Piece) -> i16 {
-    // Return the estimated value of a piece
+use crate::eval_params::MaterialParams;
+
+const KING_WEIGHT: i16 = 2500; // Above any material swing, so king captures order last among captures
+
+fn get_piece_weight(piece: Piece, material: &MaterialParams) -> i16 {
+    // Return the estimated value of a piece, the midgame material value the evaluation uses
     match piece {
-        Piece::Pawn => 100,
-        Piece::Knight => 300,
-        Piece::Bishop => 320,
-        Piece::Rook => 500,
-        Piece::Queen => 900,
-        Piece::King => 2500,
+        Piece::King => KING_WEIGHT,
+        piece => material.value(piece).mg,
     }
 }
 
-#[derive(PartialEq)]
-pub(crate) struct RetreivedCacheData {
-    pub(crate) evaluation: Eval,
-    pub(crate) flag: BoundType,
+pub(crate) fn is_good_capture(board: &Board, mve: ChessMove, material: &MaterialParams) -> bool {
+    // A capture that wins at least as much material as the capturing piece is worth
+    match (board.piece_on(mve.get_source()), board.piece_on(mve.get_dest())) {
+        (Some(source), Some(dest)) => {
+            get_piece_weight(dest, material) >= get_piece_weight(source, material)
+        }
+        (_, _) => false,
+    }
 }
 
 pub(crate) fn order_moves(
@@ -26,8 +28,9 @@ pub(crate) fn order_moves(
     board: Board,
     pv_move: Option<ChessMove>,
     cutoff_move: Option<ChessMove>,
+    material: &MaterialParams,
 ) -> std::vec::IntoIter<chess::ChessMove> {
-    let sorted_moves = moves.sorted_by_cached_key(|mve| {
+    moves.sorted_by_cached_key(|mve| {
         // Check if this move is our PV move
         if let Some(pv) = pv_move {
             if *mve == pv {
@@ -44,7 +47,9 @@ pub(crate) fn order_moves(
 
         // If we have a promotion sort it very highly
         if let Some(promotion) = mve.get_promotion() {
-            return i16::MIN + 2 + (get_piece_weight(Piece::Queen) - get_piece_weight(promotion));
+            let underpromotion =
+                get_piece_weight(Piece::Queen, material) - get_piece_weight(promotion, material);
+            return i16::MIN + 2 + underpromotion;
         }
 
         // Check if move is a non-capture
@@ -53,26 +58,27 @@ pub(crate) fn order_moves(
 
         match (source_piece, destination_piece) {
             (Some(source), Some(dest)) => {
-                let mvv_lva = get_piece_weight(dest) - get_piece_weight(source);
-                return -(mvv_lva + get_piece_weight(Piece::King)); // Sort is ascending, provide a boost for all the captures
+                let mvv_lva = get_piece_weight(dest, material) - get_piece_weight(source, material);
+                -(mvv_lva + KING_WEIGHT) // Sort is ascending, provide a boost for all the captures
             }
-            (_, _) => return 0,
-        };
-    });
-    return sorted_moves;
+            (_, _) => 0,
+        }
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use super::order_moves;
+    use super::{is_good_capture, order_moves};
+    use crate::eval_params::MaterialParams;
+    use crate::utils::common::Score;
     use chess::{Board, ChessMove, MoveGen, Square};
     #[test]
     fn test_simple_ordering() {
         let board: Board = Board::from_str("2rk4/8/8/8/1B6/3r4/4P3/3Kb3 w - - 0 1").unwrap();
         let moves = MoveGen::new_legal(&board);
-        let mut sorted_moves = order_moves(moves, board, None, None);
+        let mut sorted_moves = order_moves(moves, board, None, None, &MaterialParams::default());
 
         assert_eq!(
             sorted_moves.next(),
@@ -94,7 +100,8 @@ mod tests {
         let pv_move = ChessMove::new(Square::A2, Square::D2, None);
         let co_move = ChessMove::new(Square::D1, Square::E1, None);
         let moves = MoveGen::new_legal(&board);
-        let mut sorted_moves = order_moves(moves, board, Some(pv_move), Some(co_move));
+        let material = MaterialParams::default();
+        let mut sorted_moves = order_moves(moves, board, Some(pv_move), Some(co_move), &material);
 
         assert_eq!(sorted_moves.next(), Some(pv_move));
         assert_eq!(sorted_moves.next(), Some(co_move));
@@ -103,4 +110,41 @@ mod tests {
             Some(ChessMove::new(Square::E2, Square::D3, None))
         );
     }
+
+    #[test]
+    fn test_good_captures() {
+        let board: Board = Board::from_str("2rk4/8/8/8/1B6/3r4/4P3/3Kb3 w - - 0 1").unwrap();
+        let material = MaterialParams::default();
+        // Pawn takes rook
+        assert!(is_good_capture(&board, ChessMove::new(Square::E2, Square::D3, None), &material));
+        // The king is weighted above every other piece, so king captures never count as good
+        assert!(!is_good_capture(&board, ChessMove::new(Square::D1, Square::E1, None), &material));
+        // Quiet moves are not captures
+        assert!(!is_good_capture(&board, ChessMove::new(Square::B4, Square::A5, None), &material));
+    }
+
+    #[test]
+    fn test_material_values() {
+        // The pawn can take a knight or a bishop, and the bishop can take the knight
+        let board: Board = Board::from_str("4k3/8/8/2n1b3/3P4/B7/8/4K3 w - - 0 1").unwrap();
+        let takes_knight = ChessMove::new(Square::D4, Square::C5, None);
+        let takes_bishop = ChessMove::new(Square::D4, Square::E5, None);
+        let bishop_takes_knight = ChessMove::new(Square::A3, Square::C5, None);
+
+        let material = MaterialParams::default();
+        let moves = MoveGen::new_legal(&board);
+        let mut sorted_moves = order_moves(moves, board, None, None, &material);
+        assert_eq!(sorted_moves.next(), Some(takes_bishop));
+        assert!(!is_good_capture(&board, bishop_takes_knight, &material));
+
+        // Worth more than a bishop, the knight is now the first capture
+        let material = MaterialParams {
+            knight: Score::new(350, 350),
+            ..Default::default()
+        };
+        let moves = MoveGen::new_legal(&board);
+        let mut sorted_moves = order_moves(moves, board, None, None, &material);
+        assert_eq!(sorted_moves.next(), Some(takes_knight));
+        assert!(is_good_capture(&board, bishop_takes_knight, &material));
+    }
 }
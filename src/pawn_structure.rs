@@ -0,0 +1,407 @@
+// Pawn structure evaluation
+// Everything apart from the passed pawn bonus only depends on where the pawns are
+
+use chess::{get_adjacent_files, get_file, get_rank, BitBoard, Board, Color, Piece, ALL_FILES, EMPTY};
+
+use crate::utils::bitboards::{
+    contains, forward_file, forward_ranks, passed_pawn_span, pawn_attack_span, pawn_attacks,
+    relative_rank,
+};
+use crate::utils::common::Score;
+
+const DOUBLED_PAWN: Score = Score::new(-10, -20);
+const ISOLATED_PAWN: Score = Score::new(-10, -15);
+const BACKWARD_PAWN: Score = Score::new(-8, -12);
+const PASSED_PAWN_SUPPORTED: Score = Score::new(5, 15);
+
+// Indexed by the rank relative to the pawn's own side
+const CONNECTED_PAWN: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(3, 2),
+    Score::new(5, 4),
+    Score::new(8, 6),
+    Score::new(14, 12),
+    Score::new(25, 25),
+    Score::new(40, 40),
+    Score::new(0, 0),
+];
+
+const CANDIDATE_PASSER: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(2, 5),
+    Score::new(4, 8),
+    Score::new(6, 12),
+    Score::new(10, 20),
+    Score::new(15, 30),
+    Score::new(0, 0),
+    Score::new(0, 0),
+];
+
+const PASSED_PAWN: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(8, 15),
+    Score::new(12, 25),
+    Score::new(25, 45),
+    Score::new(45, 80),
+    Score::new(70, 130),
+    Score::new(0, 0),
+];
+
+pub(crate) fn passed_pawns(own: BitBoard, enemy: BitBoard, color: Color) -> BitBoard {
+    // Pawns with no enemy pawns in front of them or on the files either side
+    let mut passed = EMPTY;
+    for sq in own {
+        if passed_pawn_span(color, sq) & enemy == EMPTY {
+            passed |= BitBoard::from_square(sq);
+        }
+    }
+    passed
+}
+
+fn doubled_pawns(own: BitBoard, color: Color) -> BitBoard {
+    // Pawns with a friendly pawn somewhere in front of them, the front pawn isn't counted
+    let mut doubled = EMPTY;
+    for sq in own {
+        if forward_file(color, sq) & own != EMPTY {
+            doubled |= BitBoard::from_square(sq);
+        }
+    }
+    doubled
+}
+
+fn isolated_pawns(own: BitBoard) -> BitBoard {
+    let mut isolated = EMPTY;
+    for sq in own {
+        if get_adjacent_files(sq.get_file()) & own == EMPTY {
+            isolated |= BitBoard::from_square(sq);
+        }
+    }
+    isolated
+}
+
+fn backward_pawns(own: BitBoard, enemy: BitBoard, color: Color) -> BitBoard {
+    // Pawns that have left their neighbours behind and can't advance without being taken
+    let enemy_attacks = pawn_attacks(!color, enemy);
+    let mut backward = EMPTY;
+    for sq in own {
+        let neighbours = get_adjacent_files(sq.get_file()) & own;
+        let supporters = neighbours & !forward_ranks(color, sq);
+        let stop_attacked = sq
+            .forward(color)
+            .is_some_and(|stop| contains(enemy_attacks, stop));
+        if neighbours != EMPTY && supporters == EMPTY && stop_attacked {
+            backward |= BitBoard::from_square(sq);
+        }
+    }
+    backward
+}
+
+fn connected_pawns(own: BitBoard, color: Color) -> BitBoard {
+    // Pawns defended by another pawn or standing next to one
+    let defended = pawn_attacks(color, own) & own;
+    let mut phalanx = EMPTY;
+    for sq in own {
+        if get_adjacent_files(sq.get_file()) & get_rank(sq.get_rank()) & own != EMPTY {
+            phalanx |= BitBoard::from_square(sq);
+        }
+    }
+    defended | phalanx
+}
+
+fn candidate_passers(own: BitBoard, enemy: BitBoard, color: Color) -> BitBoard {
+    // Pawns on a half open file that have at least as many friendly pawns able to support their
+    // advance as there are enemy pawns guarding the way
+    let passed = passed_pawns(own, enemy, color);
+    let mut candidates = EMPTY;
+    for sq in own & !passed {
+        if forward_file(color, sq) & enemy != EMPTY {
+            continue;
+        }
+        let helpers = get_adjacent_files(sq.get_file()) & own & !forward_ranks(color, sq);
+        let sentries = pawn_attack_span(color, sq) & enemy;
+        if helpers.popcnt() >= sentries.popcnt() {
+            candidates |= BitBoard::from_square(sq);
+        }
+    }
+    candidates
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PawnParams {
+    pub doubled: Score,
+    pub isolated: Score,
+    pub backward: Score,
+    pub passed_supported: Score,
+    pub connected: [Score; 8], // Indexed by relative rank, as are the two below
+    pub candidate_passer: [Score; 8],
+    pub passed: [Score; 8],
+}
+
+impl Default for PawnParams {
+    fn default() -> Self {
+        PawnParams {
+            doubled: DOUBLED_PAWN,
+            isolated: ISOLATED_PAWN,
+            backward: BACKWARD_PAWN,
+            passed_supported: PASSED_PAWN_SUPPORTED,
+            connected: CONNECTED_PAWN,
+            candidate_passer: CANDIDATE_PASSER,
+            passed: PASSED_PAWN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PawnInfo {
+    // Everything we know from the pawns alone, this is what the pawn hash table stores
+    // Only the pawns themselves are kept so the table holds whatever weights are in use
+    // Each pair is indexed by colour
+    pub doubled: [BitBoard; 2],
+    pub isolated: [BitBoard; 2],
+    pub backward: [BitBoard; 2],
+    pub connected: [BitBoard; 2],
+    pub candidates: [BitBoard; 2],
+    pub passed: [BitBoard; 2],
+    pub open_files: BitBoard,           // Whole files without any pawns
+    pub half_open_files: [BitBoard; 2], // Whole files without pawns of that colour
+}
+
+pub(crate) fn analyse_pawns(white_pawns: BitBoard, black_pawns: BitBoard) -> PawnInfo {
+    let mut half_open_files = [EMPTY; 2];
+    for file in ALL_FILES {
+        let file_mask = get_file(file);
+        if file_mask & white_pawns == EMPTY {
+            half_open_files[Color::White.to_index()] |= file_mask;
+        }
+        if file_mask & black_pawns == EMPTY {
+            half_open_files[Color::Black.to_index()] |= file_mask;
+        }
+    }
+
+    let both = |analysis: fn(BitBoard, BitBoard, Color) -> BitBoard| {
+        [
+            analysis(white_pawns, black_pawns, Color::White),
+            analysis(black_pawns, white_pawns, Color::Black),
+        ]
+    };
+    PawnInfo {
+        doubled: both(|own, _, color| doubled_pawns(own, color)),
+        isolated: both(|own, _, _| isolated_pawns(own)),
+        backward: both(backward_pawns),
+        connected: both(|own, _, color| connected_pawns(own, color)),
+        candidates: both(candidate_passers),
+        passed: both(passed_pawns),
+        open_files: half_open_files[0] & half_open_files[1],
+        half_open_files,
+    }
+}
+
+fn pawn_structure_colour(pawn_info: &PawnInfo, color: Color, params: &PawnParams) -> Score {
+    // Structure score for one side, positive is good for that side
+    let side = color.to_index();
+    let mut score = Score::default();
+    score += params.doubled * pawn_info.doubled[side].popcnt() as i16;
+    score += params.isolated * pawn_info.isolated[side].popcnt() as i16;
+    score += params.backward * pawn_info.backward[side].popcnt() as i16;
+    for sq in pawn_info.connected[side] {
+        score += params.connected[relative_rank(color, sq)];
+    }
+    for sq in pawn_info.candidates[side] {
+        score += params.candidate_passer[relative_rank(color, sq)];
+    }
+    score
+}
+
+fn passed_pawn_score(board: &Board, color: Color, passed: BitBoard, params: &PawnParams) -> Score {
+    // Passed pawns grow more valuable as they advance, are worth less when something stands in
+    // their way and more when another pawn protects them
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let defended = pawn_attacks(color, own_pawns);
+    let mut score = Score::default();
+    for sq in passed {
+        let mut bonus = params.passed[relative_rank(color, sq)];
+        if forward_file(color, sq) & board.combined() != EMPTY {
+            bonus = Score::new(bonus.mg / 2, bonus.eg / 2);
+        }
+        if contains(defended, sq) {
+            bonus += params.passed_supported;
+        }
+        score += bonus;
+    }
+    score
+}
+
+pub(crate) fn evaluate_pawn_structure(
+    board: &Board,
+    pawn_info: &PawnInfo,
+    color: Color,
+    params: &PawnParams,
+) -> Score {
+    // Full pawn evaluation for one side, positive is good for that side
+    // The passed pawn bonus looks at the other pieces so it can't come from the pawn hash table
+    pawn_structure_colour(pawn_info, color, params)
+        + passed_pawn_score(board, color, pawn_info.passed[color.to_index()], params)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, Square};
+
+    use super::*;
+
+    fn pawns(board: &Board, color: Color) -> BitBoard {
+        board.pieces(Piece::Pawn) & board.color_combined(color)
+    }
+
+    fn evaluate(board: &Board) -> Score {
+        let info = analyse_pawns(pawns(board, Color::White), pawns(board, Color::Black));
+        let params = PawnParams::default();
+        evaluate_pawn_structure(board, &info, Color::White, &params)
+            - evaluate_pawn_structure(board, &info, Color::Black, &params)
+    }
+
+    fn squares(list: &[Square]) -> BitBoard {
+        list.iter()
+            .fold(EMPTY, |bb, sq| bb | BitBoard::from_square(*sq))
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        // d5 is passed, b4 is held by the a pawn, the black h pawn has nothing in front of it
+        let board = Board::from_str("4k3/p7/8/3P4/1P6/8/7p/4K3 w - - 0 1").unwrap();
+        let white = pawns(&board, Color::White);
+        let black = pawns(&board, Color::Black);
+        assert_eq!(
+            passed_pawns(white, black, Color::White),
+            squares(&[Square::D5])
+        );
+        assert_eq!(
+            passed_pawns(black, white, Color::Black),
+            squares(&[Square::H2])
+        );
+    }
+
+    #[test]
+    fn test_passed_pawn_scaling() {
+        // Further advanced passers are worth more, blocked ones less and defended ones more
+        let sixth = Board::from_str("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let fourth = Board::from_str("4k3/8/8/8/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = Board::from_str("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let supported = Board::from_str("4k3/8/3P4/4P3/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let passed_score = |board: &Board| {
+            let passed = squares(&[Square::D6, Square::D4]);
+            let passed = passed & pawns(board, Color::White);
+            passed_pawn_score(board, Color::White, passed, &PawnParams::default())
+        };
+
+        assert!(passed_score(&sixth).eg > passed_score(&fourth).eg);
+        assert!(passed_score(&sixth).eg > passed_score(&blocked).eg);
+        assert!(passed_score(&supported).eg > passed_score(&sixth).eg);
+    }
+
+    #[test]
+    fn test_doubled_pawns() {
+        let board = Board::from_str("4k3/8/8/8/2P5/2P5/2P5/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            doubled_pawns(pawns(&board, Color::White), Color::White),
+            squares(&[Square::C2, Square::C3])
+        );
+
+        // For black the rear pawn is the one nearer the first rank
+        let board = Board::from_str("4k3/5p2/5p2/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            doubled_pawns(pawns(&board, Color::Black), Color::Black),
+            squares(&[Square::F7])
+        );
+    }
+
+    #[test]
+    fn test_isolated_pawns() {
+        let board = Board::from_str("4k3/8/8/8/8/8/P1PP3P/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            isolated_pawns(pawns(&board, Color::White)),
+            squares(&[Square::A2, Square::H2])
+        );
+    }
+
+    #[test]
+    fn test_backward_pawns() {
+        // d3 has been left behind by c4 and e4, and black's c5 pawn guards the square in front
+        let board = Board::from_str("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+        let white = pawns(&board, Color::White);
+        let black = pawns(&board, Color::Black);
+        assert_eq!(
+            backward_pawns(white, black, Color::White),
+            squares(&[Square::D3])
+        );
+
+        // Without the guard the pawn can just catch up
+        let board = Board::from_str("4k3/8/8/8/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+        let white = pawns(&board, Color::White);
+        let black = pawns(&board, Color::Black);
+        assert_eq!(backward_pawns(white, black, Color::White), EMPTY);
+    }
+
+    #[test]
+    fn test_connected_pawns() {
+        // f2-g3 is a chain, c4-d4 a phalanx, a2 is on its own
+        let board = Board::from_str("4k3/8/8/8/2PP4/6P1/P4P2/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            connected_pawns(pawns(&board, Color::White), Color::White),
+            squares(&[Square::G3, Square::C4, Square::D4])
+        );
+    }
+
+    #[test]
+    fn test_candidate_passers() {
+        // Two against one on the queenside makes a4 a candidate, black's g pawn has the h pawn to help it past h2
+        let board = Board::from_str("4k3/1p4pp/8/8/PP6/8/7P/4K3 w - - 0 1").unwrap();
+        let white = pawns(&board, Color::White);
+        let black = pawns(&board, Color::Black);
+        assert_eq!(
+            candidate_passers(white, black, Color::White),
+            squares(&[Square::A4])
+        );
+        assert_eq!(
+            candidate_passers(black, white, Color::Black),
+            squares(&[Square::G7])
+        );
+    }
+
+    #[test]
+    fn test_symmetric_structure() {
+        assert_eq!(evaluate(&Board::default()), Score::default());
+        let board = Board::from_str("4k3/pp3p1p/2p3p1/8/8/2P3P1/PP3P1P/4K3 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&board), Score::default());
+    }
+
+    #[test]
+    fn test_structure_preference() {
+        // Healthy pawns beat doubled and isolated ones
+        let healthy = Board::from_str("4k3/ppp5/8/8/8/8/PPP5/4K3 w - - 0 1").unwrap();
+        let broken = Board::from_str("4k3/ppp5/8/8/8/P7/P1P5/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&healthy).mg > evaluate(&broken).mg);
+        assert!(evaluate(&healthy).eg > evaluate(&broken).eg);
+    }
+
+    #[test]
+    fn test_open_files() {
+        // The c file is open, the d file is half open for white and e for black
+        let board = Board::from_str("4k3/pp1p1ppp/8/8/8/8/PP2PPPP/4K3 w - - 0 1").unwrap();
+        let info = analyse_pawns(pawns(&board, Color::White), pawns(&board, Color::Black));
+        assert_eq!(info.open_files, get_file(chess::File::C));
+        assert_eq!(
+            info.half_open_files[Color::White.to_index()],
+            get_file(chess::File::C) | get_file(chess::File::D)
+        );
+        assert_eq!(
+            info.half_open_files[Color::Black.to_index()],
+            get_file(chess::File::C) | get_file(chess::File::E)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use chess::{Board, ChessMove, EMPTY};
+
+use crate::consts;
+use crate::search_config::SearchConfig;
+
+pub(crate) fn is_mate_score(score: i16) -> bool {
+    // Returns true if the score (relative to either side) represents a forced mate
+    (score as i32).abs() >= consts::MATE_THRESHOLD as i32
+}
+
+pub(crate) fn is_pv_node(alpha: i16, beta: i16) -> bool {
+    // Anything wider than a null window can still change the principal variation
+    beta as i32 - alpha as i32 > 1
+}
+
+pub(crate) fn reverse_futility_prune(
+    static_eval: i16,
+    beta: i16,
+    remaining_depth: u8,
+    config: &SearchConfig,
+) -> bool {
+    // Returns true if the static evaluation is so far above beta that we assume the node fails high
+    if !config.use_reverse_futility_pruning
+        || remaining_depth == 0
+        || remaining_depth > config.reverse_futility_depth
+        || is_mate_score(beta)
+        || is_mate_score(static_eval)
+    {
+        return false;
+    }
+
+    let margin = config.reverse_futility_margin as i32 * remaining_depth as i32;
+    static_eval as i32 - margin >= beta as i32
+}
+
+pub(crate) fn should_razor(
+    static_eval: i16,
+    alpha: i16,
+    remaining_depth: u8,
+    config: &SearchConfig,
+) -> bool {
+    // Returns true if the static evaluation is so far below alpha that the node should drop into the quiescent search
+    match config.razor_margins.get(remaining_depth as usize) {
+        Some(margin) if config.use_razoring && remaining_depth > 0 && !is_mate_score(alpha) => {
+            (static_eval as i32 + *margin as i32) < alpha as i32
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn futility_prune(
+    static_eval: i16,
+    alpha: i16,
+    remaining_depth: u8,
+    config: &SearchConfig,
+) -> bool {
+    // Returns true if quiet moves at this node are not expected to raise alpha
+    match config.futility_margins.get(remaining_depth as usize) {
+        Some(margin) if config.use_futility_pruning && remaining_depth > 0 && !is_mate_score(alpha) => {
+            (static_eval as i32 + *margin as i32) <= alpha as i32
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn probcut_beta(
+    alpha: i16,
+    beta: i16,
+    remaining_depth: u8,
+    config: &SearchConfig,
+) -> Option<i16> {
+    // Returns the raised beta the ProbCut captures have to beat, if ProbCut applies at this node
+    if !config.use_probcut
+        || remaining_depth < config.probcut_min_depth
+        || is_mate_score(beta)
+        || is_pv_node(alpha, beta)
+    {
+        return None;
+    }
+    let raised_beta = beta as i32 + config.probcut_margin as i32;
+    match is_mate_score(raised_beta.min(i16::MAX as i32) as i16) {
+        true => None,
+        false => Some(raised_beta as i16),
+    }
+}
+
+pub(crate) fn should_multi_cut(
+    alpha: i16,
+    beta: i16,
+    remaining_depth: u8,
+    config: &SearchConfig,
+) -> bool {
+    // Returns true if the multi-cut searches should be run at this node
+    config.use_multi_cut
+        && remaining_depth >= config.multi_cut_min_depth
+        && !is_mate_score(beta)
+        && !is_pv_node(alpha, beta)
+}
+
+pub(crate) fn is_quiet_move(board: &Board, mve: ChessMove) -> bool {
+    // A quiet move captures nothing and does not promote
+    let is_capture = board.piece_on(mve.get_dest()).is_some()
+        || Some(mve.get_dest()) == board.en_passant().and_then(|sq| sq.forward(board.side_to_move()));
+    !is_capture && mve.get_promotion().is_none()
+}
+
+pub(crate) fn gives_check(child_board: &Board) -> bool {
+    *child_board.checkers() != EMPTY
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove, Square};
+
+    use super::*;
+
+    #[test]
+    fn test_mate_score() {
+        assert!(is_mate_score(i16::MAX - 1));
+        assert!(is_mate_score(i16::MIN + 1));
+        assert!(is_mate_score(i16::MIN));
+        assert!(!is_mate_score(900));
+        assert!(!is_mate_score(-900));
+    }
+
+    #[test]
+    fn test_reverse_futility() {
+        let config = SearchConfig::default();
+        assert!(reverse_futility_prune(800, 100, 1, &config));
+        assert!(!reverse_futility_prune(150, 100, 1, &config));
+        assert!(!reverse_futility_prune(800, 100, config.reverse_futility_depth + 1, &config));
+        assert!(!reverse_futility_prune(800, 100, 0, &config));
+        // Never prune against a mate bound
+        assert!(!reverse_futility_prune(800, i16::MIN + 1, 1, &config));
+    }
+
+    #[test]
+    fn test_razoring() {
+        let config = SearchConfig::default();
+        assert!(should_razor(-1000, 0, 1, &config));
+        assert!(!should_razor(-10, 0, 1, &config));
+        assert!(!should_razor(-1000, 0, config.razor_margins.len() as u8, &config));
+        assert!(!should_razor(-1000, i16::MIN + 1, 1, &config));
+    }
+
+    #[test]
+    fn test_futility() {
+        let config = SearchConfig::default();
+        assert!(futility_prune(-500, 0, 1, &config));
+        assert!(!futility_prune(-100, 0, 1, &config));
+        assert!(!futility_prune(-500, 0, config.futility_margins.len() as u8, &config));
+        assert!(!futility_prune(-500, i16::MIN + 1, 1, &config));
+    }
+
+    #[test]
+    fn test_probcut_beta() {
+        let config = SearchConfig::default();
+        let depth = config.probcut_min_depth;
+        assert_eq!(probcut_beta(99, 100, depth, &config), Some(100 + config.probcut_margin));
+        assert_eq!(probcut_beta(99, 100, depth - 1, &config), None);
+        assert_eq!(probcut_beta(i16::MAX - 2, i16::MAX - 1, depth, &config), None);
+        // Raising beta into the mate range disables ProbCut too
+        let beta = consts::MATE_THRESHOLD - 1;
+        assert_eq!(probcut_beta(beta - 1, beta, depth, &config), None);
+        // Only null window nodes are cut, never the principal variation
+        assert_eq!(probcut_beta(0, 100, depth, &config), None);
+        assert_eq!(probcut_beta(i16::MIN + 1, i16::MAX - 1, depth, &config), None);
+        let disabled = SearchConfig {
+            use_probcut: false,
+            ..Default::default()
+        };
+        assert_eq!(probcut_beta(99, 100, depth, &disabled), None);
+    }
+
+    #[test]
+    fn test_multi_cut() {
+        let config = SearchConfig {
+            use_multi_cut: true,
+            ..Default::default()
+        };
+        let depth = config.multi_cut_min_depth;
+        assert!(should_multi_cut(99, 100, depth, &config));
+        assert!(!should_multi_cut(99, 100, depth - 1, &config));
+        assert!(!should_multi_cut(i16::MIN, i16::MIN + 1, depth, &config));
+        // Only null window nodes are cut, never the principal variation
+        assert!(!should_multi_cut(0, 100, depth, &config));
+        assert!(!should_multi_cut(i16::MIN + 1, i16::MAX - 1, depth, &config));
+        let disabled = SearchConfig {
+            use_multi_cut: false,
+            ..Default::default()
+        };
+        assert!(!should_multi_cut(99, 100, depth, &disabled));
+    }
+
+    #[test]
+    fn test_quiet_moves() {
+        let board = Board::from_str("3k4/5n2/8/3pP3/8/8/8/3K1R2 w - d6 0 1").unwrap();
+        assert!(is_quiet_move(&board, ChessMove::new(Square::F1, Square::F6, None)));
+        assert!(!is_quiet_move(&board, ChessMove::new(Square::F1, Square::F7, None)));
+        // En passant is a capture
+        assert!(!is_quiet_move(&board, ChessMove::new(Square::E5, Square::D6, None)));
+
+        let board = Board::from_str("3k4/8/8/8/8/8/6p1/3K1R2 b - - 0 1").unwrap();
+        // Capture and promotions are loud
+        assert!(!is_quiet_move(&board, ChessMove::new(Square::G2, Square::F1, Some(chess::Piece::Queen))));
+        assert!(!is_quiet_move(&board, ChessMove::new(Square::G2, Square::G1, Some(chess::Piece::Knight))));
+    }
+
+    #[test]
+    fn test_gives_check() {
+        let board = Board::from_str("3k4/8/8/8/8/8/8/3K1R2 w - - 0 1").unwrap();
+        assert!(gives_check(&board.make_move_new(ChessMove::new(Square::F1, Square::F8, None))));
+        assert!(!gives_check(&board.make_move_new(ChessMove::new(Square::F1, Square::F7, None))));
+    }
+}
@@ -1,9 +1,10 @@
 // Piece Square Tables
+// These are the defaults, the evaluation reads the tables from PsqtParams
 
 use chess::{Color, Piece, Square};
 
-use crate::utils::common::{Eval, abs_eval_from_color};
-use crate::gamestate::GameState;
+use crate::eval_params::fixed_array;
+use crate::utils::common::Score;
 
 const BOARD_LEN: usize = 64;
 
@@ -31,7 +32,7 @@ const PAWN_PSQT_EG: [i16; BOARD_LEN] = [
 ];
 
 // Bishop ♗
-const BISHOP_PSQT: [i16; BOARD_LEN] = [
+const BISHOP_PSQT_MG: [i16; BOARD_LEN] = [
     -20,-10,-10,-10,-10,-10,-10,-20,
     -10,  5,  0,  0,  0,  0,  5,-10,
     -10, 10, 10, 10, 10, 10, 10,-10,
@@ -42,8 +43,19 @@ const BISHOP_PSQT: [i16; BOARD_LEN] = [
     -20,-10,-10,-10,-10,-10,-10,-20, 
 ];
 
+const BISHOP_PSQT_EG: [i16; BOARD_LEN] = [
+    -15,-10,-10, -5, -5,-10,-10,-15,
+    -10, -5,  0,  0,  0,  0, -5,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5, 10, 10,  5,  0, -5,
+     -5,  0,  5, 10, 10,  5,  0, -5,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10, -5,  0,  0,  0,  0, -5,-10,
+    -15,-10,-10, -5, -5,-10,-10,-15,
+];
+
 // Rook ♖
-const ROOK_PSQT: [i16; BOARD_LEN]  = [
+const ROOK_PSQT_MG: [i16; BOARD_LEN]  = [
     0,  0,  0,  5,  5,  0,  0,  0,
    -5,  0,  0,  0,  0,  0,  0, -5,
    -5,  0,  0,  0,  0,  0,  0, -5,
@@ -54,8 +66,19 @@ const ROOK_PSQT: [i16; BOARD_LEN]  = [
     0,  0,  0,  0,  0,  0,  0,  0, 
 ];
 
+const ROOK_PSQT_EG: [i16; BOARD_LEN] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  0,  0,  0,  0,  0,  0,  0,
+    5,  5,  5,  5,  5,  5,  5,  5,
+    0,  0,  0,  0,  0,  0,  0,  0,
+];
+
 // Knight ♘
-const KNIGHT_PSQT: [i16; BOARD_LEN]  = [
+const KNIGHT_PSQT_MG: [i16; BOARD_LEN]  = [
     -50,-40,-30,-30,-30,-30,-40,-50, 
     -40,-20,  0,  0,  0,  0,-20,-40,
     -30,  0, 10, 15, 15, 10,  0,-30,
@@ -66,6 +89,17 @@ const KNIGHT_PSQT: [i16; BOARD_LEN]  = [
     -50,-40,-30,-30,-30,-30,-40,-50,                       
 ];
 
+const KNIGHT_PSQT_EG: [i16; BOARD_LEN]  = [
+    -40,-30,-20,-20,-20,-20,-30,-40,
+    -30,-15, -5,  0,  0, -5,-15,-30,
+    -20, -5, 10, 15, 15, 10, -5,-20,
+    -20,  0, 15, 20, 20, 15,  0,-20,
+    -20,  0, 15, 20, 20, 15,  0,-20,
+    -20, -5, 10, 15, 15, 10, -5,-20,
+    -30,-15, -5,  0,  0, -5,-15,-30,
+    -40,-30,-20,-20,-20,-20,-30,-40,
+];
+
 // King ♔
 const KING_PSQT_MG: [i16; BOARD_LEN] = [
     20, 30, 10,  0,  0, 10, 30, 20,
@@ -90,7 +124,7 @@ const KING_PSQT_EG: [i16; BOARD_LEN] = [
 ];
 
 // Queen ♕
-const QUEEN_PSQT: [i16; BOARD_LEN] = [
+const QUEEN_PSQT_MG: [i16; BOARD_LEN] = [
     -20,-10,-10, -5, -5,-10,-10,-20,
     -10,  0,  5,  0,  0,  0,  0,-10,
     -10,  5,  5,  5,  5,  5,  0,-10,
@@ -101,120 +135,181 @@ const QUEEN_PSQT: [i16; BOARD_LEN] = [
     -20,-10,-10, -5, -5,-10,-10,-20,            
 ];
 
-pub(crate) fn get_psqt_score(piece: Piece, color: Color, square: Square, gamestate: GameState) -> Eval {
+const QUEEN_PSQT_EG: [i16; BOARD_LEN] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+     -5,  0, 10, 15, 15, 10,  0, -5,
+     -5,  0, 10, 15, 15, 10,  0, -5,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PieceTable {
+    // Laid out from a1 to h8, black looks its squares up from the other end of the board
+    #[serde(with = "fixed_array")]
+    pub mg: [i16; BOARD_LEN],
+    #[serde(with = "fixed_array")]
+    pub eg: [i16; BOARD_LEN],
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PsqtParams {
+    pub pawn: PieceTable,
+    pub knight: PieceTable,
+    pub bishop: PieceTable,
+    pub rook: PieceTable,
+    pub queen: PieceTable,
+    pub king: PieceTable,
+}
+
+impl Default for PsqtParams {
+    fn default() -> Self {
+        let table = |mg, eg| PieceTable { mg, eg };
+        PsqtParams {
+            pawn: table(PAWN_PSQT_MG, PAWN_PSQT_EG),
+            knight: table(KNIGHT_PSQT_MG, KNIGHT_PSQT_EG),
+            bishop: table(BISHOP_PSQT_MG, BISHOP_PSQT_EG),
+            rook: table(ROOK_PSQT_MG, ROOK_PSQT_EG),
+            queen: table(QUEEN_PSQT_MG, QUEEN_PSQT_EG),
+            king: table(KING_PSQT_MG, KING_PSQT_EG),
+        }
+    }
+}
+
+impl PsqtParams {
+    pub fn table(&self, piece: Piece) -> &PieceTable {
+        match piece {
+            Piece::Pawn => &self.pawn,
+            Piece::Knight => &self.knight,
+            Piece::Bishop => &self.bishop,
+            Piece::Rook => &self.rook,
+            Piece::Queen => &self.queen,
+            Piece::King => &self.king,
+        }
+    }
+}
+
+pub(crate) fn get_psqt_score(
+    piece: Piece,
+    color: Color,
+    square: Square,
+    params: &PsqtParams,
+) -> Score {
 
     let probe_index = match color {
         Color::White => square.to_index(),
         Color::Black => (BOARD_LEN - 1) - square.to_index(),
     };
 
-    let eval_abs = match piece {
-        Piece::Pawn => match gamestate {
-            GameState::_Opening => PAWN_PSQT_MG[probe_index],
-            GameState::Middle => PAWN_PSQT_MG[probe_index],
-            GameState::End => PAWN_PSQT_EG[probe_index],
-        },
-        Piece::Knight => KNIGHT_PSQT[probe_index],
-        Piece::Bishop => BISHOP_PSQT[probe_index],
-        Piece::Rook => ROOK_PSQT[probe_index],
-        Piece::Queen => QUEEN_PSQT[probe_index],
-        Piece::King => match gamestate {
-            GameState::_Opening => KING_PSQT_MG[probe_index],
-            GameState::Middle => KING_PSQT_MG[probe_index],
-            GameState::End => KING_PSQT_EG[probe_index],
-        } ,
-    };
-
-    abs_eval_from_color(eval_abs, color)
+    let table = params.table(piece);
+    Score::new(table.mg[probe_index], table.eg[probe_index]).for_colour(color)
 }
 
 #[cfg(test)]
 mod tests {
     use chess::{Square, Color, Piece};
-    use crate::gamestate::GameState;
 
-    use super::get_psqt_score;
+    use super::{get_psqt_score, PsqtParams};
 
     #[test]
     fn test_pawn(){
         // White pawn A1
-        assert_eq!(get_psqt_score(Piece::Pawn, Color::White, Square::A2, GameState::Middle).for_colour(Color::White), 5);
+        assert_eq!(get_psqt_score(Piece::Pawn, Color::White, Square::A2, &PsqtParams::default()).for_colour(Color::White).mg, 5);
 
         // White pawn G6
-        assert_eq!(get_psqt_score(Piece::Pawn, Color::White, Square::G6, GameState::Middle).for_colour(Color::White), 10);
+        assert_eq!(get_psqt_score(Piece::Pawn, Color::White, Square::G6, &PsqtParams::default()).for_colour(Color::White).mg, 10);
 
         // Confirm symmetry
 
         // Black pawn G8
-        assert_eq!(get_psqt_score(Piece::Pawn, Color::Black, Square::H7, GameState::Middle).for_colour(Color::Black), 5);
+        assert_eq!(get_psqt_score(Piece::Pawn, Color::Black, Square::H7, &PsqtParams::default()).for_colour(Color::Black).mg, 5);
 
         // Black pawn B3
-        assert_eq!(get_psqt_score(Piece::Pawn, Color::Black, Square::B3, GameState::Middle).for_colour(Color::Black), 10);
+        assert_eq!(get_psqt_score(Piece::Pawn, Color::Black, Square::B3, &PsqtParams::default()).for_colour(Color::Black).mg, 10);
     }
 
     #[test]
     fn test_bishop(){
         // White bishop A6
-        assert_eq!(get_psqt_score(Piece::Bishop, Color::White, Square::A6, GameState::Middle).for_colour(Color::White), -10);
+        assert_eq!(get_psqt_score(Piece::Bishop, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).mg, -10);
 
         // Black bishop G5
-        assert_eq!(get_psqt_score(Piece::Bishop, Color::Black, Square::G5, GameState::Middle).for_colour(Color::Black), 0);
+        assert_eq!(get_psqt_score(Piece::Bishop, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).mg, 0);
     }
 
     #[test]
     fn test_rook(){
         // White rook A6
-        assert_eq!(get_psqt_score(Piece::Rook, Color::White, Square::A6, GameState::Middle).for_colour(Color::White), -5);
+        assert_eq!(get_psqt_score(Piece::Rook, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).mg, -5);
 
         // Black rook G5
-        assert_eq!(get_psqt_score(Piece::Rook, Color::Black, Square::G5, GameState::Middle).for_colour(Color::Black), 0);
+        assert_eq!(get_psqt_score(Piece::Rook, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).mg, 0);
     }
 
     #[test]
     fn test_knight(){
         // White knight A6
-        assert_eq!(get_psqt_score(Piece::Knight, Color::White, Square::A6, GameState::Middle).for_colour(Color::White), -30);
+        assert_eq!(get_psqt_score(Piece::Knight, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).mg, -30);
 
         // Black knight G5
-        assert_eq!(get_psqt_score(Piece::Knight, Color::Black, Square::G5, GameState::Middle).for_colour(Color::Black), 5);
+        assert_eq!(get_psqt_score(Piece::Knight, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).mg, 5);
     }
 
     #[test]
     fn test_king(){
         // White king A6
-        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::A6, GameState::Middle).for_colour(Color::White), -30);
+        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).mg, -30);
 
         // Black king G5
-        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::G5, GameState::Middle).for_colour(Color::Black), -30);
+        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).mg, -30);
 
         // Starting position white king
-        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::E1, GameState::Middle).for_colour(Color::White), 0);
+        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::E1, &PsqtParams::default()).for_colour(Color::White).mg, 0);
 
         // Starting position black king
-        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::E8, GameState::Middle).for_colour(Color::Black), 0);       
+        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::E8, &PsqtParams::default()).for_colour(Color::Black).mg, 0);       
     }
 
     #[test]
     fn test_endgame_king(){
         // White king A6
-        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::A6, GameState::End).for_colour(Color::White), -30);
+        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).eg, -30);
 
         // Black king G5
-        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::G5, GameState::End).for_colour(Color::Black), -10);
+        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).eg, -10);
 
         // Starting position white king
-        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::E1, GameState::End).for_colour(Color::White), -30);
+        assert_eq!(get_psqt_score(Piece::King, Color::White, Square::E1, &PsqtParams::default()).for_colour(Color::White).eg, -30);
 
         // Starting position black king
-        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::E8, GameState::End).for_colour(Color::Black), -30);          
+        assert_eq!(get_psqt_score(Piece::King, Color::Black, Square::E8, &PsqtParams::default()).for_colour(Color::Black).eg, -30);          
     }
 
     #[test]
     fn test_queen(){
         // White queen A6
-        assert_eq!(get_psqt_score(Piece::Queen, Color::White, Square::A6, GameState::Middle).for_colour(Color::White), -10);
+        assert_eq!(get_psqt_score(Piece::Queen, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).mg, -10);
 
         // Black queen G5
-        assert_eq!(get_psqt_score(Piece::Queen, Color::Black, Square::G5, GameState::Middle).for_colour(Color::Black), 0);
+        assert_eq!(get_psqt_score(Piece::Queen, Color::Black, Square::G5, &PsqtParams::default()).for_colour(Color::Black).mg, 0);
+    }
+
+    #[test]
+    fn test_endgame_pieces(){
+        // White knight A6
+        assert_eq!(get_psqt_score(Piece::Knight, Color::White, Square::A6, &PsqtParams::default()).for_colour(Color::White).eg, -20);
+
+        // White rook on the seventh
+        assert_eq!(get_psqt_score(Piece::Rook, Color::White, Square::A7, &PsqtParams::default()).for_colour(Color::White).eg, 5);
+
+        // Black queen D4
+        assert_eq!(get_psqt_score(Piece::Queen, Color::Black, Square::D4, &PsqtParams::default()).for_colour(Color::Black).eg, 15);
+
+        // Black bishop H1
+        assert_eq!(get_psqt_score(Piece::Bishop, Color::Black, Square::H1, &PsqtParams::default()).for_colour(Color::Black).eg, -15);
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,15 @@
 use chess::{Board, MoveGen};
 
-use crate::{utils::common::{Eval, abs_eval_from_color}, evaluation::evaluate_board, ordering, consts::QUIESENT_LIM};
+use crate::{utils::common::{Eval, abs_eval_from_color}, evaluator::{Evaluator, with_move}, ordering, search_config::SearchConfig};
+use crate::eval_params::MaterialParams;
 
-fn fetch_sorted_captures(board: &Board) -> std::vec::IntoIter<chess::ChessMove>{
+pub(crate) fn fetch_sorted_captures(
+    board: &Board,
+    material: &MaterialParams,
+) -> std::vec::IntoIter<chess::ChessMove>{
     let mut capture_moves = MoveGen::new_legal(board);
     capture_moves.set_iterator_mask(*board.color_combined(!board.side_to_move())); // Set mask for captures
-    ordering::order_moves(capture_moves, *board, None, None) // sort all the moves
+    ordering::order_moves(capture_moves, *board, None, None, material) // sort all the moves
 }
 
 pub(crate) fn quiescent_search(
@@ -13,32 +17,38 @@ pub(crate) fn quiescent_search(
     alpha: i16,
     beta: i16,
     depth: u8,
+    evaluator: &dyn Evaluator,
+    config: &SearchConfig,
 ) -> Eval {
     let mut alpha = alpha;
     // Search through all terminal captures
-    let stand_pat = evaluate_board(*board);
+    let stand_pat = evaluator.evaluate(board);
 
     if stand_pat.for_colour(board.side_to_move()) >= beta{
         return abs_eval_from_color(beta, board.side_to_move());
     }
 
-    if depth > QUIESENT_LIM{
+    if depth > config.quiescent_limit{
         return stand_pat;
     }
 
-    let sorted_moves = fetch_sorted_captures(board);
+    let sorted_moves = fetch_sorted_captures(board, evaluator.material());
 
     if alpha < stand_pat.for_colour(board.side_to_move()) {
         alpha = stand_pat.for_colour(board.side_to_move());
     }
 
     for capture_move in sorted_moves {
-        let score = quiescent_search(
-            &board.make_move_new(capture_move),
-            -beta,
-            -alpha,
-            depth + 1,
-        );
+        let score = with_move(evaluator, board, capture_move, || {
+            quiescent_search(
+                &board.make_move_new(capture_move),
+                -beta,
+                -alpha,
+                depth + 1,
+                evaluator,
+                config,
+            )
+        });
 
         if score.for_colour(board.side_to_move()) >= beta {
             return abs_eval_from_color(beta, board.side_to_move());
@@ -57,16 +67,19 @@ mod tests{
     use chess::{Board, ChessMove, Square};
 
     use super::{fetch_sorted_captures, quiescent_search};
+    use crate::eval_params::MaterialParams;
+    use crate::evaluator::HandcraftedEvaluator;
+    use crate::search_config::SearchConfig;
 
     #[test]
     #[serial_test::serial]
     fn test_caputes_only(){
         let board_init: Board = Default::default();
-        let mut sorted_cap = fetch_sorted_captures(&board_init);
+        let mut sorted_cap = fetch_sorted_captures(&board_init, &MaterialParams::default());
         assert_eq!(sorted_cap.next(), None); // Confirm we are using only captures!
 
         let board_eg: Board = Board::from_str("8/3K4/8/8/8/8/3R4/3k4 b - - 0 1").unwrap();
-        let mut sorted_cap = fetch_sorted_captures(&board_eg);
+        let mut sorted_cap = fetch_sorted_captures(&board_eg, &MaterialParams::default());
         assert_eq!(sorted_cap.next(), Some(ChessMove::new(Square::D1, Square::D2, None)));
         assert_eq!(sorted_cap.next(), None);
     }
@@ -75,7 +88,14 @@ mod tests{
     #[serial_test::serial]
     fn test_quiescent_basic(){
         let board_eg: Board = Board::from_str("8/3K4/8/8/8/8/3R4/3k4 b - - 0 1").unwrap();
-        let q_res = quiescent_search(&board_eg, i16::min_value() + 1, i16::max_value() - 1, 0);
+        let q_res = quiescent_search(
+            &board_eg,
+            i16::MIN + 1,
+            i16::MAX - 1,
+            0,
+            &HandcraftedEvaluator::default(),
+            &SearchConfig::default(),
+        );
         println!("{:#?}", q_res);
     }
 }
\ No newline at end of file
@@ -0,0 +1,840 @@
+// Retrograde analysis for small endgames
+// Builds distance to mate (DTM) tables for any material signature of up to four pieces (kings
+// included) by working backwards from checkmates, so the search can look up perfect play
+// Castling is not represented and en passant is ignored
+
+use std::{collections::HashMap, fmt, fs, io, path::Path, str::FromStr};
+
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, CastleRights, ChessMove, Color, MoveGen, Piece, ALL_SQUARES, EMPTY,
+};
+
+use crate::utils::common::{abs_eval_from_color, Eval};
+
+pub const MAX_PIECES: usize = 4;
+
+const FILE_EXTENSION: &str = "dtm";
+const FILE_HEADER: &[u8] = b"SRDTM1";
+
+// Scores used while generating, higher is better for the side to move
+// Winning in n plies scores MATE - n and losing in n plies scores n - MATE
+const MATE: i16 = 1000;
+const NO_MOVE: i16 = -2 * MATE; // Worse than any real result, for positions with no moves looked at yet
+const INVALID_SCORE: i16 = i16::MIN;
+
+// Stored codes, wins take odd ply counts and losses even ones so they can share a byte
+const DRAW_CODE: u8 = 0;
+const INVALID_CODE: u8 = u8::MAX;
+const MAX_PLIES: usize = 252;
+
+const PROMOTIONS: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+// Result for the side to move, counted in plies until mate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u8),
+    Loss(u8),
+    Draw,
+}
+
+impl Dtm {
+    fn from_code(code: u8) -> Option<Dtm> {
+        match code {
+            INVALID_CODE => None,
+            DRAW_CODE => Some(Dtm::Draw),
+            plies if plies % 2 == 1 => Some(Dtm::Win(plies)),
+            code => Some(Dtm::Loss(code - 2)),
+        }
+    }
+
+    fn to_code(self) -> u8 {
+        match self {
+            Dtm::Win(plies) => plies,
+            Dtm::Loss(plies) => plies + 2,
+            Dtm::Draw => DRAW_CODE,
+        }
+    }
+
+    fn score(self) -> i16 {
+        match self {
+            Dtm::Win(plies) => MATE - plies as i16,
+            Dtm::Loss(plies) => plies as i16 - MATE,
+            Dtm::Draw => 0,
+        }
+    }
+
+    fn from_score(score: i16) -> Dtm {
+        match score {
+            0 => Dtm::Draw,
+            score if score > 0 => Dtm::Win((MATE - score) as u8),
+            score => Dtm::Loss((MATE + score) as u8),
+        }
+    }
+
+    pub fn after_move(self) -> Dtm {
+        // Result for the side that moved into this position
+        match self {
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+            Dtm::Draw => Dtm::Draw,
+        }
+    }
+}
+
+fn plies(score: i16) -> usize {
+    (MATE - score.abs()) as usize
+}
+
+fn parent_score(child_score: i16) -> i16 {
+    // Score one ply earlier, from the point of view of the side that moved
+    match child_score {
+        0 => 0,
+        score if score > 0 => 1 - score,
+        score => -score - 1,
+    }
+}
+
+// Material of both sides, excluding kings, most valuable pieces first
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    white: Vec<Piece>,
+    black: Vec<Piece>,
+}
+
+fn piece_order(piece: Piece) -> u8 {
+    match piece {
+        Piece::Queen => 0,
+        Piece::Rook => 1,
+        Piece::Bishop => 2,
+        Piece::Knight => 3,
+        Piece::Pawn => 4,
+        Piece::King => 5,
+    }
+}
+
+fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::Queen => 'Q',
+        Piece::Rook => 'R',
+        Piece::Bishop => 'B',
+        Piece::Knight => 'N',
+        Piece::Pawn => 'P',
+        Piece::King => 'K',
+    }
+}
+
+impl MaterialSignature {
+    pub fn new(mut white: Vec<Piece>, mut black: Vec<Piece>) -> Self {
+        white.retain(|piece| *piece != Piece::King);
+        black.retain(|piece| *piece != Piece::King);
+        white.sort_by_key(|piece| piece_order(*piece));
+        black.sort_by_key(|piece| piece_order(*piece));
+        Self { white, black }
+    }
+
+    fn from_placed(placed: &[(Piece, Color, usize)]) -> Self {
+        let side = |color: Color| {
+            placed
+                .iter()
+                .filter(|(_, piece_color, _)| *piece_color == color)
+                .map(|(piece, _, _)| *piece)
+                .collect()
+        };
+        Self::new(side(Color::White), side(Color::Black))
+    }
+
+    pub fn from_board(board: &Board) -> Self {
+        let side = |color: Color| {
+            board
+                .color_combined(color)
+                .filter_map(|sq| board.piece_on(sq))
+                .collect()
+        };
+        Self::new(side(Color::White), side(Color::Black))
+    }
+
+    pub fn side(&self, color: Color) -> &[Piece] {
+        // Pieces of one colour, kings left out
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    pub fn num_pieces(&self) -> usize {
+        2 + self.white.len() + self.black.len()
+    }
+
+    fn side_mut(&mut self, color: Color) -> &mut Vec<Piece> {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    fn pieces(&self) -> Vec<(Piece, Color)> {
+        // Table slots, the two kings first
+        let mut pieces = vec![(Piece::King, Color::White), (Piece::King, Color::Black)];
+        pieces.extend(self.white.iter().map(|piece| (*piece, Color::White)));
+        pieces.extend(self.black.iter().map(|piece| (*piece, Color::Black)));
+        pieces
+    }
+
+    fn children(&self) -> Vec<MaterialSignature> {
+        // Signatures reachable by a capture, a promotion or both
+        let mut children: Vec<MaterialSignature> = Vec::new();
+        for color in [Color::White, Color::Black] {
+            let own = match color {
+                Color::White => &self.white,
+                Color::Black => &self.black,
+            };
+            let enemy_count = self.num_pieces() - 2 - own.len();
+
+            // Captures of one of our pieces
+            for i in 0..own.len() {
+                let mut child = self.clone();
+                child.side_mut(color).remove(i);
+                children.push(child);
+            }
+
+            // Promotions, optionally capturing an enemy piece
+            for (i, _) in own.iter().enumerate().filter(|(_, piece)| **piece == Piece::Pawn) {
+                for promotion in PROMOTIONS {
+                    let mut promoted = self.clone();
+                    promoted.side_mut(color)[i] = promotion;
+                    for j in 0..enemy_count {
+                        let mut child = promoted.clone();
+                        child.side_mut(!color).remove(j);
+                        children.push(child);
+                    }
+                    children.push(promoted);
+                }
+            }
+        }
+        children
+            .into_iter()
+            .map(|child| Self::new(child.white, child.black))
+            .filter(|child| child.num_pieces() > 2)
+            .fold(Vec::new(), |mut unique, child| {
+                if !unique.contains(&child) {
+                    unique.push(child);
+                }
+                unique
+            })
+    }
+}
+
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let white: String = self.white.iter().map(|piece| piece_char(*piece)).collect();
+        let black: String = self.black.iter().map(|piece| piece_char(*piece)).collect();
+        write!(f, "K{}vK{}", white, black)
+    }
+}
+
+impl FromStr for MaterialSignature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Parses signatures such as "KQvK" or "KRPvKR"
+        let parse_side = |side: &str| -> Result<Vec<Piece>, String> {
+            let pieces = side
+                .strip_prefix('K')
+                .ok_or_else(|| format!("each side of {} needs a king", s))?;
+            pieces
+                .chars()
+                .map(|c| match c {
+                    'Q' => Ok(Piece::Queen),
+                    'R' => Ok(Piece::Rook),
+                    'B' => Ok(Piece::Bishop),
+                    'N' => Ok(Piece::Knight),
+                    'P' => Ok(Piece::Pawn),
+                    _ => Err(format!("unknown piece {} in {}", c, s)),
+                })
+                .collect()
+        };
+        let (white, black) = s
+            .split_once('v')
+            .ok_or_else(|| format!("{} should look like KQvK", s))?;
+        Ok(Self::new(parse_side(white)?, parse_side(black)?))
+    }
+}
+
+// Positions are indexed by side to move and the square of every piece in the signature's slot order
+fn encode(stm: Color, squares: &[usize]) -> usize {
+    let placement = squares.iter().rev().fold(0, |idx, sq| idx * 64 + sq);
+    placement * 2 + (stm == Color::Black) as usize
+}
+
+fn decode(idx: usize, squares: &mut [usize]) -> Color {
+    let mut placement = idx / 2;
+    for sq in squares.iter_mut() {
+        *sq = placement % 64;
+        placement /= 64;
+    }
+    if idx.is_multiple_of(2) {
+        Color::White
+    } else {
+        Color::Black
+    }
+}
+
+fn bit(sq: usize) -> BitBoard {
+    BitBoard(1 << sq)
+}
+
+fn attacks(piece: Piece, color: Color, sq: usize, occupied: BitBoard) -> BitBoard {
+    let sq = ALL_SQUARES[sq];
+    match piece {
+        Piece::Pawn => get_pawn_attacks(sq, color, !EMPTY),
+        Piece::Knight => get_knight_moves(sq),
+        Piece::Bishop => get_bishop_moves(sq, occupied),
+        Piece::Rook => get_rook_moves(sq, occupied),
+        Piece::Queen => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+        Piece::King => get_king_moves(sq),
+    }
+}
+
+fn occupancy(pieces: &[(Piece, Color)], squares: &[usize], color: Color) -> BitBoard {
+    pieces
+        .iter()
+        .zip(squares)
+        .filter(|((_, piece_color), _)| *piece_color == color)
+        .fold(EMPTY, |occupied, (_, sq)| occupied | bit(*sq))
+}
+
+fn king_slot(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn is_attacked(
+    sq: usize,
+    by: Color,
+    pieces: &[(Piece, Color)],
+    squares: &[usize],
+    occupied: BitBoard,
+    captured: Option<usize>,
+) -> bool {
+    // Whether any piece of `by` (other than a captured one) attacks the square
+    pieces.iter().zip(squares).enumerate().any(|(slot, ((piece, color), from))| {
+        *color == by
+            && Some(slot) != captured
+            && attacks(*piece, by, *from, occupied) & bit(sq) != EMPTY
+    })
+}
+
+fn is_valid(pieces: &[(Piece, Color)], squares: &[usize], stm: Color) -> bool {
+    // Pieces on distinct squares, no pawns on the back ranks and the side not to move not in check
+    let occupied = occupancy(pieces, squares, Color::White) | occupancy(pieces, squares, Color::Black);
+    occupied.popcnt() as usize == squares.len()
+        && pieces
+            .iter()
+            .zip(squares)
+            .all(|((piece, _), sq)| *piece != Piece::Pawn || (8..56).contains(sq))
+        && !is_attacked(squares[king_slot(!stm)], stm, pieces, squares, occupied, None)
+}
+
+fn pawn_pushes(from: usize, color: Color, occupied: BitBoard) -> BitBoard {
+    let (forward, start_rank): (isize, usize) = match color {
+        Color::White => (8, 1),
+        Color::Black => (-8, 6),
+    };
+    let one = (from as isize + forward) as usize;
+    if occupied & bit(one) != EMPTY {
+        return EMPTY;
+    }
+    let two = (one as isize + forward) as usize;
+    if from / 8 == start_rank && occupied & bit(two) == EMPTY {
+        bit(one) | bit(two)
+    } else {
+        bit(one)
+    }
+}
+
+fn pawn_unpushes(to: usize, color: Color, occupied: BitBoard) -> BitBoard {
+    // Squares a pawn on `to` could have been pushed from
+    let (backward, fourth_rank): (isize, usize) = match color {
+        Color::White => (-8, 3),
+        Color::Black => (8, 4),
+    };
+    let one = (to as isize + backward) as usize;
+    if occupied & bit(one) != EMPTY || !(1..7).contains(&(one / 8)) {
+        return EMPTY;
+    }
+    let two = (one as isize + backward) as usize;
+    if to / 8 == fourth_rank && occupied & bit(two) == EMPTY {
+        bit(one) | bit(two)
+    } else {
+        bit(one)
+    }
+}
+
+fn predecessors(pieces: &[(Piece, Color)], squares: &[usize], stm: Color, mut f: impl FnMut(usize)) {
+    // Positions that reach this one with a move that doesn't capture or promote
+    let mover = !stm;
+    let occupied = occupancy(pieces, squares, Color::White) | occupancy(pieces, squares, Color::Black);
+    let mut previous = [0; MAX_PIECES];
+    previous[..squares.len()].copy_from_slice(squares);
+
+    for (slot, (piece, color)) in pieces.iter().enumerate() {
+        if *color != mover {
+            continue;
+        }
+        let origins = match piece {
+            Piece::Pawn => pawn_unpushes(squares[slot], mover, occupied),
+            _ => attacks(*piece, mover, squares[slot], occupied) & !occupied,
+        };
+        for origin in origins {
+            previous[slot] = origin.to_index();
+            f(encode(mover, &previous[..squares.len()]));
+        }
+        previous[slot] = squares[slot];
+    }
+}
+
+enum Successor {
+    Internal,   // Move to a position with the same material
+    Exit(i16),       // Score (for the side to move) of a capture or promotion into another table
+}
+
+pub struct DtmTable {
+    pieces: Vec<(Piece, Color)>,
+    codes: Vec<u8>,
+}
+
+impl DtmTable {
+    pub fn max_win(&self) -> Option<u8> {
+        // Longest mate in the table, in plies
+        self.codes
+            .iter()
+            .filter_map(|code| match Dtm::from_code(*code) {
+                Some(Dtm::Win(plies)) => Some(plies),
+                _ => None,
+            })
+            .max()
+    }
+}
+
+#[derive(Default)]
+pub struct DtmTables {
+    tables: HashMap<MaterialSignature, DtmTable>,
+}
+
+impl DtmTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn signatures(&self) -> Vec<String> {
+        let mut signatures: Vec<String> = self.tables.keys().map(|sig| sig.to_string()).collect();
+        signatures.sort();
+        signatures
+    }
+
+    pub fn table(&self, signature: &MaterialSignature) -> Option<&DtmTable> {
+        self.tables.get(signature)
+    }
+
+    pub fn generate(&mut self, signature: &MaterialSignature) -> io::Result<()> {
+        // Generates the table along with every table it can capture or promote into
+        if signature.num_pieces() > MAX_PIECES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has more than {} pieces", signature, MAX_PIECES),
+            ));
+        }
+        if self.tables.contains_key(signature) || signature.num_pieces() <= 2 {
+            return Ok(());
+        }
+        for child in signature.children() {
+            self.generate(&child)?;
+        }
+        let table = self.build_table(signature);
+        self.tables.insert(signature.clone(), table);
+        Ok(())
+    }
+
+    fn lookup_score(&self, stm: Color, placed: &[(Piece, Color, usize)]) -> Option<i16> {
+        // Score for the side to move of an arbitrary placement, bare kings are a draw
+        if placed.len() <= 2 {
+            return Some(0);
+        }
+        let table = self.tables.get(&MaterialSignature::from_placed(placed))?;
+
+        // Fill the table's slots from the placed pieces, identical pieces in any order
+        let mut used = [false; MAX_PIECES];
+        let mut squares = [0; MAX_PIECES];
+        for (slot, (piece, color)) in table.pieces.iter().enumerate() {
+            let found = (0..placed.len())
+                .find(|i| !used[*i] && placed[*i].0 == *piece && placed[*i].1 == *color)?;
+            used[found] = true;
+            squares[slot] = placed[found].2;
+        }
+        let code = table.codes[encode(stm, &squares[..placed.len()])];
+        Dtm::from_code(code).map(Dtm::score)
+    }
+
+    fn successors(
+        &self,
+        pieces: &[(Piece, Color)],
+        squares: &[usize],
+        stm: Color,
+        mut f: impl FnMut(Successor),
+    ) {
+        // Every legal move of the position
+        let own = occupancy(pieces, squares, stm);
+        let enemy = occupancy(pieces, squares, !stm);
+        let occupied = own | enemy;
+        let mut next = [0; MAX_PIECES];
+        next[..squares.len()].copy_from_slice(squares);
+
+        for (slot, (piece, color)) in pieces.iter().enumerate() {
+            if *color != stm {
+                continue;
+            }
+            let from = squares[slot];
+            let targets = match piece {
+                Piece::Pawn => pawn_pushes(from, stm, occupied) | (attacks(*piece, stm, from, occupied) & enemy),
+                _ => attacks(*piece, stm, from, occupied) & !own,
+            };
+
+            for to in targets {
+                let to = to.to_index();
+                let captured = (0..squares.len()).find(|i| pieces[*i].1 != stm && squares[*i] == to);
+                next[slot] = to;
+
+                // Our king mustn't be left in check
+                let next_occupied = (occupied & !bit(from)) | bit(to);
+                let king_sq = next[king_slot(stm)];
+                if is_attacked(king_sq, !stm, pieces, &next[..squares.len()], next_occupied, captured) {
+                    continue;
+                }
+
+                let promotes = *piece == Piece::Pawn && (to / 8 == 0 || to / 8 == 7);
+                if captured.is_none() && !promotes {
+                    f(Successor::Internal);
+                    continue;
+                }
+
+                let new_pieces: &[Piece] = if promotes { &PROMOTIONS } else { std::slice::from_ref(piece) };
+                for new_piece in new_pieces {
+                    let placed: Vec<(Piece, Color, usize)> = (0..squares.len())
+                        .filter(|i| Some(*i) != captured)
+                        .map(|i| {
+                            let placed_piece = if i == slot { *new_piece } else { pieces[i].0 };
+                            (placed_piece, pieces[i].1, next[i])
+                        })
+                        .collect();
+                    let child_score = self
+                        .lookup_score(!stm, &placed)
+                        .expect("child tables are generated first");
+                    f(Successor::Exit(parent_score(child_score)));
+                }
+            }
+            next[slot] = from;
+        }
+    }
+
+    fn build_table(&self, signature: &MaterialSignature) -> DtmTable {
+        let pieces = signature.pieces();
+        let n = pieces.len();
+        let size = 2 * 64usize.pow(n as u32);
+
+        let mut best = vec![NO_MOVE; size]; // Best score found so far for the side to move
+        let mut remaining = vec![0u8; size]; // Moves within this table whose result is still unknown
+        let mut resolved = vec![false; size];
+        let mut buckets: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2]; // Positions to resolve, by plies to mate
+
+        // Start from everything we know without searching: mates, stalemates and captures or
+        // promotions into tables we already have
+        let mut squares = [0; MAX_PIECES];
+        for idx in 0..size {
+            let stm = decode(idx, &mut squares[..n]);
+            let squares = &squares[..n];
+            if !is_valid(&pieces, squares, stm) {
+                best[idx] = INVALID_SCORE;
+                resolved[idx] = true;
+                continue;
+            }
+
+            let mut internal = 0;
+            let mut exit_best = NO_MOVE;
+            self.successors(&pieces, squares, stm, |successor| match successor {
+                Successor::Internal => internal += 1,
+                Successor::Exit(score) => exit_best = exit_best.max(score),
+            });
+
+            if internal == 0 && exit_best == NO_MOVE {
+                let occupied = occupancy(&pieces, squares, Color::White) | occupancy(&pieces, squares, Color::Black);
+                if is_attacked(squares[king_slot(stm)], !stm, &pieces, squares, occupied, None) {
+                    best[idx] = -MATE; // Checkmated
+                    buckets[0].push(idx as u32);
+                } else {
+                    best[idx] = 0; // Stalemate
+                    resolved[idx] = true;
+                }
+                continue;
+            }
+
+            best[idx] = exit_best;
+            remaining[idx] = internal;
+            if exit_best > 0 || (internal == 0 && exit_best < 0) {
+                buckets[plies(exit_best)].push(idx as u32);
+            } else if internal == 0 {
+                resolved[idx] = true; // Every move leaves the table and the best of them draws
+            }
+        }
+
+        // Work outwards one ply at a time, a position next to a loss is a win and a position
+        // whose moves all reach wins is a loss
+        for ply in 0..=MAX_PLIES {
+            for idx in std::mem::take(&mut buckets[ply]) {
+                let idx = idx as usize;
+                let score = best[idx];
+                if resolved[idx] || plies(score) != ply || (score < 0 && remaining[idx] != 0) {
+                    continue; // Already done, or queued before a better score was found
+                }
+                resolved[idx] = true;
+
+                let stm = decode(idx, &mut squares[..n]);
+                predecessors(&pieces, &squares[..n], stm, |prev| {
+                    if resolved[prev] {
+                        return;
+                    }
+                    let candidate = parent_score(score);
+                    if score < 0 {
+                        if candidate > best[prev] {
+                            best[prev] = candidate;
+                            buckets[ply + 1].push(prev as u32);
+                        }
+                    } else {
+                        remaining[prev] -= 1;
+                        best[prev] = best[prev].max(candidate);
+                        if remaining[prev] == 0 && best[prev] < 0 && plies(best[prev]) <= MAX_PLIES {
+                            buckets[plies(best[prev])].push(prev as u32);
+                        }
+                    }
+                });
+            }
+        }
+
+        // Anything left can't be forced either way
+        let codes = (0..size)
+            .map(|idx| match best[idx] {
+                INVALID_SCORE => INVALID_CODE,
+                score if resolved[idx] && score != NO_MOVE => Dtm::from_score(score).to_code(),
+                _ => DRAW_CODE,
+            })
+            .collect();
+        DtmTable { pieces, codes }
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        // Result for the side to move, None if the position isn't in our tables
+        if board.combined().popcnt() as usize > MAX_PIECES
+            || board.castle_rights(Color::White) != CastleRights::NoRights
+            || board.castle_rights(Color::Black) != CastleRights::NoRights
+        {
+            return None;
+        }
+        let placed: Vec<(Piece, Color, usize)> = board
+            .combined()
+            .into_iter()
+            .map(|sq| (board.piece_on(sq).unwrap(), board.color_on(sq).unwrap(), sq.to_index()))
+            .collect();
+        self.lookup_score(board.side_to_move(), &placed)
+            .map(Dtm::from_score)
+    }
+
+    pub(crate) fn probe_eval(&self, board: &Board, depth: u8) -> Option<Eval> {
+        // Tablebase mates score like mates found by the search, sooner mates score higher
+        let score = match self.probe(board)? {
+            Dtm::Win(plies) => i16::MAX - depth as i16 - plies as i16,
+            Dtm::Loss(plies) => -(i16::MAX - depth as i16 - plies as i16),
+            Dtm::Draw => 0,
+        };
+        Some(abs_eval_from_color(score, board.side_to_move()))
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<(ChessMove, Dtm)> {
+        // Fastest mate, slowest loss or a draw if that's all there is
+        let mut best: Option<(ChessMove, Dtm)> = None;
+        for mve in MoveGen::new_legal(board) {
+            let result = self.probe(&board.make_move_new(mve))?.after_move();
+            if best.is_none_or(|(_, best_result)| result.score() > best_result.score()) {
+                best = Some((mve, result));
+            }
+        }
+        best
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        // One file per signature, a short header followed by a byte per position
+        fs::create_dir_all(dir)?;
+        for (signature, table) in &self.tables {
+            let path = dir.join(format!("{}.{}", signature, FILE_EXTENSION));
+            fs::write(path, [FILE_HEADER, &table.codes].concat())?;
+        }
+        Ok(())
+    }
+
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let mut tables = Self::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(FILE_EXTENSION) {
+                continue;
+            }
+            let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+            let signature: MaterialSignature = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .parse()
+                .map_err(invalid)?;
+            let bytes = fs::read(&path)?;
+            let codes = bytes
+                .strip_prefix(FILE_HEADER)
+                .filter(|codes| codes.len() == 2 * 64usize.pow(signature.num_pieces() as u32))
+                .ok_or_else(|| invalid(format!("{} is not a {} table", path.display(), signature)))?;
+            tables.tables.insert(
+                signature.clone(),
+                DtmTable {
+                    pieces: signature.pieces(),
+                    codes: codes.to_vec(),
+                },
+            );
+        }
+        Ok(tables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, BoardBuilder, ChessMove, Square};
+
+    use super::*;
+
+    fn tables_for(signature: &str) -> DtmTables {
+        let mut tables = DtmTables::new();
+        tables.generate(&signature.parse().unwrap()).unwrap();
+        tables
+    }
+
+    #[test]
+    fn test_signature() {
+        let signature: MaterialSignature = "KPRvKN".parse().unwrap();
+        assert_eq!(signature.to_string(), "KRPvKN");
+        assert_eq!(signature.num_pieces(), 5);
+        assert!("KQK".parse::<MaterialSignature>().is_err());
+        assert!("QvK".parse::<MaterialSignature>().is_err());
+
+        let board = Board::from_str("8/8/4k3/8/2n5/3K4/8/4NB2 w - - 0 1").unwrap();
+        let signature = MaterialSignature::from_board(&board);
+        assert_eq!(signature.to_string(), "KBNvKN");
+        assert_eq!(signature.side(Color::White), &[Piece::Bishop, Piece::Knight]);
+
+        let children: Vec<String> = "KPvK"
+            .parse::<MaterialSignature>()
+            .unwrap()
+            .children()
+            .iter()
+            .map(|child| child.to_string())
+            .collect();
+        assert_eq!(children, vec!["KQvK", "KRvK", "KBvK", "KNvK"]);
+    }
+
+    #[test]
+    fn test_dtm_codes() {
+        for dtm in [Dtm::Win(1), Dtm::Win(31), Dtm::Loss(0), Dtm::Loss(30), Dtm::Draw] {
+            assert_eq!(Dtm::from_code(dtm.to_code()), Some(dtm));
+            assert_eq!(Dtm::from_score(dtm.score()), dtm);
+        }
+        assert_eq!(Dtm::Loss(0).after_move(), Dtm::Win(1));
+        assert_eq!(parent_score(Dtm::Loss(0).score()), Dtm::Win(1).score());
+        assert_eq!(parent_score(Dtm::Win(1).score()), Dtm::Loss(2).score());
+    }
+
+    #[test]
+    fn test_too_many_pieces() {
+        let mut tables = DtmTables::new();
+        assert!(tables.generate(&"KQRvKR".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_kqk() {
+        let tables = tables_for("KQvK");
+        let probe = |fen: &str| tables.probe(&Board::from_str(fen).unwrap());
+
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe("k5Q1/8/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        assert_eq!(probe("k7/8/1Q6/8/8/8/8/7K b - - 0 1"), Some(Dtm::Draw)); // Stalemate
+        assert_eq!(probe("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        assert_eq!(probe("kQ6/8/2K5/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw)); // Takes the queen
+
+        // KQK is mate in at most 10 moves
+        let longest = tables.table(&"KQvK".parse().unwrap()).unwrap().max_win();
+        assert_eq!(longest, Some(19));
+
+        // The best move keeps to the shortest mate
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(
+            tables.best_move(&board),
+            Some((ChessMove::new(Square::G1, Square::G8, None), Dtm::Win(1)))
+        );
+        assert_eq!(tables.probe(&Board::default()), None);
+    }
+
+    #[test]
+    fn test_krk() {
+        // KRK is mate in at most 16 moves
+        let tables = tables_for("KRvK");
+        let longest = tables.table(&"KRvK".parse().unwrap()).unwrap().max_win();
+        assert_eq!(longest, Some(31));
+    }
+
+    #[test]
+    fn test_matches_kpk_bitbase() {
+        // Every legal KPK position agrees with the bitbase
+        let tables = tables_for("KPvK");
+        let table = tables.table(&"KPvK".parse().unwrap()).unwrap();
+        let mut squares = [0; 3];
+        for (idx, code) in table.codes.iter().enumerate() {
+            let stm = decode(idx, &mut squares);
+            let Some(dtm) = Dtm::from_code(*code) else {
+                continue;
+            };
+            let mut builder = BoardBuilder::new();
+            builder
+                .piece(ALL_SQUARES[squares[0]], Piece::King, Color::White)
+                .piece(ALL_SQUARES[squares[1]], Piece::King, Color::Black)
+                .piece(ALL_SQUARES[squares[2]], Piece::Pawn, Color::White)
+                .side_to_move(stm);
+            let board = Board::try_from(&builder).unwrap();
+            let white_wins = match stm {
+                Color::White => matches!(dtm, Dtm::Win(_)),
+                Color::Black => matches!(dtm, Dtm::Loss(_)),
+            };
+            assert_eq!(crate::kpk::probe(&board), Some((Color::White, white_wins)), "{}", board);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let tables = tables_for("KQvK");
+        let dir = std::env::temp_dir().join("shallow_red_test_dtm");
+        tables.save(&dir).unwrap();
+        let loaded = DtmTables::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.signatures(), vec!["KQvK"]);
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        assert_eq!(loaded.probe(&board), Some(Dtm::Win(1)));
+    }
+}
@@ -1,23 +1,29 @@
 use crate::consts::{self, USE_CACHE};
 
-use crate::extensions::should_extend;
+use crate::evaluator::with_move;
+use crate::extensions::{self, should_extend, ExtensionContext};
 use crate::managers::cache_manager::{BoundType, CacheData, CacheEntry};
 use crate::managers::stats_manager::Statistics;
 // use crate::ordering::RetreivedCacheData;
-use crate::quiescent::quiescent_search;
-use crate::utils::common::min;
-use crate::utils::search_interface::{SearchOutput, SearchParameters};
+use crate::quiescent::{fetch_sorted_captures, quiescent_search};
+use crate::pruning;
+use crate::utils::common::{abs_eval_from_color, min, Eval};
+use crate::utils::search_interface::{InternalIterativeMode, SearchOutput, SearchParameters};
 use crate::{
     ordering,
     utils::common::{flip_colour, max},
 };
-use chess::{Board, BoardStatus, ChessMove, Color, MoveGen};
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Square, EMPTY};
 
+#[allow(clippy::result_unit_err)]
 pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<SearchOutput, ()> {
     let alpha_orig = params.alpha;
+    let config = params.search_config;
 
     let mut cache_pv_move: Option<ChessMove> = None;
     let mut cache_cutoff_move: Option<ChessMove> = None;
+    let mut tt_entry: Option<CacheData> = None; // Raw TT entry for this node, used by the singular search
+    let tt_key = tt_key(&board, params.excluded_move);
 
     // Internal stats data for this node and children
     let mut node_stats = Statistics::default();
@@ -25,65 +31,65 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
     let mut max_move = ChessMove::default();
 
     // ===================== Check TT for this node ===================== //
-    let mut skip_tt_push = false; // Flag for whether or not we should avoid pushing to the TT
+    let mut _skip_tt_push = false; // Flag for whether or not we should avoid pushing to the TT
                                   // Check if this move is in our cache (with a flag to disable cache lookup)
-    match USE_CACHE && params.depth > 0 {
-        true => {
-            if let Some(cache_result) = params
-                .cache
-                .cache_ref
-                .try_read_for(crate::consts::TT_MAXTIME_LOOKUP)
-            {
-                if let Some(cache_result) = cache_result.cache_manager_get(board.get_hash()) {
-                    // Check if we have a sufficient lookup distance
-                    let cache_lookahead = cache_result.search_depth - cache_result.move_depth;
-                    let current_lookahead = (params.depth_lim + params.extension) - params.depth;
-                    let evaluation_valid = cache_lookahead >= current_lookahead;
-
-                    skip_tt_push = current_lookahead < cache_lookahead; // Don't replace in TT if our lookahead is worse
-
-                    if evaluation_valid {
-                        // If the cache is valid manage the cache
-                        node_stats.caches_used += 1;
-                        match cache_result.flag {
-                            BoundType::Exact => {
-                                return Ok(SearchOutput {
-                                    node_eval: cache_result.evaluation,
-                                    best_move: Default::default(),
-                                    node_stats,
-                                });
-                                // return value directly
-                            }
-                            BoundType::UpperBound => {
-                                params.alpha = max(
-                                    params.alpha,
-                                    cache_result.evaluation.for_colour(board.side_to_move()),
-                                )
-                            }
-                            BoundType::LowerBound => {
-                                params.beta = min(
-                                    params.beta,
-                                    cache_result.evaluation.for_colour(board.side_to_move()),
-                                )
-                            }
-                        };
-                        if params.alpha >= params.beta {
+    if USE_CACHE && params.depth > 0 {
+        if let Some(cache_result) = params
+            .cache
+            .cache_ref
+            .try_read_for(crate::consts::TT_MAXTIME_LOOKUP)
+        {
+            if let Some(mut cache_result) = cache_result.cache_manager_get(tt_key) {
+                cache_result.evaluation = mate_from_tt(cache_result.evaluation, params.depth);
+                tt_entry = Some(cache_result);
+
+                // Check if we have a sufficient lookup distance
+                let cache_lookahead = cache_result.search_depth - cache_result.move_depth;
+                let current_lookahead = params.horizon().saturating_sub(params.depth);
+                let evaluation_valid = cache_lookahead >= current_lookahead;
+
+                _skip_tt_push = current_lookahead < cache_lookahead; // Don't replace in TT if our lookahead is worse
+
+                if evaluation_valid {
+                    // If the cache is valid manage the cache
+                    node_stats.caches_used += 1;
+                    match cache_result.flag {
+                        BoundType::Exact => {
                             return Ok(SearchOutput {
                                 node_eval: cache_result.evaluation,
                                 best_move: Default::default(),
                                 node_stats,
                             });
+                            // return value directly
+                        }
+                        BoundType::LowerBound => {
+                            params.alpha = max(
+                                params.alpha,
+                                cache_result.evaluation.for_colour(board.side_to_move()),
+                            )
+                        }
+                        BoundType::UpperBound => {
+                            params.beta = min(
+                                params.beta,
+                                cache_result.evaluation.for_colour(board.side_to_move()),
+                            )
                         }
-                        // Populate the PV and cutoff moves
-                        // Look at these first even if the cache isn't technically valid
-                        cache_pv_move = cache_result.pv_move;
-                        cache_cutoff_move = cache_result.cutoff_move;
+                    };
+                    if params.alpha >= params.beta {
+                        return Ok(SearchOutput {
+                            node_eval: cache_result.evaluation,
+                            best_move: Default::default(),
+                            node_stats,
+                        });
                     }
+                    // Populate the PV and cutoff moves
+                    // Look at these first even if the cache isn't technically valid
+                    cache_pv_move = cache_result.pv_move;
+                    cache_cutoff_move = cache_result.cutoff_move;
                 }
             }
         }
-        false => {}
-    };
+    }
     // ===================== Done TT for this node  ===================== //
 
     // Initial PV
@@ -102,20 +108,58 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
     }
     // ===================== Done check time        ===================== //
 
+    // ===================== Tablebase probe        ===================== //
+    // Small enough endgames are looked up rather than searched (the root is handled by the engine)
+    if params.depth > 0 && params.excluded_move.is_none() {
+        let tb_eval = params
+            .dtm_tables
+            .and_then(|dtm_tables| dtm_tables.probe_eval(&board, params.depth))
+            .or_else(|| {
+                params
+                    .tablebase
+                    .and_then(|tablebase| tablebase.probe_wdl(&board, params.depth))
+            });
+        if let Some(tb_eval) = tb_eval {
+            node_stats.tb_hits += 1;
+            return Ok(SearchOutput {
+                node_eval: tb_eval,
+                best_move: Default::default(),
+                node_stats,
+            });
+        }
+    }
+    // ===================== Done tablebase probe   ===================== //
+
+    // Mates are scored by their distance from the root so the quickest mate is preferred
+    if params.depth > 0 && board.status() == BoardStatus::Checkmate {
+        return Ok(SearchOutput {
+            node_eval: abs_eval_from_color(i16::MIN + 1 + params.depth as i16, board.side_to_move()),
+            best_move: Default::default(),
+            node_stats,
+        });
+    }
+
     // Run Extension if needed
-    if (params.depth >= (params.depth_lim + params.extension))
+    if (params.depth >= params.horizon())
         || (board.status() == BoardStatus::Checkmate)
         || (board.status() == BoardStatus::Stalemate)
     {
-        if should_extend(&board, params.extension) {
+        if let Some(extension) = should_extend(&board, params.extension, config) {
             // We've determined that the search should be extended
-            params.extension += 1;
+            params.extension += extension;
         } else {
             // We're not in check so finish the search
             let mut _blank_move: ChessMove;
 
             return Ok(SearchOutput {
-                node_eval: quiescent_search(&board, params.alpha, params.beta, 0),
+                node_eval: quiescent_search(
+                    &board,
+                    params.alpha,
+                    params.beta,
+                    0,
+                    params.evaluator,
+                    config,
+                ),
                 best_move: Default::default(),
                 node_stats,
             });
@@ -130,13 +174,172 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
         }
     }
 
+    // ===================== Forward pruning        ===================== //
+    // Near the horizon, use the static evaluation to skip nodes that are hopeless or already won
+    let remaining_depth = params.horizon().saturating_sub(params.depth);
+    let mut futile_node = false;
+    if params.depth > 0 && *board.checkers() == EMPTY {
+        let static_eval = params.evaluator.evaluate(&board).for_colour(board.side_to_move());
+
+        // Reverse futility (static null move) pruning
+        if pruning::reverse_futility_prune(static_eval, params.beta, remaining_depth, config) {
+            return Ok(SearchOutput {
+                node_eval: abs_eval_from_color(static_eval, board.side_to_move()),
+                best_move: Default::default(),
+                node_stats,
+            });
+        }
+
+        // Razoring, drop straight into the quiescent search and trust it if it stays below alpha
+        if pruning::should_razor(static_eval, params.alpha, remaining_depth, config) {
+            let razor_eval =
+                quiescent_search(&board, params.alpha, params.beta, 0, params.evaluator, config);
+            if razor_eval.for_colour(board.side_to_move()) <= params.alpha {
+                return Ok(SearchOutput {
+                    node_eval: razor_eval,
+                    best_move: Default::default(),
+                    node_stats,
+                });
+            }
+        }
+
+        // Futility pruning, quiet moves at this node are skipped in the move loop
+        futile_node = pruning::futility_prune(static_eval, params.alpha, remaining_depth, config);
+    }
+    // ===================== Done forward pruning   ===================== //
+
+    // ===================== ProbCut                ===================== //
+    // If a good capture beats a raised beta at reduced depth it will very likely fail high at full depth
+    // Only tried at null window nodes, a wrong cut on the principal variation costs too much
+    if params.depth > 0 && params.excluded_move.is_none() && *board.checkers() == EMPTY {
+        let probcut_beta =
+            pruning::probcut_beta(params.alpha, params.beta, remaining_depth, config);
+        if let Some(probcut_beta) = probcut_beta {
+            let material = params.evaluator.material();
+            for capture in fetch_sorted_captures(&board, material)
+                .filter(|mve| ordering::is_good_capture(&board, *mve, material))
+            {
+                let child_board = board.make_move_new(capture);
+
+                // Cheap check with the quiescent search before committing to the reduced search
+                let q_eval = with_move(params.evaluator, &board, capture, || {
+                    quiescent_search(
+                        &child_board,
+                        -probcut_beta,
+                        -probcut_beta + 1,
+                        0,
+                        params.evaluator,
+                        config,
+                    )
+                });
+                if q_eval.for_colour(board.side_to_move()) < probcut_beta {
+                    continue;
+                }
+
+                if let Some(child_params) = reduced_child_params(
+                    &params,
+                    &board,
+                    capture,
+                    config.probcut_reduction,
+                    probcut_beta,
+                ) {
+                    let probcut_result = with_move(params.evaluator, &board, capture, || {
+                        find_best_move(child_board, child_params)
+                    })?;
+                    node_stats += probcut_result.node_stats;
+                    if probcut_result.node_eval.for_colour(board.side_to_move()) >= probcut_beta {
+                        return Ok(SearchOutput {
+                            node_eval: probcut_result.node_eval,
+                            best_move: capture,
+                            node_stats,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    // ===================== Done ProbCut           ===================== //
+
+    // ===================== Internal iterative deepening ================ //
+    // Without a hash move our ordering is only MVV-LVA, so find a good first move or search shallower
+    if params.depth > 0
+        && params.excluded_move.is_none()
+        && cache_pv_move.is_none()
+        && remaining_depth >= config.iid_min_depth
+    {
+        match config.internal_iterative_mode {
+            InternalIterativeMode::Deepening
+                if config.iid_reduction > 0 && params.depth_lim > config.iid_reduction =>
+            {
+                let iid_result = find_best_move(
+                    board,
+                    SearchParameters {
+                        depth_lim: params.depth_lim - config.iid_reduction,
+                        cache: params.cache.clone(),
+                        first_search_move: None,
+                        ..params
+                    },
+                )?;
+                node_stats += iid_result.node_stats;
+                if iid_result.best_move != ChessMove::default() {
+                    cache_pv_move = Some(iid_result.best_move);
+                }
+            }
+            InternalIterativeMode::Reduction => params.depth_lim -= 1,
+            _ => {}
+        }
+    }
+    // ===================== Done internal iterative deepening ============ //
+
+    // ===================== Singular extension search ==================== //
+    // If every move other than the TT move fails low against a margin below the TT score,
+    // the TT move is singular and gets extended
+    let mut singular_move = None;
+    if let (Some(entry), None) = (tt_entry, params.excluded_move) {
+        let tt_eval = entry.evaluation.for_colour(board.side_to_move());
+        let reduction = remaining_depth / 2;
+        if remaining_depth >= config.singular_min_depth
+            && entry.pv_move.is_some()
+            && entry.flag != BoundType::UpperBound
+            && entry.search_depth - entry.move_depth + 3 >= remaining_depth
+            && !pruning::is_mate_score(tt_eval)
+            && params.depth_lim > reduction
+        {
+            let singular_beta = (tt_eval as i32
+                - config.singular_margin as i32 * remaining_depth as i32)
+                .max(i16::MIN as i32 + 2) as i16;
+            let singular_result = find_best_move(
+                board,
+                SearchParameters {
+                    depth_lim: params.depth_lim - reduction,
+                    alpha: singular_beta - 1,
+                    beta: singular_beta,
+                    cache: params.cache.clone(),
+                    first_search_move: None,
+                    excluded_move: entry.pv_move,
+                    ..params
+                },
+            )?;
+            node_stats += singular_result.node_stats;
+            if singular_result.node_eval.for_colour(board.side_to_move()) < singular_beta {
+                singular_move = entry.pv_move;
+            }
+        }
+    }
+    // ===================== Done singular extension search =============== //
+
     // Generate moves
     let child_moves = MoveGen::new_legal(&board);
     // Get length of moves
     let num_moves = child_moves.len();
 
-    let mut sorted_moves =
-        ordering::order_moves(child_moves, board, cache_pv_move, cache_cutoff_move); // sort all the moves
+    let mut sorted_moves = ordering::order_moves(
+        child_moves,
+        board,
+        cache_pv_move,
+        cache_cutoff_move,
+        params.evaluator.material(),
+    ); // sort all the moves
 
     // Initialize with least desirable evaluation
     let mut max_val = match board.side_to_move() {
@@ -145,7 +348,47 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
     };
 
     node_stats.all_nodes += sorted_moves.len() as i32;
+
+    // ===================== Multi-cut              ===================== //
+    // If several of the first moves fail high at reduced depth, assume one of them will at full depth
+    // Like ProbCut, only at null window nodes
+    if params.depth > 0
+        && params.excluded_move.is_none()
+        && *board.checkers() == EMPTY
+        && pruning::should_multi_cut(params.alpha, params.beta, remaining_depth, config)
+    {
+        let mut cutoffs = 0;
+        for mve in sorted_moves.clone().take(config.multi_cut_moves) {
+            if let Some(child_params) =
+                reduced_child_params(&params, &board, mve, config.multi_cut_reduction, params.beta)
+            {
+                let multi_cut_result = with_move(params.evaluator, &board, mve, || {
+                    find_best_move(board.make_move_new(mve), child_params)
+                })?;
+                node_stats += multi_cut_result.node_stats;
+                if multi_cut_result.node_eval.for_colour(board.side_to_move()) >= params.beta {
+                    cutoffs += 1;
+                    if cutoffs >= config.multi_cut_required {
+                        return Ok(SearchOutput {
+                            node_eval: abs_eval_from_color(params.beta, board.side_to_move()),
+                            best_move: mve,
+                            node_stats,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    // ===================== Done multi-cut         ===================== //
+
     let mut cutoff_move = None;
+    let mut moves_searched: u32 = 0;
+
+    let extension_context = ExtensionContext {
+        num_moves,
+        last_capture: params.last_capture,
+        singular_move,
+    };
 
     for mve in &mut sorted_moves {
         let node_evaluation;
@@ -153,21 +396,49 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
 
         let _move_is_cache_move: bool = false; // Flag to check if the move is a cache move or not (avoid rewriting)
 
-        let search_result = find_best_move(
-            board.make_move_new(mve),
-            SearchParameters {
-                depth: params.depth + 1,
-                depth_lim: params.depth_lim,
-                extension: params.extension,
-                alpha: -params.beta,
-                beta: -params.alpha,
-                color: flip_colour(board.side_to_move()),
-                cache: params.cache.clone(),
-                t_start: params.t_start,
-                t_lim: params.t_lim,
-                first_search_move: None,
-            },
-        );
+        // The excluded move is searched by the node that set up the singular search
+        if params.excluded_move == Some(mve) {
+            continue;
+        }
+
+        let child_board = board.make_move_new(mve);
+
+        // Skip quiet moves that cannot raise alpha (always search at least one move)
+        if futile_node
+            && moves_searched > 0
+            && pruning::is_quiet_move(&board, mve)
+            && !pruning::gives_check(&child_board)
+        {
+            continue;
+        }
+        moves_searched += 1;
+
+        let extension =
+            extensions::move_extension(&board, &child_board, mve, &extension_context, config);
+
+        let search_result = with_move(params.evaluator, &board, mve, || {
+            find_best_move(
+                child_board,
+                SearchParameters {
+                    depth: params.depth + 1,
+                    depth_lim: params.depth_lim,
+                    extension: extensions::apply_extension(params.extension, extension, config),
+                    alpha: -params.beta,
+                    beta: -params.alpha,
+                    color: flip_colour(board.side_to_move()),
+                    cache: params.cache.clone(),
+                    t_start: params.t_start,
+                    t_lim: params.t_lim,
+                    first_search_move: None,
+                    last_capture: captured_square(&board, mve),
+                    excluded_move: None,
+                    tablebase: params.tablebase,
+                    dtm_tables: params.dtm_tables,
+                    evaluator: params.evaluator,
+                    search_config: params.search_config,
+                },
+            )
+        });
 
         match search_result {
             Ok(result) => {
@@ -227,14 +498,14 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
     }
 
     let node_entry = CacheEntry {
-        board_hash: board.get_hash(),
+        board_hash: tt_key,
         cachedata: CacheData {
             move_depth: params.depth,
-            search_depth: params.depth_lim + params.extension,
-            evaluation: node_value,
+            search_depth: params.horizon(),
+            evaluation: mate_to_tt(node_value, params.depth),
             flag: node_flag,
             pv_move: Some(max_move),
-            cutoff_move: cutoff_move,
+            cutoff_move,
         },
     };
     let _ = params.cache.cache_tx.send(node_entry);
@@ -246,6 +517,70 @@ pub fn find_best_move(board: Board, mut params: SearchParameters) -> Result<Sear
     })
 }
 
+fn mate_to_tt(eval: Eval, depth: u8) -> Eval {
+    // Mate scores count plies from the root, the TT keeps them relative to the node instead
+    match eval.score {
+        score if score >= consts::MATE_THRESHOLD => Eval { score: score.saturating_add(depth as i16) },
+        score if score <= -consts::MATE_THRESHOLD => Eval { score: score.saturating_sub(depth as i16) },
+        score => Eval { score },
+    }
+}
+
+fn mate_from_tt(eval: Eval, depth: u8) -> Eval {
+    match eval.score {
+        score if score >= consts::MATE_THRESHOLD => Eval { score: score.saturating_sub(depth as i16) },
+        score if score <= -consts::MATE_THRESHOLD => Eval { score: score.saturating_add(depth as i16) },
+        score => Eval { score },
+    }
+}
+
+fn tt_key(board: &Board, excluded_move: Option<ChessMove>) -> u64 {
+    // Searches with an excluded move get their own TT entries so they don't clash with the full search
+    match excluded_move {
+        Some(mve) => {
+            let move_index = (mve.get_source().to_index() * 64 + mve.get_dest().to_index()) as u64 + 1;
+            board.get_hash() ^ move_index.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        }
+        None => board.get_hash(),
+    }
+}
+
+fn reduced_child_params<'a>(
+    params: &SearchParameters<'a>,
+    board: &Board,
+    mve: ChessMove,
+    reduction: u8,
+    beta: i16,
+) -> Option<SearchParameters<'a>> {
+    // Parameters for a reduced depth, zero window search of a child, testing if the move scores at least beta
+    if params.depth_lim <= reduction || params.horizon() < params.depth + 1 + reduction {
+        return None;
+    }
+    Some(SearchParameters {
+        depth: params.depth + 1,
+        depth_lim: params.depth_lim - reduction,
+        extension: params.extension,
+        alpha: -beta,
+        beta: -beta + 1,
+        color: flip_colour(board.side_to_move()),
+        cache: params.cache.clone(),
+        t_start: params.t_start,
+        t_lim: params.t_lim,
+        first_search_move: None,
+        last_capture: captured_square(board, mve),
+        excluded_move: None,
+        tablebase: params.tablebase,
+        dtm_tables: params.dtm_tables,
+        evaluator: params.evaluator,
+        search_config: params.search_config,
+    })
+}
+
+fn captured_square(board: &Board, mve: ChessMove) -> Option<Square> {
+    // Square a move captures on, if it is a capture
+    board.piece_on(mve.get_dest()).map(|_| mve.get_dest())
+}
+
 #[cfg(test)]
 mod tests {
     // use::super*;
@@ -258,17 +593,21 @@ mod tests {
     use parking_lot::RwLock;
     use std::sync::Arc;
 
-    use chess::{Board, ChessMove, Square};
+    use chess::{Board, CacheTable, ChessMove, Square};
 
     use crate::{
+        evaluator::HandcraftedEvaluator,
+        search_config::SearchConfig,
         managers::{
-            cache_manager::{Cache, CacheInputGrouping},
+            cache_manager::{BoundType, Cache, CacheData, CacheInputGrouping},
             stats_manager::Statistics,
         },
-        utils::search_interface::SearchParameters,
+        utils::search_interface::{SearchOutput, SearchParameters},
     };
 
-    use super::find_best_move;
+    use super::{find_best_move, mate_from_tt, mate_to_tt};
+    use crate::retrograde::DtmTables;
+    use crate::utils::common::Eval;
 
     #[test]
     #[serial_test::serial]
@@ -283,6 +622,9 @@ mod tests {
             all_nodes: 0,
             searched_nodes: 0,
             caches_used: 0,
+            tb_hits: 0,
+            pawn_hash_hits: 0,
+            pawn_hash_probes: 0,
         };
 
         let t_start = SystemTime::now(); // Initial time before running
@@ -308,6 +650,12 @@ mod tests {
                 t_start: &t_start,
                 t_lim: Duration::from_secs(7),
                 first_search_move: Some(mve),
+                last_capture: None,
+                excluded_move: None,
+                tablebase: None,
+                dtm_tables: None,
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
             },
         )
         .unwrap();
@@ -331,6 +679,9 @@ mod tests {
             all_nodes: 0,
             searched_nodes: 0,
             caches_used: 0,
+            tb_hits: 0,
+            pawn_hash_hits: 0,
+            pawn_hash_probes: 0,
         };
 
         let t_start = SystemTime::now(); // Initial time before running
@@ -356,6 +707,12 @@ mod tests {
                 t_start: &t_start,
                 t_lim: Duration::from_secs(7),
                 first_search_move: Some(mve),
+                last_capture: None,
+                excluded_move: None,
+                tablebase: None,
+                dtm_tables: None,
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
             },
         )
         .unwrap();
@@ -365,4 +722,169 @@ mod tests {
             ChessMove::new(Square::C3, Square::E4, None)
         )
     }
+
+    #[test]
+    fn test_mate_scores_in_tt() {
+        // A mate three plies below a node at depth 2 is stored as mate in three, and read back at
+        // depth 4 as a mate seven plies from the root
+        let mate = Eval { score: i16::MAX - 5 };
+        assert_eq!(mate_to_tt(mate, 2), Eval { score: i16::MAX - 3 });
+        assert_eq!(mate_from_tt(mate_to_tt(mate, 2), 4), Eval { score: i16::MAX - 7 });
+        let mated = Eval { score: -mate.score };
+        assert_eq!(mate_from_tt(mate_to_tt(mated, 2), 2), mated);
+        assert_eq!(mate_to_tt(Eval { score: 900 }, 5), Eval { score: 900 });
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_prefers_quickest_mate() {
+        // Qe1+ mates a couple of moves later, Qg2 mates straight away
+        let board = Board::from_str("8/8/8/8/8/5KQ1/8/7k w - - 0 1").unwrap();
+        let t_start = SystemTime::now();
+        let (cache_tx, _cache_rx) = Cache::generate_channel();
+        let search_res = find_best_move(
+            board,
+            SearchParameters {
+                depth: 0,
+                depth_lim: 4,
+                extension: 0,
+                alpha: i16::MIN + 1,
+                beta: i16::MAX - 1,
+                color: board.side_to_move(),
+                cache: CacheInputGrouping {
+                    cache_ref: Arc::new(RwLock::new(Cache::default())),
+                    cache_tx,
+                },
+                t_start: &t_start,
+                t_lim: Duration::from_secs(60),
+                first_search_move: None,
+                last_capture: None,
+                excluded_move: None,
+                tablebase: None,
+                dtm_tables: None,
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
+            },
+        )
+        .unwrap();
+        assert_eq!(search_res.best_move, ChessMove::new(Square::G3, Square::G2, None));
+        assert_eq!(search_res.node_eval.score, i16::MAX - 1);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_dtm_mate_matches_search() {
+        // Qg8 mates, the tables have to score the mate exactly like the search finds it
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let mut tables = DtmTables::new();
+        tables.generate(&"KQvK".parse().unwrap()).unwrap();
+        let search = |dtm_tables: Option<&DtmTables>| {
+            let t_start = SystemTime::now();
+            let (cache_tx, _cache_rx) = Cache::generate_channel();
+            find_best_move(
+                board,
+                SearchParameters {
+                    depth: 0,
+                    depth_lim: 2,
+                    extension: 0,
+                    alpha: i16::MIN + 1,
+                    beta: i16::MAX - 1,
+                    color: board.side_to_move(),
+                    cache: CacheInputGrouping {
+                        cache_ref: Arc::new(RwLock::new(Cache::default())),
+                        cache_tx,
+                    },
+                    t_start: &t_start,
+                    t_lim: Duration::from_secs(60),
+                    first_search_move: None,
+                    last_capture: None,
+                    excluded_move: None,
+                    tablebase: None,
+                    dtm_tables,
+                    evaluator: &HandcraftedEvaluator::default(),
+                    search_config: &SearchConfig::default(),
+                },
+            )
+            .unwrap()
+        };
+
+        let searched = search(None);
+        let probed = search(Some(&tables));
+        assert_eq!(searched.node_eval.score, i16::MAX - 1);
+        assert_eq!(probed.node_eval, searched.node_eval);
+        assert_eq!(probed.best_move, ChessMove::new(Square::G1, Square::G8, None));
+        assert_eq!(tables.probe_eval(&board, 0), Some(searched.node_eval));
+    }
+
+    fn search_with_tt_entry(flag: BoundType, score: i16) -> SearchOutput {
+        // Search the starting position one ply below the root with a (-100, 100) window, with a
+        // deep enough TT entry for it already stored
+        let board = Board::default();
+        let mut cache = Cache {
+            cache: CacheTable::new(
+                1024,
+                CacheData {
+                    move_depth: 0,
+                    search_depth: 0,
+                    evaluation: Eval { score: 0 },
+                    flag: BoundType::Exact,
+                    pv_move: None,
+                    cutoff_move: None,
+                },
+            ),
+        };
+        cache.cache.add(
+            board.get_hash(),
+            CacheData {
+                move_depth: 1,
+                search_depth: 10,
+                evaluation: Eval { score },
+                flag,
+                pv_move: None,
+                cutoff_move: None,
+            },
+        );
+        let t_start = SystemTime::now();
+        let (cache_tx, _cache_rx) = Cache::generate_channel();
+        find_best_move(
+            board,
+            SearchParameters {
+                depth: 1,
+                depth_lim: 3,
+                extension: 0,
+                alpha: -100,
+                beta: 100,
+                color: board.side_to_move(),
+                cache: CacheInputGrouping {
+                    cache_ref: Arc::new(RwLock::new(cache)),
+                    cache_tx,
+                },
+                t_start: &t_start,
+                t_lim: Duration::from_secs(60),
+                first_search_move: None,
+                last_capture: None,
+                excluded_move: None,
+                tablebase: None,
+                dtm_tables: None,
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tt_bounds_tighten_window() {
+        // A lower bound at or above beta and an upper bound at or below alpha cut off straight away
+        let fail_high = search_with_tt_entry(BoundType::LowerBound, 500);
+        assert_eq!(fail_high.node_stats.searched_nodes, 1);
+        assert_eq!(fail_high.node_eval, Eval { score: 500 });
+        let fail_low = search_with_tt_entry(BoundType::UpperBound, -500);
+        assert_eq!(fail_low.node_stats.searched_nodes, 1);
+        assert_eq!(fail_low.node_eval, Eval { score: -500 });
+
+        // Bounds on the far side of the window say nothing about it, so the node is searched
+        assert!(search_with_tt_entry(BoundType::LowerBound, -500).node_stats.searched_nodes > 1);
+        assert!(search_with_tt_entry(BoundType::UpperBound, 500).node_stats.searched_nodes > 1);
+    }
 }
@@ -0,0 +1,179 @@
+// Search limits, pruning margins, reductions and extensions, so they can be changed without
+// recompiling. The defaults are the constants in consts.rs, a file only has to list the values it
+// changes
+
+use std::io;
+use std::path::Path;
+
+use crate::consts;
+use crate::utils::config_file;
+use crate::utils::search_interface::InternalIterativeMode;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub extension_limit: u8, // Whole plies a path can be extended by
+    pub quiescent_limit: u8, // Captures deep the quiescent search goes
+
+    // Extensions, in fractions of a ply (see consts::ONE_PLY)
+    pub check_extension: u8,
+    pub single_reply_extension: u8,
+    pub recapture_extension: u8,
+    pub pawn_seventh_extension: u8,
+    pub singular_extension: u8,
+    pub singular_min_depth: u8,
+    pub singular_margin: i16, // Per ply of remaining depth
+
+    // Forward pruning, margins are indexed by remaining depth
+    pub use_futility_pruning: bool,
+    pub futility_margins: [i16; 3],
+    pub use_reverse_futility_pruning: bool,
+    pub reverse_futility_depth: u8,
+    pub reverse_futility_margin: i16, // Per ply of remaining depth
+    pub use_razoring: bool,
+    pub razor_margins: [i16; 3],
+
+    pub internal_iterative_mode: InternalIterativeMode,
+    pub iid_min_depth: u8,
+    pub iid_reduction: u8,
+
+    pub use_probcut: bool,
+    pub probcut_min_depth: u8,
+    pub probcut_reduction: u8,
+    pub probcut_margin: i16,
+
+    pub use_multi_cut: bool,
+    pub multi_cut_min_depth: u8,
+    pub multi_cut_reduction: u8,
+    pub multi_cut_moves: usize,
+    pub multi_cut_required: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            extension_limit: consts::EXTENSION_LIM,
+            quiescent_limit: consts::QUIESENT_LIM,
+            check_extension: consts::CHECK_EXTENSION,
+            single_reply_extension: consts::SINGLE_REPLY_EXTENSION,
+            recapture_extension: consts::RECAPTURE_EXTENSION,
+            pawn_seventh_extension: consts::PAWN_SEVENTH_EXTENSION,
+            singular_extension: consts::SINGULAR_EXTENSION,
+            singular_min_depth: consts::SINGULAR_MIN_DEPTH,
+            singular_margin: consts::SINGULAR_MARGIN,
+            use_futility_pruning: consts::USE_FUTILITY_PRUNING,
+            futility_margins: consts::FUTILITY_MARGINS,
+            use_reverse_futility_pruning: consts::USE_REVERSE_FUTILITY_PRUNING,
+            reverse_futility_depth: consts::REVERSE_FUTILITY_DEPTH,
+            reverse_futility_margin: consts::REVERSE_FUTILITY_MARGIN,
+            use_razoring: consts::USE_RAZORING,
+            razor_margins: consts::RAZOR_MARGINS,
+            internal_iterative_mode: consts::INTERNAL_ITERATIVE_MODE,
+            iid_min_depth: consts::IID_MIN_DEPTH,
+            iid_reduction: consts::IID_REDUCTION,
+            use_probcut: consts::USE_PROBCUT,
+            probcut_min_depth: consts::PROBCUT_MIN_DEPTH,
+            probcut_reduction: consts::PROBCUT_REDUCTION,
+            probcut_margin: consts::PROBCUT_MARGIN,
+            use_multi_cut: consts::USE_MULTI_CUT,
+            multi_cut_min_depth: consts::MULTI_CUT_MIN_DEPTH,
+            multi_cut_reduction: consts::MULTI_CUT_REDUCTION,
+            multi_cut_moves: consts::MULTI_CUT_MOVES,
+            multi_cut_required: consts::MULTI_CUT_REQUIRED,
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        // TOML if the file says so, JSON otherwise
+        let config: SearchConfig = config_file::load(path)?;
+        config
+            .validate()
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        // Values the search can't work with, rather than ones that are just weak
+        let reductions = [
+            ("iid_reduction", self.iid_reduction),
+            ("probcut_reduction", self.probcut_reduction),
+            ("multi_cut_reduction", self.multi_cut_reduction),
+        ];
+        if let Some((name, _)) = reductions.iter().find(|(_, reduction)| *reduction == 0) {
+            return Err(format!("{} must be at least 1", name));
+        }
+        if self.extension_limit > u8::MAX / consts::ONE_PLY {
+            return Err(format!(
+                "extension_limit must be at most {}",
+                u8::MAX / consts::ONE_PLY
+            ));
+        }
+        if self.multi_cut_required > self.multi_cut_moves {
+            return Err("multi_cut_required must not exceed multi_cut_moves".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        config_file::save(self, path)
+    }
+
+    pub(crate) fn extension_budget(&self) -> u8 {
+        // Total (fractional) extension allowed along one path
+        self.extension_limit.saturating_mul(consts::ONE_PLY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_file() {
+        let path = std::env::temp_dir().join(format!("search_config_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "probcut_margin = 150\ninternal_iterative_mode = \"Reduction\"\n",
+        )
+        .unwrap();
+        let config = SearchConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.probcut_margin, 150);
+        assert_eq!(
+            config.internal_iterative_mode,
+            InternalIterativeMode::Reduction
+        );
+        assert_eq!(config.futility_margins, consts::FUTILITY_MARGINS);
+        assert_eq!(
+            config.extension_budget(),
+            consts::EXTENSION_LIM * consts::ONE_PLY
+        );
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let path =
+            std::env::temp_dir().join(format!("search_config_bad_{}.toml", std::process::id()));
+        for contents in [
+            "iid_reduction = 0\n",
+            "multi_cut_reduction = 0\n",
+            "extension_limit = 200\n",
+            "multi_cut_moves = 2\nmulti_cut_required = 3\n",
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            let result = SearchConfig::load(&path);
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", contents);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        // Configs built in code aren't validated, the budget still mustn't overflow
+        let config = SearchConfig {
+            extension_limit: u8::MAX,
+            ..Default::default()
+        };
+        assert_eq!(config.extension_budget(), u8::MAX);
+    }
+}
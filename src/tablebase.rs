@@ -0,0 +1,139 @@
+// Syzygy endgame tablebase probing
+// The prober works on shakmaty positions, so boards are converted through their FEN
+
+use std::{io, path::Path, str::FromStr};
+
+use chess::{Board, ChessMove, Color};
+use shakmaty::{fen::Fen, CastlingMode, Chess};
+use shakmaty_syzygy::{Tablebase, Wdl};
+
+use crate::consts;
+use crate::utils::common::{abs_eval_from_color, Eval};
+
+pub struct SyzygyTablebase {
+    tables: Tablebase<Chess>,
+}
+
+impl SyzygyTablebase {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        // Loads every table in the directory, erroring if there are none
+        let mut tables = Tablebase::new();
+        if tables.add_directory(dir)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no Syzygy tables found in {}", dir.display()),
+            ));
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    pub fn covers(&self, board: &Board) -> bool {
+        // Syzygy tables have no positions with castling rights
+        board.combined().popcnt() as usize <= self.max_pieces()
+            && board.castle_rights(Color::White) == chess::CastleRights::NoRights
+            && board.castle_rights(Color::Black) == chess::CastleRights::NoRights
+    }
+
+    pub(crate) fn probe_wdl(&self, board: &Board, depth: u8) -> Option<Eval> {
+        // Tablebase score for a node inside the search, None if the position isn't in our tables
+        // We don't track the halfmove clock so the position is assumed to follow a capture or pawn move
+        if !self.covers(board) {
+            return None;
+        }
+        let wdl = self.tables.probe_wdl_after_zeroing(&to_position(board)?).ok()?;
+        Some(abs_eval_from_color(wdl_score(wdl, depth), board.side_to_move()))
+    }
+
+    pub fn root_move(&self, board: &Board) -> Option<(ChessMove, Eval)> {
+        // Move that wins fastest (or loses slowest) while staying safe under the 50 move rule
+        if !self.covers(board) {
+            return None;
+        }
+        let pos = to_position(board)?;
+        let (mve, dtz) = self.tables.best_move(&pos).ok()??;
+        let wdl = Wdl::from_dtz_after_zeroing(dtz);
+        let mve = ChessMove::from_str(&mve.to_uci(CastlingMode::Standard).to_string()).ok()?;
+        Some((mve, abs_eval_from_color(wdl_score(wdl, 0), board.side_to_move())))
+    }
+}
+
+fn to_position(board: &Board) -> Option<Chess> {
+    // The chess crate writes the square of the pawn that can be taken en passant rather than the
+    // square behind it, so fix that field up before parsing
+    let mut fields: Vec<String> = board.to_string().split(' ').map(String::from).collect();
+    if let (Some(ep_square), Some(ep_field)) = (board.en_passant(), fields.get_mut(3)) {
+        *ep_field = ep_square.uforward(board.side_to_move()).to_string();
+    }
+    Fen::from_ascii(fields.join(" ").as_bytes())
+        .ok()?
+        .into_position(CastlingMode::Standard)
+        .ok()
+}
+
+fn wdl_score(wdl: Wdl, depth: u8) -> i16 {
+    // Score relative to the side to move, wins found sooner score higher
+    // Wins and losses that the 50 move rule turns into draws only score a little
+    match wdl {
+        Wdl::Win => consts::TB_WIN_SCORE - depth as i16,
+        Wdl::CursedWin => 1,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -1,
+        Wdl::Loss => -consts::TB_WIN_SCORE + depth as i16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Board;
+
+    use super::*;
+
+    #[test]
+    fn test_to_position() {
+        // Castling rights and en passant survive the conversion
+        let board =
+            Board::from_str("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        let pos = to_position(&board).unwrap();
+        assert_eq!(
+            Fen::from_position(&pos, shakmaty::EnPassantMode::Legal).to_string(),
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 1"
+        );
+    }
+
+    #[test]
+    fn test_covers() {
+        let board = Board::from_str("8/8/8/8/8/2k5/8/K6Q w - - 0 1").unwrap();
+        let castling = Board::from_str("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let tables = SyzygyTablebase {
+            tables: Tablebase::new(),
+        };
+        // No tables are loaded so nothing is covered
+        assert!(!tables.covers(&board));
+        assert!(!tables.covers(&castling));
+        assert_eq!(tables.probe_wdl(&board, 1), None);
+        assert_eq!(tables.root_move(&board), None);
+    }
+
+    #[test]
+    fn test_wdl_score() {
+        assert!(wdl_score(Wdl::Win, 1) > wdl_score(Wdl::Win, 5));
+        assert!(wdl_score(Wdl::Win, 0) < consts::MATE_THRESHOLD);
+        assert_eq!(wdl_score(Wdl::Loss, 3), -wdl_score(Wdl::Win, 3));
+        assert_eq!(wdl_score(Wdl::Draw, 3), 0);
+        assert!(wdl_score(Wdl::CursedWin, 0) < wdl_score(Wdl::Win, 0));
+    }
+
+    #[test]
+    fn test_empty_directory() {
+        let dir = std::env::temp_dir().join("shallow_red_test_empty_syzygy");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(SyzygyTablebase::open(&dir).is_err());
+        assert!(SyzygyTablebase::open(Path::new("this_directory_does_not_exist")).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+// Training data from self-play, for Texel tuning or training a network
+// Games are played from random openings at a fixed node count or depth, and the quiet positions
+// are written out one per line as `<fen> | <score> | <best move> | [<result>]`. The score and the
+// result are from White's point of view, and texel_tune reads the files as they are
+
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use chess::{Board, ChessMove, EMPTY};
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::selfplay::{
+    random_opening, record_game, GameLimits, GameRecord, GameResult, Player, RecordedMove,
+};
+use super::texel::valid_placement;
+use crate::consts::MATE_THRESHOLD;
+use crate::pruning::{gives_check, is_quiet_move};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataEntry {
+    pub board: Board,
+    pub score: i16, // Search score, positive for White
+    pub best_move: ChessMove,
+    pub result: GameResult,
+}
+
+impl DataEntry {
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} | {} | {} | [{:.1}]",
+            self.board,
+            self.score,
+            self.best_move,
+            self.result.white_score()
+        )
+    }
+
+    pub fn from_line(line: &str) -> Option<Self> {
+        let fields = line.split('|').map(str::trim).collect::<Vec<&str>>();
+        let [fen, score, best_move, result] = fields.as_slice() else {
+            return None;
+        };
+        let result = match *result {
+            "[1.0]" => GameResult::WhiteWin,
+            "[0.0]" => GameResult::BlackWin,
+            "[0.5]" => GameResult::Draw,
+            _ => return None,
+        };
+        if !valid_placement(fen.split(' ').next()?) {
+            return None;
+        }
+        Some(DataEntry {
+            board: Board::from_str(fen).ok()?,
+            score: score.parse().ok()?,
+            best_move: ChessMove::from_str(best_move).ok()?,
+            result,
+        })
+    }
+}
+
+pub fn is_quiet(recorded: &RecordedMove) -> bool {
+    // Positions whose score doesn't hinge on a tactic, so no checks, captures, promotions or mates
+    let board = &recorded.board;
+    *board.checkers() == EMPTY
+        && is_quiet_move(board, recorded.best_move)
+        && !gives_check(&board.make_move_new(recorded.best_move))
+        && recorded
+            .score
+            .is_some_and(|score| score.abs() < MATE_THRESHOLD)
+}
+
+pub fn quiet_entries(record: &GameRecord) -> Vec<DataEntry> {
+    record
+        .moves
+        .iter()
+        .filter(|recorded| is_quiet(recorded))
+        .filter_map(|recorded| {
+            Some(DataEntry {
+                board: recorded.board,
+                score: recorded.score?,
+                best_move: recorded.best_move,
+                result: record.result,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct DataOptions {
+    pub games: usize,
+    pub opening_plies: usize,
+    pub limits: GameLimits,
+    pub threads: usize,
+    pub seed: u64, // Game n's opening comes from seed + n, so runs can be repeated
+}
+
+impl Default for DataOptions {
+    fn default() -> Self {
+        DataOptions {
+            games: 1000,
+            opening_plies: 8,
+            limits: GameLimits::default(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: 0,
+        }
+    }
+}
+
+pub fn generate(
+    player: &Player,
+    options: &DataOptions,
+    output: &mut impl Write,
+    mut progress: impl FnMut(usize, usize),
+) -> io::Result<usize> {
+    // Games are handed out to the threads as they free up and written in the order they finish.
+    // Returns the number of positions written, progress gets the games and positions so far
+    let next_game = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false); // Set when writing fails, so no more games are started
+    let (entries_tx, entries_rx) = mpsc::channel::<Vec<DataEntry>>();
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let entries_tx = entries_tx.clone();
+            let (next_game, stop) = (&next_game, &stop);
+            scope.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= options.games || stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let mut rng = StdRng::seed_from_u64(options.seed.wrapping_add(game as u64));
+                let opening = random_opening(&mut rng, options.opening_plies);
+                let record = record_game(&opening, player, player, &options.limits);
+                // The receiver is dropped as soon as writing fails
+                if entries_tx.send(quiet_entries(&record)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(entries_tx);
+
+        let written = write_entries(entries_rx, output, &mut progress);
+        if written.is_err() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        written
+    })
+}
+
+fn write_entries(
+    entries_rx: Receiver<Vec<DataEntry>>,
+    output: &mut impl Write,
+    progress: &mut impl FnMut(usize, usize),
+) -> io::Result<usize> {
+    // Takes the receiver so it is dropped when this returns, a write error included
+    let mut positions = 0;
+    for (games, entries) in entries_rx.iter().enumerate() {
+        for entry in &entries {
+            writeln!(output, "{}", entry.to_line())?;
+        }
+        positions += entries.len();
+        progress(games + 1, positions);
+    }
+    output.flush()?;
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuning::texel::parse_position;
+
+    fn recorded(fen: &str, best_move: &str, score: i16) -> RecordedMove {
+        RecordedMove {
+            board: Board::from_str(fen).unwrap(),
+            best_move: ChessMove::from_str(best_move).unwrap(),
+            score: Some(score),
+        }
+    }
+
+    #[test]
+    fn test_entry_line() {
+        let entry = DataEntry {
+            board: Board::from_str(
+                "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            )
+            .unwrap(),
+            score: -35,
+            best_move: ChessMove::from_str("e1g1").unwrap(),
+            result: GameResult::BlackWin,
+        };
+        let line = entry.to_line();
+        // The chess crate doesn't keep the move counters
+        assert_eq!(
+            line,
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 1 | -35 | e1g1 | [0.0]"
+        );
+        assert_eq!(DataEntry::from_line(&line), Some(entry.clone()));
+        assert_eq!(
+            DataEntry::from_line("8/8/4k3/8 w - - | 0 | e2e4 | [0.5]"),
+            None
+        );
+
+        // The tuner reads the same lines
+        let position = parse_position(&line).unwrap();
+        assert_eq!(position.board, entry.board);
+        assert_eq!(position.result, 0.0);
+    }
+
+    #[test]
+    fn test_quiet_filter() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(is_quiet(&recorded(start, "e2e4", 20)));
+        assert!(!is_quiet(&recorded(start, "e2e4", MATE_THRESHOLD + 5)));
+
+        // A capture, a check and a position in check
+        let board = "4k3/8/8/3p4/4P3/8/8/R3K3 w - - 0 1";
+        assert!(!is_quiet(&recorded(board, "e4d5", 100)));
+        assert!(!is_quiet(&recorded(board, "a1a8", 100)));
+        assert!(is_quiet(&recorded(board, "a1a2", 100)));
+        let in_check = "4k3/8/8/8/8/8/8/r3K3 w - - 0 1";
+        assert!(!is_quiet(&recorded(in_check, "e1e2", -400)));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_generate() {
+        let options = DataOptions {
+            games: 3,
+            threads: 2,
+            seed: 11,
+            limits: GameLimits {
+                node_limit: None,
+                depth_limit: Some(2),
+                max_plies: 12,
+                cache_size: 1 << 10,
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut games_seen = 0;
+        let positions = generate(&Player::default(), &options, &mut output, |games, _| {
+            games_seen = games
+        })
+        .unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let entries = text
+            .lines()
+            .map(|line| DataEntry::from_line(line).unwrap())
+            .collect::<Vec<DataEntry>>();
+        assert_eq!(games_seen, 3);
+        assert!(positions > 0);
+        assert_eq!(entries.len(), positions);
+        assert!(entries.iter().all(|entry| *entry.board.checkers() == EMPTY));
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_generate_write_error() {
+        // Far more games than could be played in a test, the first failed write has to stop them
+        let options = DataOptions {
+            games: 1_000_000,
+            threads: 2,
+            limits: GameLimits {
+                node_limit: None,
+                depth_limit: Some(1),
+                max_plies: 12,
+                cache_size: 1 << 10,
+            },
+            ..Default::default()
+        };
+        let written = generate(&Player::default(), &options, &mut FailingWriter, |_, _| {});
+        assert_eq!(written.unwrap_err().to_string(), "disk full");
+    }
+}
@@ -0,0 +1,4 @@
+pub mod datagen;
+pub mod selfplay;
+pub mod spsa;
+pub mod texel;
@@ -0,0 +1,254 @@
+// Games the engine plays against itself, for tuning and generating training data
+// Moves are searched to a node or depth limit rather than a time limit so results don't depend on
+// how busy the machine is, and every player gets its own small transposition table for the game
+
+use std::{sync::Arc, thread, time::Duration};
+
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+use parking_lot::RwLock;
+use rand::{seq::IteratorRandom, Rng};
+
+use crate::endgame::is_insufficient_material;
+use crate::engine::enter_engine;
+use crate::eval_params::EvalParams;
+use crate::managers::cache_manager::{Cache, CacheInputGrouping};
+use crate::search_config::SearchConfig;
+use crate::utils::engine_interface::EngineSettings;
+
+#[derive(Debug, Clone, Default)]
+pub struct Player {
+    pub eval_params: Arc<EvalParams>,
+    pub search_config: Arc<SearchConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+}
+
+impl GameResult {
+    pub fn white_score(self) -> f64 {
+        match self {
+            GameResult::WhiteWin => 1.0,
+            GameResult::BlackWin => 0.0,
+            GameResult::Draw => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameLimits {
+    pub node_limit: Option<u64>, // Per move, the iteration that crosses it is still finished
+    pub depth_limit: Option<u8>, // Per move, a move with neither limit gets an hour
+    pub max_plies: usize,        // Games still going after this many plies are drawn
+    pub cache_size: usize,       // Transposition table entries per player, a power of two
+}
+
+impl Default for GameLimits {
+    fn default() -> Self {
+        GameLimits {
+            node_limit: Some(5000),
+            depth_limit: None,
+            max_plies: 400,
+            cache_size: 1 << 16,
+        }
+    }
+}
+
+fn player_cache(entries: usize) -> CacheInputGrouping {
+    // The server thread stops once the game drops its senders
+    let cache_ref = Arc::new(RwLock::new(Cache::with_size(entries)));
+    let (cache_tx, cache_rx) = Cache::generate_channel();
+    let server_cache = cache_ref.clone();
+    thread::spawn(move || Cache::cache_manager_server(server_cache, cache_rx));
+    CacheInputGrouping {
+        cache_ref,
+        cache_tx,
+    }
+}
+
+pub fn game_over(board: &Board, history: &[u64], halfmove_clock: u32) -> Option<GameResult> {
+    // history holds the hashes since the last pawn move or capture, including the current position
+    match board.status() {
+        BoardStatus::Checkmate => match board.side_to_move() {
+            Color::White => return Some(GameResult::BlackWin),
+            Color::Black => return Some(GameResult::WhiteWin),
+        },
+        BoardStatus::Stalemate => return Some(GameResult::Draw),
+        BoardStatus::Ongoing => {}
+    }
+    let repetitions = history
+        .iter()
+        .filter(|hash| **hash == board.get_hash())
+        .count();
+    if is_insufficient_material(board) || halfmove_clock >= 100 || repetitions >= 3 {
+        return Some(GameResult::Draw);
+    }
+    None
+}
+
+pub fn random_opening(rng: &mut impl Rng, plies: usize) -> Board {
+    // A few random moves from the start position, tried again if the game ends on the way
+    loop {
+        let mut board = Board::default();
+        for _ in 0..plies {
+            match MoveGen::new_legal(&board).choose(rng) {
+                Some(mve) => board = board.make_move_new(mve),
+                None => break,
+            }
+        }
+        if board.status() == BoardStatus::Ongoing && !is_insufficient_material(&board) {
+            return board;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedMove {
+    pub board: Board, // Position the move was played in
+    pub best_move: ChessMove,
+    pub score: Option<i16>, // Search score, positive for White
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub moves: Vec<RecordedMove>,
+    pub result: GameResult,
+}
+
+fn is_zeroing(board: &Board, mve: ChessMove) -> bool {
+    // Pawn moves and captures reset the fifty move count and can't be repeated
+    board.piece_on(mve.get_source()) == Some(Piece::Pawn)
+        || board.piece_on(mve.get_dest()).is_some()
+}
+
+pub fn play_game(
+    opening: &Board,
+    white: &Player,
+    black: &Player,
+    limits: &GameLimits,
+) -> GameResult {
+    record_game(opening, white, black, limits).result
+}
+
+pub fn record_game(
+    opening: &Board,
+    white: &Player,
+    black: &Player,
+    limits: &GameLimits,
+) -> GameRecord {
+    // Plays the game out, keeping every move along with the position and score it came from
+    let caches = [
+        player_cache(limits.cache_size),
+        player_cache(limits.cache_size),
+    ];
+    let mut board = *opening;
+    let mut history = vec![board.get_hash()];
+    let mut halfmove_clock = 0;
+    let mut moves = Vec::new();
+
+    for _ in 0..limits.max_plies {
+        if let Some(result) = game_over(&board, &history, halfmove_clock) {
+            return GameRecord { moves, result };
+        }
+        let (player, cache) = match board.side_to_move() {
+            Color::White => (white, &caches[0]),
+            Color::Black => (black, &caches[1]),
+        };
+        let settings = EngineSettings {
+            cache_settings: Some(cache.clone()),
+            time_limit: Duration::from_secs(3600), // The node or depth limit decides
+            eval_params: player.eval_params.clone(),
+            search_config: player.search_config.clone(),
+            node_limit: limits.node_limit,
+            depth_limit: limits.depth_limit,
+            ..Default::default()
+        };
+        let (mve, eng_return) = enter_engine(board, settings);
+        moves.push(RecordedMove {
+            board,
+            best_move: mve,
+            score: eng_return.and_then(|eng_return| eng_return.engine_score),
+        });
+
+        if is_zeroing(&board, mve) {
+            history.clear();
+            halfmove_clock = 0;
+        } else {
+            halfmove_clock += 1;
+        }
+        board = board.make_move_new(mve);
+        history.push(board.get_hash());
+    }
+    let result = game_over(&board, &history, halfmove_clock).unwrap_or(GameResult::Draw);
+    GameRecord { moves, result }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_game_over() {
+        let mate = Board::from_str("k6R/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game_over(&mate, &[], 0), Some(GameResult::WhiteWin));
+        let stalemate = Board::from_str("k7/8/1QK5/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game_over(&stalemate, &[], 0), Some(GameResult::Draw));
+
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(game_over(&board, &[board.get_hash()], 0), None);
+        assert_eq!(
+            game_over(&board, &[board.get_hash()], 100),
+            Some(GameResult::Draw)
+        );
+        let repeated = [board.get_hash(); 3];
+        assert_eq!(game_over(&board, &repeated, 8), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn test_random_opening() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let opening = random_opening(&mut rng, 8);
+        assert_eq!(opening.side_to_move(), Color::White);
+        assert_ne!(opening, Board::default());
+        assert_eq!(opening.status(), BoardStatus::Ongoing);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_play_game() {
+        // A won ending, the stronger side converts even on a small node budget
+        let board = Board::from_str("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        let limits = GameLimits {
+            node_limit: Some(2000),
+            max_plies: 60,
+            cache_size: 1 << 12,
+            ..Default::default()
+        };
+        let player = Player::default();
+        assert_eq!(
+            play_game(&board, &player, &player, &limits),
+            GameResult::WhiteWin
+        );
+
+        let limits = GameLimits {
+            max_plies: 2,
+            ..limits
+        };
+        let record = record_game(&Board::default(), &player, &player, &limits);
+        assert_eq!(record.result, GameResult::Draw);
+        assert_eq!(record.moves.len(), 2);
+        assert_eq!(record.moves[0].board, Board::default());
+        assert_eq!(
+            record.moves[1].board,
+            Board::default().make_move_new(record.moves[0].best_move)
+        );
+        assert!(record.moves.iter().all(|mve| mve.score.is_some()));
+    }
+}
@@ -0,0 +1,324 @@
+// SPSA tuning of the search settings
+// Every iteration all the tuned values are nudged up or down at random at once, the + and − versions
+// play a few game pairs against each other, and each value moves towards the side that scored
+// better. The step sizes shrink as the iterations go on, following the usual SPSA schedules
+
+use std::{sync::Arc, thread};
+
+use rand::Rng;
+use serde_json::Value;
+
+use super::selfplay::{play_game, random_opening, GameLimits, Player};
+use crate::search_config::SearchConfig;
+
+const ALPHA: f64 = 0.602; // How quickly the learning rate falls off
+const GAMMA: f64 = 0.101; // How quickly the perturbation falls off
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpsaParam {
+    pub name: String, // Field of SearchConfig, array entries as e.g. futility_margins.1
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub c_end: f64, // Perturbation at the last iteration
+    pub r_end: f64, // Learning rate at the last iteration
+}
+
+impl SpsaParam {
+    pub fn new(config: &SearchConfig, name: &str, min: f64, max: f64, c_end: f64) -> Option<Self> {
+        Some(SpsaParam {
+            name: name.to_string(),
+            value: config_value(config, name)?,
+            min,
+            max,
+            c_end,
+            r_end: 0.002,
+        })
+    }
+
+    fn clamp(&self, value: f64) -> f64 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+pub fn default_params(config: &SearchConfig) -> Vec<SpsaParam> {
+    // The margins and extensions, the switches and depth limits are left alone
+    [
+        ("futility_margins.1", 0.0, 1000.0, 20.0),
+        ("futility_margins.2", 0.0, 1500.0, 30.0),
+        ("reverse_futility_margin", 0.0, 500.0, 10.0),
+        ("razor_margins.1", 0.0, 1000.0, 20.0),
+        ("razor_margins.2", 0.0, 1500.0, 30.0),
+        ("probcut_margin", 0.0, 1000.0, 20.0),
+        ("singular_margin", 0.0, 100.0, 4.0),
+        ("check_extension", 0.0, 4.0, 1.0),
+        ("recapture_extension", 0.0, 4.0, 1.0),
+        ("pawn_seventh_extension", 0.0, 4.0, 1.0),
+    ]
+    .into_iter()
+    .filter_map(|(name, min, max, c_end)| SpsaParam::new(config, name, min, max, c_end))
+    .collect()
+}
+
+fn field<'a>(value: &'a mut Value, name: &str) -> Option<&'a mut Value> {
+    name.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(values) => values.get_mut(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+pub fn config_value(config: &SearchConfig, name: &str) -> Option<f64> {
+    let mut value = serde_json::to_value(config).ok()?;
+    field(&mut value, name)?.as_f64()
+}
+
+pub fn with_values(config: &SearchConfig, values: &[(&str, f64)]) -> SearchConfig {
+    // The config with the named fields set, rounded to whole numbers
+    let mut value = serde_json::to_value(config).unwrap();
+    for (name, new_value) in values {
+        if let Some(field) = field(&mut value, name) {
+            *field = Value::from(new_value.round() as i64);
+        }
+    }
+    serde_json::from_value(value).unwrap_or_else(|_| config.clone())
+}
+
+#[derive(Debug, Clone)]
+pub struct SpsaOptions {
+    pub iterations: usize,
+    pub game_pairs: usize, // Per iteration, each opening is played with both colours
+    pub opening_plies: usize,
+    pub limits: GameLimits,
+    pub threads: usize,
+}
+
+impl Default for SpsaOptions {
+    fn default() -> Self {
+        SpsaOptions {
+            iterations: 1000,
+            game_pairs: 8,
+            opening_plies: 8,
+            limits: GameLimits::default(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IterationScore {
+    // From the + side's point of view
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+pub struct Spsa {
+    pub base: SearchConfig, // Everything that isn't tuned
+    pub params: Vec<SpsaParam>,
+    pub options: SpsaOptions,
+    pub iteration: usize,
+}
+
+impl Spsa {
+    pub fn new(base: SearchConfig, params: Vec<SpsaParam>, options: SpsaOptions) -> Self {
+        Spsa {
+            base,
+            params,
+            options,
+            iteration: 0,
+        }
+    }
+
+    pub fn config(&self) -> SearchConfig {
+        // The current values
+        let values = self
+            .params
+            .iter()
+            .map(|param| (param.name.as_str(), param.value))
+            .collect::<Vec<_>>();
+        with_values(&self.base, &values)
+    }
+
+    fn perturbed(&self, signs: &[f64], c: &[f64], direction: f64) -> Player {
+        let values = self
+            .params
+            .iter()
+            .zip(signs.iter().zip(c))
+            .map(|(param, (sign, c))| {
+                (
+                    param.name.as_str(),
+                    param.clamp(param.value + direction * sign * c),
+                )
+            })
+            .collect::<Vec<_>>();
+        Player {
+            search_config: Arc::new(with_values(&self.base, &values)),
+            ..Default::default()
+        }
+    }
+
+    fn play_pairs(&self, plus: &Player, minus: &Player, rng: &mut impl Rng) -> IterationScore {
+        // Game pairs split across threads, + plays white in the first game of each pair
+        let openings = (0..self.options.game_pairs)
+            .map(|_| random_opening(rng, self.options.opening_plies))
+            .collect::<Vec<_>>();
+        let chunk_size = openings.len().div_ceil(self.options.threads.max(1)).max(1);
+        let limits = &self.options.limits;
+        let results = thread::scope(|scope| {
+            let workers = openings
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .flat_map(|opening| {
+                                let plus_white =
+                                    play_game(opening, plus, minus, limits).white_score();
+                                let plus_black =
+                                    1.0 - play_game(opening, minus, plus, limits).white_score();
+                                [plus_white, plus_black]
+                            })
+                            .collect::<Vec<f64>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<f64>>()
+        });
+
+        let mut score = IterationScore::default();
+        for result in results {
+            match result {
+                r if r > 0.5 => score.wins += 1,
+                r if r < 0.5 => score.losses += 1,
+                _ => score.draws += 1,
+            }
+        }
+        score
+    }
+
+    pub fn step(&mut self, rng: &mut impl Rng) -> IterationScore {
+        // One iteration, updates the values and returns how the + side did
+        let iterations = self.options.iterations.max(1) as f64;
+        let k = self.iteration as f64 + 1.0;
+        let big_a = 0.1 * iterations; // Stability constant, keeps the first steps from being huge
+
+        let c = self
+            .params
+            .iter()
+            .map(|param| param.c_end * iterations.powf(GAMMA) / k.powf(GAMMA))
+            .collect::<Vec<f64>>();
+        let a = self
+            .params
+            .iter()
+            .map(|param| {
+                param.r_end * param.c_end.powi(2) * (big_a + iterations).powf(ALPHA)
+                    / (big_a + k).powf(ALPHA)
+            })
+            .collect::<Vec<f64>>();
+        let signs = self
+            .params
+            .iter()
+            .map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 })
+            .collect::<Vec<f64>>();
+
+        let plus = self.perturbed(&signs, &c, 1.0);
+        let minus = self.perturbed(&signs, &c, -1.0);
+        let score = self.play_pairs(&plus, &minus, rng);
+
+        // Move towards whichever side did better, by more the clearer the result
+        let result = score.wins as f64 - score.losses as f64;
+        for (index, param) in self.params.iter_mut().enumerate() {
+            param.value = param.clamp(param.value + a[index] / c[index] * result * signs[index]);
+        }
+        self.iteration += 1;
+        score
+    }
+
+    pub fn csv_header(&self) -> String {
+        let names = self.params.iter().map(|param| param.name.as_str());
+        ["iteration", "wins", "losses", "draws"]
+            .into_iter()
+            .chain(names)
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+
+    pub fn csv_row(&self, score: &IterationScore) -> String {
+        let counts =
+            [self.iteration as u32, score.wins, score.losses, score.draws].map(|n| n.to_string());
+        let values = self
+            .params
+            .iter()
+            .map(|param| format!("{:.2}", param.value));
+        counts
+            .into_iter()
+            .chain(values)
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_config_values() {
+        let config = SearchConfig::default();
+        assert_eq!(
+            config_value(&config, "probcut_margin"),
+            Some(config.probcut_margin as f64)
+        );
+        assert_eq!(
+            config_value(&config, "futility_margins.2"),
+            Some(config.futility_margins[2] as f64)
+        );
+        assert_eq!(config_value(&config, "no_such_field"), None);
+        assert_eq!(config_value(&config, "use_probcut"), None); // Switches aren't numbers
+
+        let changed = with_values(
+            &config,
+            &[("probcut_margin", 181.6), ("razor_margins.1", 250.0)],
+        );
+        assert_eq!(changed.probcut_margin, 182);
+        assert_eq!(changed.razor_margins[1], 250);
+        assert_eq!(changed.futility_margins, config.futility_margins);
+
+        assert_eq!(default_params(&config).len(), 10);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_step() {
+        let config = SearchConfig::default();
+        let params = vec![SpsaParam::new(&config, "check_extension", 0.0, 4.0, 1.0).unwrap()];
+        let options = SpsaOptions {
+            iterations: 10,
+            game_pairs: 1,
+            limits: GameLimits {
+                node_limit: Some(50),
+                max_plies: 6,
+                cache_size: 1 << 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut spsa = Spsa::new(config, params, options);
+        let score = spsa.step(&mut StdRng::seed_from_u64(3));
+
+        assert_eq!(spsa.iteration, 1);
+        assert_eq!(score.wins + score.losses + score.draws, 2);
+        assert!((0.0..=4.0).contains(&spsa.params[0].value));
+        assert_eq!(
+            spsa.csv_header(),
+            "iteration,wins,losses,draws,check_extension"
+        );
+        assert!(spsa.csv_row(&score).starts_with("1,"));
+    }
+}
@@ -0,0 +1,406 @@
+// Texel tuning of the evaluation weights
+// Positions are labelled with the result of the game they came from. The evaluation of each one is
+// squashed into an expected result with a sigmoid, and the weights are nudged one at a time for as
+// long as the mean squared error against the real results keeps going down
+
+use std::{fs, io, path::Path, str::FromStr, thread};
+
+use chess::Board;
+use serde_json::Value;
+
+use crate::consts::QUIESENT_LIM;
+use crate::eval_params::EvalParams;
+use crate::evaluation::evaluate_board_with;
+use crate::quiescent::fetch_sorted_captures;
+
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+    pub board: Board,
+    pub result: f64, // 1 for a white win, 0.5 for a draw and 0 for a black win
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    // Accepts PGN style results and the bracketed [1.0] / [0.5] / [0.0] form
+    let token = token.trim_matches(|c: char| c == '"' || c == ';');
+    match token {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => token
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .parse()
+            .ok()
+            .filter(|result| (0.0..=1.0).contains(result)),
+    }
+}
+
+pub(crate) fn valid_placement(placement: &str) -> bool {
+    // The chess crate panics on a board that doesn't have eight full ranks rather than failing
+    let ranks = placement.split('/').collect::<Vec<&str>>();
+    ranks.len() == 8
+        && ranks.iter().all(|rank| {
+            let squares = rank
+                .chars()
+                .map(|c| c.to_digit(10).unwrap_or(1))
+                .sum::<u32>();
+            squares == 8
+                && rank
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "pnbrqkPNBRQK".contains(c))
+        })
+}
+
+pub fn parse_position(line: &str) -> Option<TuningPosition> {
+    // "<fen> [0.5]", "<fen> 1-0" or EPD with the result as an opcode, `<fen> c9 "1-0";`
+    let tokens = line.split_whitespace().collect::<Vec<&str>>();
+    let result = tokens.iter().rev().find_map(|token| parse_result(token))?;
+    if !valid_placement(tokens.first()?) {
+        return None;
+    }
+
+    // Four fields are enough, the move counters are only read if they are there
+    let mut fen = tokens.iter().take(4).copied().collect::<Vec<&str>>();
+    let counters = tokens.iter().skip(4).take(2);
+    match counters.clone().all(|token| token.parse::<u32>().is_ok()) && counters.len() == 2 {
+        true => fen.extend(counters),
+        false => fen.extend(["0", "1"]),
+    }
+    let board = Board::from_str(&fen.join(" ")).ok()?;
+    Some(TuningPosition { board, result })
+}
+
+fn quiet_search(
+    board: Board,
+    alpha: i16,
+    beta: i16,
+    depth: u8,
+    params: &EvalParams,
+) -> (i16, Board) {
+    // Quiescent search that also hands back the position its score came from
+    let stand_pat = evaluate_board_with(board, params).for_colour(board.side_to_move());
+    if stand_pat >= beta || depth > QUIESENT_LIM {
+        return (stand_pat, board);
+    }
+
+    let mut alpha = alpha.max(stand_pat);
+    let mut best = (stand_pat, board);
+    for capture in fetch_sorted_captures(&board, &params.material) {
+        let (score, leaf) = quiet_search(
+            board.make_move_new(capture),
+            -beta,
+            -alpha,
+            depth + 1,
+            params,
+        );
+        let score = -score;
+        if score > best.0 {
+            best = (score, leaf);
+        }
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+pub fn quiet_position(board: Board, params: &EvalParams) -> Board {
+    // The end of the capture sequence the quiescent search would expect, the position the
+    // evaluation really scores
+    quiet_search(board, i16::MIN + 1, i16::MAX - 1, 0, params).1
+}
+
+pub fn load_positions(path: &Path, params: &EvalParams) -> io::Result<Vec<TuningPosition>> {
+    // One position per line, blank lines and # comments are skipped
+    // Every position is swapped for its quiet position up front so the tuning only has to evaluate
+    let text = fs::read_to_string(path)?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| {
+            let position = parse_position(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: could not read a position and result", number + 1),
+                )
+            })?;
+            Ok(TuningPosition {
+                board: quiet_position(position.board, params),
+                ..position
+            })
+        })
+        .collect()
+}
+
+// The parameters as a flat list, in the order they appear in the serialised EvalParams
+
+fn collect_leaves(value: &Value, path: &str, leaves: &mut Vec<(String, i16)>) {
+    let child = |key: &dyn std::fmt::Display| match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key),
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                collect_leaves(value, &child(key), leaves);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                collect_leaves(value, &child(&index), leaves);
+            }
+        }
+        Value::Number(number) => {
+            leaves.push((path.to_string(), number.as_i64().unwrap_or(0) as i16));
+        }
+        _ => {}
+    }
+}
+
+fn replace_leaves(value: &mut Value, weights: &mut impl Iterator<Item = i16>) {
+    match value {
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| replace_leaves(value, weights)),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| replace_leaves(value, weights)),
+        Value::Number(_) => *value = Value::from(weights.next().unwrap_or(0)),
+        _ => {}
+    }
+}
+
+pub fn weight_names(params: &EvalParams) -> Vec<String> {
+    // Dotted paths, e.g. psqt.knight.mg.27
+    let mut leaves = vec![];
+    collect_leaves(&serde_json::to_value(params).unwrap(), "", &mut leaves);
+    leaves.into_iter().map(|(name, _)| name).collect()
+}
+
+pub fn to_weights(params: &EvalParams) -> Vec<i16> {
+    let mut leaves = vec![];
+    collect_leaves(&serde_json::to_value(params).unwrap(), "", &mut leaves);
+    leaves.into_iter().map(|(_, weight)| weight).collect()
+}
+
+pub fn from_weights(weights: &[i16]) -> EvalParams {
+    let mut value = serde_json::to_value(EvalParams::default()).unwrap();
+    replace_leaves(&mut value, &mut weights.iter().copied());
+    serde_json::from_value(value).unwrap()
+}
+
+// Error
+
+pub fn sigmoid(eval: f64, k: f64) -> f64 {
+    // Expected result for White from a centipawn score
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+pub fn error(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+    // Mean squared error between the results and what the evaluation predicts, split across threads
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = positions.len().div_ceil(threads);
+    let total: f64 = thread::scope(|scope| {
+        let workers = positions
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|position| {
+                            let eval = evaluate_board_with(position.board, params).score as f64;
+                            (position.result - sigmoid(eval, k)).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum()
+    });
+    total / positions.len() as f64
+}
+
+pub fn fit_k(positions: &[TuningPosition], params: &EvalParams) -> f64 {
+    // Scaling constant that best fits the current evaluation to the results, so the tuning
+    // changes the weights rather than how confident the evaluation is
+    let (mut best, mut step) = (1.0, 0.5);
+    for _ in 0..4 {
+        best = (-5..=5)
+            .map(|i| best + step * i as f64)
+            .filter(|k| *k > 0.0)
+            .map(|k| (k, error(positions, params, k)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(best, |(k, _)| k);
+        step /= 10.0;
+    }
+    best
+}
+
+// Tuning
+
+#[derive(Debug, Clone)]
+pub struct TuneOptions {
+    pub k: f64,
+    pub max_passes: usize,
+    pub only: Vec<String>, // Only tune weights whose names start with one of these, all if empty
+}
+
+impl Default for TuneOptions {
+    fn default() -> Self {
+        TuneOptions {
+            k: 1.0,
+            max_passes: 100,
+            only: vec![],
+        }
+    }
+}
+
+pub fn tune(
+    positions: &[TuningPosition],
+    params: &EvalParams,
+    options: &TuneOptions,
+    mut progress: impl FnMut(usize, f64, &EvalParams),
+) -> EvalParams {
+    // Local search, each weight in turn is stepped by one for as long as that lowers the error
+    // Stops after a pass that improves nothing, progress is called after every pass
+    let active = weight_names(params)
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| {
+            options.only.is_empty()
+                || options
+                    .only
+                    .iter()
+                    .any(|only| name.starts_with(only.as_str()))
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+
+    let mut weights = to_weights(params);
+    let mut best_error = error(positions, params, options.k);
+    for pass in 1..=options.max_passes {
+        let mut improved = false;
+        for &index in &active {
+            for delta in [1, -1] {
+                let mut stepped = false;
+                loop {
+                    weights[index] += delta;
+                    let new_error = error(positions, &from_weights(&weights), options.k);
+                    if new_error >= best_error {
+                        weights[index] -= delta;
+                        break;
+                    }
+                    best_error = new_error;
+                    stepped = true;
+                }
+                if stepped {
+                    improved = true;
+                    break; // No point trying the other direction
+                }
+            }
+        }
+        progress(pass, best_error, &from_weights(&weights));
+        if !improved {
+            break;
+        }
+    }
+    from_weights(&weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::common::Score;
+
+    #[test]
+    fn test_parse_position() {
+        let position = parse_position("8/8/4k3/8/8/3K4/3P4/8 w - - 0 1 [1.0]").unwrap();
+        assert_eq!(position.result, 1.0);
+        let position = parse_position(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1/2-1/2\";",
+        )
+        .unwrap();
+        assert_eq!(position.result, 0.5);
+        assert_eq!(
+            position.board,
+            Board::from_str("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap()
+        );
+        assert_eq!(
+            parse_position("8/8/4k3/8/8/3K4/3P4/8 w - - 0-1")
+                .unwrap()
+                .result,
+            0.0
+        );
+
+        assert!(parse_position("8/8/4k3/8/8/3K4/3P4/8 w - - 0 1").is_none()); // No result
+        assert!(parse_position("8/8/4k3/8 w - - [1.0]").is_none());
+        assert!(parse_position("8/8/4k3/8/8/3K4/3P4/8 w - - [1.5]").is_none());
+    }
+
+    #[test]
+    fn test_quiet_position() {
+        // The rook hanging on d5 gets taken before the position is scored
+        let board = Board::from_str("4k3/8/8/3r4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let quiet = quiet_position(board, &EvalParams::default());
+        assert_eq!(
+            quiet,
+            Board::from_str("4k3/8/8/3P4/8/8/8/4K3 b - - 0 1").unwrap()
+        );
+
+        let quiet_already = Board::default();
+        assert_eq!(
+            quiet_position(quiet_already, &EvalParams::default()),
+            quiet_already
+        );
+    }
+
+    #[test]
+    fn test_weights_round_trip() {
+        let mut params = EvalParams::default();
+        params.material.bishop = Score::new(330, 340);
+        params.king_safety.safety_table[50] = 77;
+
+        let weights = to_weights(&params);
+        assert_eq!(weights.len(), weight_names(&params).len());
+        assert_eq!(from_weights(&weights), params);
+
+        let names = weight_names(&params);
+        assert!(names.contains(&"material.bishop.mg".to_string()));
+        assert!(names.contains(&"psqt.knight.eg.63".to_string()));
+    }
+
+    #[test]
+    fn test_tune_lowers_error() {
+        // Positions a knight up that only ever get drawn, the knight should end up worth less
+        let positions = [
+            "4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1",
+            "4k3/pppp4/8/8/8/8/PPPP4/4K1N1 b - - 0 1",
+            "2n1k3/4pppp/8/8/8/8/4PPPP/4K3 w - - 0 1",
+        ]
+        .map(|fen| TuningPosition {
+            board: Board::from_str(fen).unwrap(),
+            result: 0.5,
+        });
+        let params = EvalParams::default();
+        let options = TuneOptions {
+            max_passes: 1,
+            only: vec!["material.knight".to_string()],
+            ..Default::default()
+        };
+        let mut passes = 0;
+        let tuned = tune(&positions, &params, &options, |_, _, _| passes += 1);
+
+        assert_eq!(passes, 1);
+        assert!(error(&positions, &tuned, 1.0) < error(&positions, &params, 1.0));
+        assert!(tuned.material.knight.eg < params.material.knight.eg);
+        assert_eq!(tuned.material.rook, params.material.rook);
+        assert_eq!(tuned.psqt, params.psqt);
+    }
+}
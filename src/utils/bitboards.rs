@@ -0,0 +1,103 @@
+// Bitboard helpers shared by the evaluation terms
+
+use chess::{get_adjacent_files, get_file, BitBoard, Color, Square, EMPTY};
+
+pub(crate) const FILE_A: BitBoard = BitBoard(0x0101_0101_0101_0101);
+pub(crate) const FILE_H: BitBoard = BitBoard(0x8080_8080_8080_8080);
+pub(crate) const LIGHT_SQUARES: BitBoard = BitBoard(0x55AA_55AA_55AA_55AA);
+
+pub(crate) fn contains(bitboard: BitBoard, sq: Square) -> bool {
+    bitboard & BitBoard::from_square(sq) != EMPTY
+}
+
+pub(crate) fn relative_rank(color: Color, sq: Square) -> usize {
+    // Rank counted from the colour's own back rank, so 0 to 7 for both sides
+    match color {
+        Color::White => sq.get_rank().to_index(),
+        Color::Black => 7 - sq.get_rank().to_index(),
+    }
+}
+
+pub(crate) fn forward_ranks(color: Color, sq: Square) -> BitBoard {
+    // Every square on the ranks in front of the square, as seen by the colour
+    let rank = sq.get_rank().to_index();
+    match color {
+        Color::White if rank == 7 => EMPTY,
+        Color::White => BitBoard(!0u64 << (8 * (rank + 1))),
+        Color::Black => BitBoard((1u64 << (8 * rank)) - 1),
+    }
+}
+
+pub(crate) fn forward_file(color: Color, sq: Square) -> BitBoard {
+    forward_ranks(color, sq) & get_file(sq.get_file())
+}
+
+pub(crate) fn pawn_attack_span(color: Color, sq: Square) -> BitBoard {
+    // Squares a pawn on this square could ever attack as it advances
+    forward_ranks(color, sq) & get_adjacent_files(sq.get_file())
+}
+
+pub(crate) fn passed_pawn_span(color: Color, sq: Square) -> BitBoard {
+    // Any enemy pawn in here stops the pawn from being passed
+    forward_file(color, sq) | pawn_attack_span(color, sq)
+}
+
+pub(crate) fn pawn_attacks(color: Color, pawns: BitBoard) -> BitBoard {
+    // Every square attacked by a set of pawns, shifting the whole set at once
+    let west = (pawns & !FILE_A).0;
+    let east = (pawns & !FILE_H).0;
+    match color {
+        Color::White => BitBoard((west << 7) | (east << 9)),
+        Color::Black => BitBoard((west >> 9) | (east >> 7)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::{get_pawn_attacks, Square, ALL_SQUARES};
+
+    use super::*;
+
+    #[test]
+    fn test_light_squares() {
+        assert!(!contains(LIGHT_SQUARES, Square::A1));
+        assert!(contains(LIGHT_SQUARES, Square::H1));
+        assert!(contains(LIGHT_SQUARES, Square::D1));
+        assert!(!contains(LIGHT_SQUARES, Square::H8));
+        assert_eq!(LIGHT_SQUARES.popcnt(), 32);
+    }
+
+    #[test]
+    fn test_relative_rank() {
+        assert_eq!(relative_rank(Color::White, Square::E2), 1);
+        assert_eq!(relative_rank(Color::Black, Square::E7), 1);
+        assert_eq!(relative_rank(Color::Black, Square::A1), 7);
+    }
+
+    #[test]
+    fn test_forward_spans() {
+        assert_eq!(forward_ranks(Color::White, Square::A8), EMPTY);
+        assert_eq!(forward_ranks(Color::Black, Square::H1), EMPTY);
+        assert_eq!(forward_ranks(Color::White, Square::C7).popcnt(), 8);
+        assert_eq!(forward_file(Color::White, Square::E2).popcnt(), 6);
+        assert_eq!(
+            forward_file(Color::Black, Square::D3),
+            BitBoard::from_square(Square::D2) | BitBoard::from_square(Square::D1)
+        );
+        assert_eq!(pawn_attack_span(Color::White, Square::A6).popcnt(), 2);
+        assert_eq!(passed_pawn_span(Color::Black, Square::E7).popcnt(), 18);
+    }
+
+    #[test]
+    fn test_pawn_attacks() {
+        // Shifting a set matches looking each pawn up in the attack tables
+        for color in [Color::White, Color::Black] {
+            for sq in ALL_SQUARES {
+                assert_eq!(
+                    pawn_attacks(color, BitBoard::from_square(sq)),
+                    get_pawn_attacks(sq, color, !EMPTY)
+                );
+            }
+        }
+    }
+}
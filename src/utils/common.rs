@@ -1,12 +1,14 @@
-use std::ops::{AddAssign, Add};
+use std::ops::{AddAssign, Add, Mul, Neg, Sub};
 use chess::Color;
+use crate::gamestate::MAX_PHASE;
 use crate::managers::stats_manager::{Statistics, StatisticsDepth};
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct EngineReturn {
     pub engine_move: String,
     pub engine_search_stats: Option<Statistics>,
-    pub engine_depth_stats: Option<StatisticsDepth>
+    pub engine_depth_stats: Option<StatisticsDepth>,
+    pub engine_score: Option<i16>, // Search score, positive for White. None for book and tablebase moves
 }
 
 pub(crate) fn max<T: PartialOrd>(a: T, b: T) -> T {
@@ -56,6 +58,87 @@ impl AddAssign for Eval {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Score {
+    // A midgame and an endgame value, blended by the game phase once the evaluation is summed
+    pub mg: i16,
+    pub eg: i16,
+}
+
+impl Score {
+    pub(crate) const fn new(mg: i16, eg: i16) -> Score {
+        Score { mg, eg }
+    }
+
+    pub(crate) fn for_colour(&self, colour: Color) -> Score {
+        // Same as Eval::for_colour, applied to both halves
+        match colour {
+            Color::White => *self,
+            Color::Black => -*self,
+        }
+    }
+
+    pub(crate) fn taper(&self, phase: i16) -> Eval {
+        // Phase runs from 0 (bare kings and pawns) to MAX_PHASE (all pieces on the board)
+        let phase = phase.clamp(0, MAX_PHASE) as i32;
+        let blended = (self.mg as i32 * phase + self.eg as i32 * (MAX_PHASE as i32 - phase)) / MAX_PHASE as i32;
+        Eval {
+            score: blended as i16,
+        }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, rhs: Score) -> Self::Output {
+        Score {
+            mg: self.mg + rhs.mg,
+            eg: self.eg + rhs.eg,
+        }
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, rhs: Score) -> Self::Output {
+        Score {
+            mg: self.mg - rhs.mg,
+            eg: self.eg - rhs.eg,
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Self::Output {
+        Score {
+            mg: -self.mg,
+            eg: -self.eg,
+        }
+    }
+}
+
+impl Mul<i16> for Score {
+    type Output = Score;
+
+    fn mul(self, rhs: i16) -> Self::Output {
+        Score {
+            mg: self.mg * rhs,
+            eg: self.eg * rhs,
+        }
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, rhs: Score) {
+        self.mg += rhs.mg;
+        self.eg += rhs.eg;
+    }
+}
+
 pub(crate) fn abs_eval_from_color(eval_rel: i16, color: Color) -> Eval {
     // Function provides a global eval struct from a local evaluation
     // specific to one colour, and the colour it is specific to.
@@ -0,0 +1,29 @@
+// Reading and writing settings files, TOML if the file name ends in .toml and JSON otherwise
+
+use std::{fs, io, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("toml")
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn load<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let text = fs::read_to_string(path)?;
+    match is_toml(path) {
+        true => toml::from_str(&text).map_err(|e| invalid(e.to_string())),
+        false => serde_json::from_str(&text).map_err(|e| invalid(e.to_string())),
+    }
+}
+
+pub(crate) fn save<T: Serialize>(value: &T, path: &Path) -> io::Result<()> {
+    let text = match is_toml(path) {
+        true => toml::to_string(value).map_err(|e| invalid(e.to_string()))?,
+        false => serde_json::to_string_pretty(value).map_err(|e| invalid(e.to_string()))?,
+    };
+    fs::write(path, text)
+}
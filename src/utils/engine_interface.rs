@@ -1,14 +1,29 @@
 // Houses the settings interface struct for running the engine
 
-use std::{fmt::Debug, sync::mpsc::Receiver, time::Duration};
+use std::{fmt::Debug, sync::mpsc::Receiver, sync::Arc, time::Duration};
 
+use crate::book::polyglot::{BookSelection, OpeningBook};
+use crate::eval_params::EvalParams;
+use crate::evaluator::Evaluator;
 use crate::managers::cache_manager::CacheInputGrouping;
+use crate::retrograde::DtmTables;
+use crate::search_config::SearchConfig;
+use crate::tablebase::SyzygyTablebase;
 
 pub struct EngineSettings {
     pub cache_settings: Option<CacheInputGrouping>, // Settings to provide an external cache to the simulation
     pub time_limit: Duration, // Search will try to return ASAP after this limit
     pub stop_engine_rcv: Option<Receiver<bool>>, // Stop asap if this channel writes true
     pub verbose: bool,        // Engine will print extra data to stdout
+    pub book: Option<Arc<OpeningBook>>, // Polyglot opening book to consult before searching
+    pub book_selection: BookSelection, // How to choose between book moves
+    pub tablebase: Option<Arc<SyzygyTablebase>>, // Syzygy tables to probe, shared between searches
+    pub dtm_tables: Option<Arc<DtmTables>>, // Generated distance to mate tables, shared between searches
+    pub eval_params: Arc<EvalParams>, // Evaluation weights and tables, e.g. loaded with EvalParams::load
+    pub evaluator: Option<Arc<dyn Evaluator>>, // Replaces the handcrafted evaluation and eval_params
+    pub search_config: Arc<SearchConfig>, // Pruning margins, reductions and extensions
+    pub node_limit: Option<u64>, // Don't start another iteration once this many nodes have been searched
+    pub depth_limit: Option<u8>, // Deepest iteration to run, the engine's own limit if None
 }
 
 impl Debug for EngineSettings {
@@ -17,6 +32,15 @@ impl Debug for EngineSettings {
             .field("time_limit", &self.time_limit)
             .field("stop_engine_rcv", &self.stop_engine_rcv)
             .field("verbose", &self.verbose)
+            .field("book", &self.book.as_ref().map(|book| book.len()))
+            .field("book_selection", &self.book_selection)
+            .field("tablebase", &self.tablebase.as_ref().map(|tb| tb.max_pieces()))
+            .field("dtm_tables", &self.dtm_tables.as_ref().map(|tables| tables.signatures()))
+            .field("eval_params", &self.eval_params)
+            .field("evaluator", &self.evaluator.as_ref().map(|evaluator| evaluator.name()))
+            .field("search_config", &self.search_config)
+            .field("node_limit", &self.node_limit)
+            .field("depth_limit", &self.depth_limit)
             .finish()
     }
 }
@@ -29,6 +53,15 @@ impl Default for EngineSettings {
             time_limit: Duration::from_secs(7), // Use a time limit of 7 sec
             stop_engine_rcv: None,              // Ignore the channel
             verbose: false, // Assume we're using a UCI interface so avoid stdout
+            book: None,                         // No opening book
+            book_selection: BookSelection::WeightedRandom, // Vary our openings
+            tablebase: None,                    // No tablebases
+            dtm_tables: None,
+            eval_params: Arc::new(EvalParams::default()), // The built in weights
+            evaluator: None,                    // The handcrafted evaluation
+            search_config: Arc::new(SearchConfig::default()),
+            node_limit: None,                   // Only the time limit applies
+            depth_limit: None,
         }
     }
 }
@@ -1,3 +1,5 @@
+pub(crate) mod bitboards;
+pub(crate) mod config_file;
 pub mod common;
 pub mod engine_interface;
 pub mod search_interface;
\ No newline at end of file
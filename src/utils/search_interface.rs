@@ -2,18 +2,23 @@
 
 use std::time::{SystemTime, Duration};
 
-use chess::{ChessMove, Color};
+use chess::{ChessMove, Color, Square};
 
 
+use crate::consts;
+use crate::evaluator::Evaluator;
 use crate::managers::cache_manager::CacheInputGrouping;
 use crate::managers::stats_manager::Statistics;
+use crate::retrograde::DtmTables;
+use crate::search_config::SearchConfig;
+use crate::tablebase::SyzygyTablebase;
 
 use super::common::Eval;
 
 pub struct SearchParameters<'a> {
     pub depth: u8,
     pub depth_lim: u8,
-    pub extension: u8,
+    pub extension: u8, // Extension along this path, in fractions of a ply
     pub alpha: i16,
     pub beta: i16,
     pub color: Color,
@@ -21,6 +26,27 @@ pub struct SearchParameters<'a> {
     pub t_start: &'a SystemTime,
     pub t_lim: Duration,
     pub first_search_move: Option<ChessMove>,
+    pub last_capture: Option<Square>, // Square captured on by the move leading to this node
+    pub excluded_move: Option<ChessMove>, // Move to skip, used by the singular extension search
+    pub tablebase: Option<&'a SyzygyTablebase>, // Endgame tables to probe, if loaded
+    pub dtm_tables: Option<&'a DtmTables>, // Generated distance to mate tables to probe
+    pub evaluator: &'a dyn Evaluator, // Static evaluation, told about the moves the search makes
+    pub search_config: &'a SearchConfig, // Pruning margins, reductions and extensions
+}
+
+impl SearchParameters<'_> {
+    pub(crate) fn horizon(&self) -> u8 {
+        // Depth at which the main search hands over to the quiescent search
+        self.depth_lim + self.extension / consts::ONE_PLY
+    }
+}
+
+// How to handle nodes where the TT gives us no move to search first
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalIterativeMode {
+    Disabled,
+    Deepening, // Run a reduced depth search to find a move to search first
+    Reduction, // Search the node one ply shallower
 }
 
 pub struct SearchOutput {
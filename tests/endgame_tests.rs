@@ -130,4 +130,234 @@ mod tests{
     fn pawn_mate_2() {
         check_solve!("r1b3nr/ppp3qp/1bnpk3/4p1BQ/3PP3/2P5/PP3PPP/RN3RK1 w - - 0 11", "h5e8")
     }
-}
\ No newline at end of file
+
+    // Tablebase tests need Syzygy files, point SYZYGY_PATH at a directory with at least the 3 piece tables
+    // and run with `cargo test -- --ignored`
+
+    fn syzygy_path() -> std::path::PathBuf {
+        std::path::PathBuf::from(std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH is not set"))
+    }
+
+    #[test]
+    #[ignore]
+    #[serial_test::serial]
+    fn tablebase_root_move() {
+        use std::sync::Arc;
+
+        use shallow_red_engine::tablebase::SyzygyTablebase;
+
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let tablebase = SyzygyTablebase::open(&syzygy_path()).unwrap();
+        let settings = EngineSettings {
+            tablebase: Some(Arc::new(tablebase)),
+            ..Default::default()
+        };
+        let (eng_move, eng_return) = enter_engine(board, settings);
+        assert_eq!(eng_move.to_string(), "g1g8");
+        assert_eq!(eng_return.unwrap().engine_search_stats.unwrap().tb_hits, 1);
+    }
+
+    #[test]
+    #[ignore]
+    #[serial_test::serial]
+    fn tablebase_search_probes() {
+        use std::{sync::Arc, time::SystemTime};
+
+        use parking_lot::RwLock;
+        use shallow_red_engine::{
+            evaluator::HandcraftedEvaluator,
+            managers::cache_manager::{Cache, CacheInputGrouping},
+            search::find_best_move,
+            search_config::SearchConfig,
+            tablebase::SyzygyTablebase,
+            utils::search_interface::SearchParameters,
+        };
+
+        // Every child of a KQK position is in the tables
+        let tablebase = SyzygyTablebase::open(&syzygy_path()).unwrap();
+        let board = Board::from_str("8/8/8/4k3/8/8/8/K6Q w - - 0 1").unwrap();
+        let (cache_tx, _cache_rx) = Cache::generate_channel();
+        let t_start = SystemTime::now();
+        let result = find_best_move(
+            board,
+            SearchParameters {
+                depth: 0,
+                depth_lim: 2,
+                extension: 0,
+                alpha: i16::MIN + 1,
+                beta: i16::MAX - 1,
+                color: board.side_to_move(),
+                cache: CacheInputGrouping {
+                    cache_ref: Arc::new(RwLock::new(Cache::default())),
+                    cache_tx,
+                },
+                t_start: &t_start,
+                t_lim: Duration::from_secs(60),
+                first_search_move: None,
+                last_capture: None,
+                excluded_move: None,
+                tablebase: Some(&tablebase),
+                dtm_tables: None,
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
+            },
+        )
+        .unwrap();
+        assert!(result.node_stats.tb_hits > 0);
+        assert!(result.node_eval.score > 10000);
+    }
+
+    // Generated distance to mate tables
+
+    #[test]
+    #[serial_test::serial]
+    fn dtm_root_move() {
+        use std::sync::Arc;
+
+        use shallow_red_engine::retrograde::DtmTables;
+
+        let mut tables = DtmTables::new();
+        tables.generate(&"KQvK".parse().unwrap()).unwrap();
+        let board = Board::from_str("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let settings = EngineSettings {
+            dtm_tables: Some(Arc::new(tables)),
+            ..Default::default()
+        };
+        let (eng_move, eng_return) = enter_engine(board, settings);
+        assert_eq!(eng_move.to_string(), "g1g8");
+        assert_eq!(eng_return.unwrap().engine_search_stats.unwrap().tb_hits, 1);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn dtm_search_probes() {
+        use std::{sync::Arc, time::SystemTime};
+
+        use parking_lot::RwLock;
+        use shallow_red_engine::{
+            evaluator::HandcraftedEvaluator,
+            managers::cache_manager::{Cache, CacheInputGrouping},
+            retrograde::DtmTables,
+            search::find_best_move,
+            search_config::SearchConfig,
+            utils::search_interface::SearchParameters,
+        };
+
+        let mut tables = DtmTables::new();
+        tables.generate(&"KRvK".parse().unwrap()).unwrap();
+        let board = Board::from_str("8/8/8/4k3/8/8/8/K6R w - - 0 1").unwrap();
+        let (cache_tx, _cache_rx) = Cache::generate_channel();
+        let t_start = SystemTime::now();
+        let result = find_best_move(
+            board,
+            SearchParameters {
+                depth: 0,
+                depth_lim: 2,
+                extension: 0,
+                alpha: i16::MIN + 1,
+                beta: i16::MAX - 1,
+                color: board.side_to_move(),
+                cache: CacheInputGrouping {
+                    cache_ref: Arc::new(RwLock::new(Cache::default())),
+                    cache_tx,
+                },
+                t_start: &t_start,
+                t_lim: Duration::from_secs(60),
+                first_search_move: None,
+                last_capture: None,
+                excluded_move: None,
+                tablebase: None,
+                dtm_tables: Some(&tables),
+                evaluator: &HandcraftedEvaluator::default(),
+                search_config: &SearchConfig::default(),
+            },
+        )
+        .unwrap();
+        // Every child is looked up and scored as a forced mate
+        assert!(result.node_stats.tb_hits > 0);
+        assert!(result.node_eval.score > i16::MAX - 100);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn dtm_three_piece_mates() {
+        use shallow_red_engine::retrograde::{Dtm, DtmTables};
+
+        let mut tables = DtmTables::new();
+        tables.generate(&"KQvK".parse().unwrap()).unwrap();
+        tables.generate(&"KRvK".parse().unwrap()).unwrap();
+        let solve = |fen: &str| {
+            let board = Board::from_str(fen).unwrap();
+            let (best_move, result) = tables.best_move(&board).unwrap();
+            assert_eq!(tables.probe(&board), Some(result));
+            (best_move.to_string(), result)
+        };
+
+        // Mates in one, the best move has to be the mate
+        assert_eq!(solve("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"), ("g1g8".to_string(), Dtm::Win(1)));
+        assert_eq!(solve("1k6/8/1K6/8/8/8/8/7R w - - 0 1"), ("h1h8".to_string(), Dtm::Win(1)));
+
+        // The positions the mating and probing tests start from
+        assert_eq!(solve("8/8/8/8/8/8/2k5/K6Q w - - 0 1").1, Dtm::Win(9));
+        assert_eq!(solve("7k/8/8/8/8/8/8/R3K3 w - - 0 1").1, Dtm::Win(19));
+        assert_eq!(solve("8/8/8/4k3/8/8/8/K6R w - - 0 1").1, Dtm::Win(29));
+    }
+
+    #[test]
+    #[ignore] // Generates several four piece tables, run with --release
+    #[serial_test::serial]
+    fn dtm_anderssens_mate_1() {
+        use shallow_red_engine::retrograde::{Dtm, DtmTables};
+
+        let mut tables = DtmTables::new();
+        tables.generate(&"KRPvK".parse().unwrap()).unwrap();
+        let board = Board::from_str("6k1/6P1/5K1R/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(tables.probe(&board), Some(Dtm::Win(1)));
+        let (best_move, result) = tables.best_move(&board).unwrap();
+        assert_eq!(best_move.to_string(), "h6h8");
+        assert_eq!(result, Dtm::Win(1));
+    }
+
+    // Basic mates against perfect defence, the engine has to mate within the longest mate possible
+    // for the material
+
+    fn mates_within(fen: &str, signature: &str, moves: usize) {
+        use shallow_red_engine::retrograde::DtmTables;
+
+        let mut tables = DtmTables::new();
+        tables.generate(&signature.parse().unwrap()).unwrap();
+        let mut board = Board::from_str(fen).unwrap();
+        for _ in 0..moves {
+            let settings = EngineSettings {time_limit: Duration::from_secs(1), ..Default::default()};
+            let (eng_move, _) = enter_engine(board, settings);
+            board = board.make_move_new(eng_move);
+            if board.status() == chess::BoardStatus::Checkmate {
+                return;
+            }
+            assert_eq!(board.status(), chess::BoardStatus::Ongoing, "stalemate after {}", eng_move);
+            let (reply, _) = tables.best_move(&board).unwrap();
+            board = board.make_move_new(reply);
+        }
+        panic!("no mate within {} moves, reached {}", moves, board);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn kqk_mate() {
+        mates_within("8/8/8/8/8/8/2k5/K6Q w - - 0 1", "KQvK", 10)
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn krk_mate() {
+        mates_within("7k/8/8/8/8/8/8/R3K3 w - - 0 1", "KRvK", 16)
+    }
+
+    #[test]
+    #[ignore] // Generates the four piece table, run with --release
+    #[serial_test::serial]
+    fn kbnk_mate() {
+        // Has to walk the king over from the middle of the back rank towards a1 or h8
+        mates_within("3k4/8/3K4/8/8/8/8/2B1N3 w - - 0 1", "KBNvK", 33)
+    }
+}
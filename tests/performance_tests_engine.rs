@@ -1,11 +1,22 @@
 use parking_lot::RwLock;
-use std::{str::FromStr, sync::Arc, thread};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use chess::Board;
 use shallow_red_engine::{
     engine::enter_engine,
+    evaluator::HandcraftedEvaluator,
+    search_config::SearchConfig,
     managers::cache_manager::{Cache, CacheInputGrouping},
-    utils::engine_interface::EngineSettings,
+    search::find_best_move,
+    utils::{
+        engine_interface::EngineSettings,
+        search_interface::{InternalIterativeMode, SearchParameters},
+    },
 };
 
 #[test]
@@ -36,12 +47,101 @@ fn test_external_cache() {
     let _cache_thread_hndl =
         thread::spawn(move || Cache::cache_manager_server(cache_arc_thread, cache_rx));
 
-    let mut settings = EngineSettings::default();
-    settings.cache_settings = Some(CacheInputGrouping {
-        cache_ref: cache_arc,
-        cache_tx,
-    });
+    let settings = EngineSettings {
+        cache_settings: Some(CacheInputGrouping {
+            cache_ref: cache_arc,
+            cache_tx,
+        }),
+        ..Default::default()
+    };
 
     let board: Board = Board::from_str("3r4/8/3k4/8/8/3K4/8/8 b - - 0 1").unwrap();
     enter_engine(board, settings);
 }
+
+fn benchmark_nodes(config: &SearchConfig) -> i32 {
+    // Fixed depth searches over a few middle game positions, returning the nodes searched
+    let fens = [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "r1k2b1r/ppp1nNpp/2p5/4P3/5Bb1/2N5/PPP2P1P/R4RK1 b - - 0 1",
+        "r4rk1/pq3ppp/2p5/2PpP3/2pP4/P1P3R1/4QPPP/R5K1 b - - 0 1",
+    ];
+
+    let mut total_nodes = 0;
+    for fen in fens {
+        let board = Board::from_str(fen).unwrap();
+
+        let cache_arc = Arc::new(RwLock::new(Cache::default()));
+        let cache_arc_thread = cache_arc.clone();
+        let (cache_tx, cache_rx) = Cache::generate_channel();
+        let _cache_thread_hndl =
+            thread::spawn(move || Cache::cache_manager_server(cache_arc_thread, cache_rx));
+        let cache = CacheInputGrouping {
+            cache_ref: cache_arc,
+            cache_tx,
+        };
+
+        let t_start = SystemTime::now();
+        let mut first_search_move = None;
+        let mut nodes = 0;
+        for depth_lim in 1..=5 {
+            let result = find_best_move(
+                board,
+                SearchParameters {
+                    depth: 0,
+                    depth_lim,
+                    extension: 0,
+                    alpha: i16::MIN + 1,
+                    beta: i16::MAX - 1,
+                    color: board.side_to_move(),
+                    cache: cache.clone(),
+                    t_start: &t_start,
+                    t_lim: Duration::from_secs(600),
+                    first_search_move,
+                    last_capture: None,
+                    excluded_move: None,
+                    tablebase: None,
+                    dtm_tables: None,
+                    evaluator: &HandcraftedEvaluator::default(),
+                    search_config: config,
+                },
+            )
+            .unwrap();
+            assert!(board.legal(result.best_move));
+            first_search_move = Some(result.best_move);
+            nodes += result.node_stats.searched_nodes;
+        }
+        println!(
+            "{:?} {fen}: {nodes} nodes, best move {}",
+            config.internal_iterative_mode,
+            first_search_move.unwrap()
+        );
+        total_nodes += nodes;
+    }
+    total_nodes
+}
+
+#[test]
+#[ignore] // Benchmark, run with `cargo test --release -- --ignored`
+#[serial_test::serial]
+fn test_node_count_benchmark() {
+    // Node counts with each way of handling nodes without a hash move, so search changes can be
+    // compared run to run. Either way of finding a first move has to pay for itself, the best
+    // moves are only printed as reductions can settle on a different move of about equal worth
+    let nodes = [
+        InternalIterativeMode::Disabled,
+        InternalIterativeMode::Reduction,
+        InternalIterativeMode::Deepening,
+    ]
+    .map(|mode| {
+        let config = SearchConfig {
+            internal_iterative_mode: mode,
+            ..Default::default()
+        };
+        let nodes = benchmark_nodes(&config);
+        println!("{mode:?}: {nodes} nodes searched");
+        nodes
+    });
+    assert!(nodes[1] < nodes[0]);
+    assert!(nodes[2] < nodes[0]);
+}